@@ -0,0 +1,52 @@
+// 本地 Webhook 接收桩，用于联调卡密生命周期事件的投递与签名
+//
+// 运行: WEBHOOK_SECRET=whsec_xxx cargo run --example webhook_receiver
+// 然后将 Webhook URL 配置为 http://127.0.0.1:5010/webhook
+// 设置 FAIL_STATUS=500 可模拟接收方故障，观察重试与退避
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+async fn receive(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string()
+    };
+    let event = header("X-Webhook-Event");
+    let delivery = header("X-Webhook-Delivery");
+    let timestamp = header("X-Webhook-Timestamp");
+    let signature = header("X-Webhook-Signature");
+    let payload = String::from_utf8_lossy(&body);
+
+    let verified = match std::env::var("WEBHOOK_SECRET") {
+        Ok(secret) => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+            mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+            let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+            Some(expected == signature)
+        }
+        Err(_) => None,
+    };
+
+    println!("event={} delivery={} signature_valid={:?}\n{}\n", event, delivery, verified, payload);
+
+    match std::env::var("FAIL_STATUS").ok().and_then(|s| s.parse::<u16>().ok()) {
+        Some(status) => HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).finish(),
+        None if verified == Some(false) => HttpResponse::Unauthorized().body("invalid signature"),
+        None => HttpResponse::Ok().finish(),
+    }
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let addr = std::env::var("WEBHOOK_RECEIVER_ADDR").unwrap_or_else(|_| "127.0.0.1:5010".to_string());
+    println!("Webhook receiver listening at http://{}/webhook", addr);
+    HttpServer::new(|| App::new().route("/webhook", web::post().to(receive)))
+        .bind(addr)?
+        .run()
+        .await
+}
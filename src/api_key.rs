@@ -0,0 +1,135 @@
+use actix_web::{http::Method, HttpRequest};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, warn};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::Database;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::client_ip::{self, ClientIp};
+use crate::models::api_key::{ApiKey, ApiKeyScope, API_KEY_PREFIX};
+use crate::models::user::User;
+
+// 密钥随机部分的长度
+const KEY_LENGTH: usize = 40;
+// 列表中展示的密钥开头长度（含前缀）
+const DISPLAY_PREFIX_LEN: usize = 12;
+// 最近使用时间的更新间隔，避免每次请求都写库
+const LAST_USED_UPDATE_SECS: i64 = 60;
+// API 版本前缀，校验权限时去掉后得到资源路径
+const API_PATH_PREFIXES: &[&str] = &["/api/v2/", "/api/v1/", "/api/"];
+
+// 生成新密钥，返回 (明文密钥, 哈希, 展示用前缀)
+pub fn generate_key() -> (String, String, String) {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(KEY_LENGTH)
+        .map(char::from)
+        .collect();
+    let key = format!("{}{}", API_KEY_PREFIX, random);
+    let prefix = key[..DISPLAY_PREFIX_LEN].to_string();
+    (key.clone(), hash_key(&key), prefix)
+}
+
+// 密钥的 SHA-256 十六进制哈希；密钥本身是高熵随机串，无需加盐
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// 校验 IP 白名单中的各项，返回第一个无效项
+pub fn invalid_allowed_ip(allowed_ips: &[String]) -> Option<&String> {
+    allowed_ips.iter().find(|item| client_ip::parse_net(item).is_none())
+}
+
+// 根据请求路径和方法计算所需的权限范围；API 密钥管理等不在列表中的资源不允许使用 API 密钥访问
+fn required_scope(req: &HttpRequest) -> Option<ApiKeyScope> {
+    let path = req.path();
+    let rest = API_PATH_PREFIXES.iter().find_map(|prefix| path.strip_prefix(prefix))?;
+    let resource = rest.split('/').next().unwrap_or("");
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD);
+    ApiKeyScope::required(resource, write)
+}
+
+// 验证 API 密钥，通过后返回密钥记录和所属用户
+pub async fn authenticate(
+    key: &str,
+    req: &HttpRequest,
+    client_ip: ClientIp,
+    db: &Database,
+) -> Result<(ApiKey, User), String> {
+    let collection = db.collection::<ApiKey>("api_keys");
+    let api_key = match collection.find_one(doc! { "key_hash": hash_key(key) }, None).await {
+        Ok(Some(api_key)) => api_key,
+        Ok(None) => return Err("Unknown API key".to_string()),
+        Err(e) => {
+            error!("Failed to look up API key: {}", e);
+            return Err("Failed to validate API key".to_string());
+        }
+    };
+
+    let now = Utc::now();
+    if api_key.revoked_at_str.is_some() {
+        return Err("API key has been revoked".to_string());
+    }
+    if let Some(expires_at) = &api_key.expires_at_str {
+        match DateTime::parse_from_rfc3339(expires_at) {
+            Ok(expires_at) if expires_at > now => {}
+            _ => return Err("API key has expired".to_string()),
+        }
+    }
+    if !api_key.allowed_ips.is_empty()
+        && !api_key
+            .allowed_ips
+            .iter()
+            .filter_map(|item| client_ip::parse_net(item))
+            .any(|net| net.contains(&client_ip.0))
+    {
+        warn!("API key '{}' used from disallowed IP {}", api_key.key_prefix, client_ip);
+        return Err("Client IP is not allowed for this API key".to_string());
+    }
+    match required_scope(req) {
+        Some(scope) if api_key.scopes.contains(&scope) => {}
+        _ => return Err("API key does not have the required scope".to_string()),
+    }
+
+    // 使用所属用户当前的角色，用户被删除后密钥随之失效
+    let user_id = ObjectId::parse_str(&api_key.user_id).map_err(|_| "Invalid API key owner".to_string())?;
+    let user = match db
+        .collection::<User>("users")
+        .find_one(doc! { "_id": user_id, "deleted_at_str": null }, None)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err("API key owner no longer exists".to_string()),
+        Err(e) => {
+            error!("Failed to look up API key owner: {}", e);
+            return Err("Failed to validate API key".to_string());
+        }
+    };
+
+    touch(db, &api_key, client_ip, now).await;
+    Ok((api_key, user))
+}
+
+// 更新最近使用时间和IP，距上次更新不足 LAST_USED_UPDATE_SECS 且IP未变时跳过
+async fn touch(db: &Database, api_key: &ApiKey, client_ip: ClientIp, now: DateTime<Utc>) {
+    let ip = client_ip.to_string();
+    let recent = api_key
+        .last_used_at_str
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .is_some_and(|last| now.signed_duration_since(last) < Duration::seconds(LAST_USED_UPDATE_SECS));
+    if recent && api_key.last_used_ip.as_deref() == Some(ip.as_str()) {
+        return;
+    }
+
+    let update = doc! { "$set": { "last_used_at_str": now.to_rfc3339(), "last_used_ip": ip } };
+    if let Err(e) = db
+        .collection::<ApiKey>("api_keys")
+        .update_one(doc! { "_id": api_key.id }, update, None)
+        .await
+    {
+        error!("Failed to update API key last used time: {}", e);
+    }
+}
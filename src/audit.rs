@@ -0,0 +1,66 @@
+use log::{error, info};
+use mongodb::Database;
+use std::sync::{Arc, Mutex};
+
+use crate::auth::AuthenticatedUser;
+use crate::models::audit::AuditLog;
+use crate::shutdown::Shutdown;
+
+// 缓冲中的审计日志写入数据库的间隔
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+// 审计日志记录器：处理函数只将记录放入内存缓冲，由后台任务批量写入 audit_logs，停机时写入剩余的记录
+#[derive(Clone)]
+pub struct AuditLogger {
+    db: Database,
+    pending: Arc<Mutex<Vec<AuditLog>>>,
+}
+
+impl AuditLogger {
+    pub fn new(db: Database) -> Self {
+        Self { db, pending: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    // 记录一次管理操作
+    pub fn record(&self, user: &AuthenticatedUser, action: &str, target: Option<String>, detail: Option<serde_json::Value>) {
+        self.pending.lock().unwrap().push(AuditLog::new(user, action, target, detail));
+    }
+
+    // 将缓冲中的记录写入数据库，写入失败时放回缓冲等待下次重试；返回写入的数量
+    pub async fn flush(&self) -> Result<usize, mongodb::error::Error> {
+        let logs = std::mem::take(&mut *self.pending.lock().unwrap());
+        if logs.is_empty() {
+            return Ok(0);
+        }
+        match self.db.collection::<AuditLog>("audit_logs").insert_many(&logs, None).await {
+            Ok(_) => Ok(logs.len()),
+            Err(e) => {
+                let mut pending = self.pending.lock().unwrap();
+                let newer = std::mem::replace(&mut *pending, logs);
+                pending.extend(newer);
+                Err(e)
+            }
+        }
+    }
+
+    // 启动后台写入任务
+    pub fn spawn_writer(&self, shutdown: Shutdown) {
+        let audit = self.clone();
+        actix_web::rt::spawn(async move {
+            info!("Audit log writer started");
+            let mut interval = actix_web::rt::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                // 停机后由 flush 接管剩余的记录
+                if shutdown.is_requested() {
+                    break;
+                }
+                let _active = shutdown.track();
+                if let Err(e) = audit.flush().await {
+                    error!("Failed to write audit logs: {}", e);
+                }
+            }
+            info!("Audit log writer stopped");
+        });
+    }
+}
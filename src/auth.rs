@@ -1,5 +1,5 @@
 use actix_web::{
-    dev::Payload, error::ErrorUnauthorized, http::header, Error, FromRequest, HttpRequest, web,
+    dev::Payload, error::ErrorUnauthorized, http::header, Error, FromRequest, HttpMessage, HttpRequest, web,
 };
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::{Duration, Utc};
@@ -9,7 +9,14 @@ use serde::{Deserialize, Serialize};
 use mongodb::Database;
 use mongodb::bson::{doc, oid::ObjectId};
 use std::pin::Pin;
+use utoipa::ToSchema;
 
+use crate::api_key;
+use crate::client_ip::ClientIp;
+use crate::config::AuthConfig;
+use crate::middleware::RequestContext;
+use crate::models::api_key::API_KEY_PREFIX;
+use crate::models::customer::Customer;
 use crate::models::user::{User, UserRole};
 use crate::AppState;
 
@@ -22,14 +29,71 @@ pub struct Claims {
     pub exp: usize,         // 过期时间戳
 }
 
+// 客户令牌的受众，用于区分客户令牌和员工令牌
+const CUSTOMER_AUDIENCE: &str = "customer";
+
+// 客户 JWT 令牌的声明结构
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomerClaims {
+    pub sub: String,    // 客户ID
+    pub email: String,  // 客户邮箱
+    pub aud: String,    // 固定为 customer，员工令牌不能用于客户接口，反之亦然
+    pub exp: usize,     // 过期时间戳
+}
+
+// 已认证的终端客户
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuthenticatedCustomer {
+    pub customer_id: String,
+    pub email: String,
+}
+
+// 实现 FromRequest trait，使 AuthenticatedCustomer 可以作为客户接口的请求提取器
+impl FromRequest for AuthenticatedCustomer {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let app_state = req.app_data::<web::Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let (Some(state), Some(token)) = (
+                app_state,
+                req.headers()
+                    .get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer ")),
+            ) else {
+                return Err(ErrorUnauthorized("Authorization header missing or invalid"));
+            };
+
+            match validate_customer_token(token, &state.db, &state.config.auth).await {
+                Ok(claims) => {
+                    if let Some(context) = req.extensions().get::<RequestContext>() {
+                        context.set_user(&claims.email);
+                    }
+                    Ok(AuthenticatedCustomer { customer_id: claims.sub, email: claims.email })
+                }
+                Err(e) => Err(ErrorUnauthorized(format!("Invalid token: {}", e))),
+            }
+        })
+    }
+}
+
 // 已认证用户的结构体
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthenticatedUser {
     pub user_id: String,    // 用户ID
     pub username: String,   // 用户名
     pub role: UserRole,     // 用户角色
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<String>,  // 通过 API 密钥认证时的密钥ID
 }
 
+// 通过 API 密钥认证时使用的请求头，也可以使用 Authorization: Bearer cpk_...
+const API_KEY_HEADER: &str = "x-api-key";
+
 // 实现 FromRequest trait，使 AuthenticatedUser 可以作为请求提取器
 impl FromRequest for AuthenticatedUser {
     type Error = Error;
@@ -40,34 +104,59 @@ impl FromRequest for AuthenticatedUser {
         let app_state = req.app_data::<web::Data<AppState>>().cloned();
         
         Box::pin(async move {
-            // 从请求头中获取 Authorization 头
-            let auth_header = req.headers().get(header::AUTHORIZATION);
-            if let Some(auth_header) = auth_header {
-                if let Ok(auth_str) = auth_header.to_str() {
-                    // 检查是否是 Bearer 令牌
-                    if auth_str.starts_with("Bearer ") {
-                        let token = &auth_str[7..];
-                        
-                        // 获取应用状态
-                        if let Some(state) = app_state {
-                            // 验证令牌
-                            match validate_token(token, &state.db).await {
-                                Ok(claims) => {
-                                    return Ok(AuthenticatedUser {
-                                        user_id: claims.sub,
-                                        username: claims.username,
-                                        role: claims.role,
-                                    });
-                                }
-                                Err(e) => {
-                                    // 提供更详细的错误信息
-                                    return Err(ErrorUnauthorized(format!("Invalid token: {}", e)));
-                                }
-                            }
+            // 获取应用状态
+            let state = match app_state {
+                Some(state) => state,
+                None => return Err(ErrorUnauthorized("Authorization header missing or invalid")),
+            };
+
+            // 从 X-API-Key 或 Authorization 头中获取凭据
+            let api_key = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+            let bearer = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+
+            // API 密钥
+            if let Some(key) = api_key.or(bearer.filter(|token| token.starts_with(API_KEY_PREFIX))) {
+                let Ok(client_ip) = ClientIp::extract(&req).await;
+                return match api_key::authenticate(key, &req, client_ip, &state.db).await {
+                    Ok((api_key, user)) => {
+                        if let Some(context) = req.extensions().get::<RequestContext>() {
+                            context.set_user(&user.username);
                         }
+                        Ok(AuthenticatedUser {
+                            user_id: api_key.user_id,
+                            username: user.username,
+                            role: user.role,
+                            api_key_id: api_key.id.map(|id| id.to_hex()),
+                        })
                     }
-                }
+                    Err(e) => Err(ErrorUnauthorized(format!("Invalid API key: {}", e))),
+                };
             }
+
+            // 检查是否是 Bearer 令牌
+            if let Some(token) = bearer {
+                // 验证令牌
+                return match validate_token(token, &state.db, &state.config.auth).await {
+                    Ok(claims) => {
+                        if let Some(context) = req.extensions().get::<RequestContext>() {
+                            context.set_user(&claims.username);
+                        }
+                        Ok(AuthenticatedUser {
+                            user_id: claims.sub,
+                            username: claims.username,
+                            role: claims.role,
+                            api_key_id: None,
+                        })
+                    }
+                    // 提供更详细的错误信息
+                    Err(e) => Err(ErrorUnauthorized(format!("Invalid token: {}", e))),
+                };
+            }
+
             // 如果没有有效的令牌，返回未授权错误
             Err(ErrorUnauthorized("Authorization header missing or invalid"))
         })
@@ -85,18 +174,12 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::Bcryp
 }
 
 // 生成 JWT 令牌
-pub async fn generate_token(user: &User, db: &Database) -> Result<String, jsonwebtoken::errors::Error> {
-    // 从环境变量获取令牌过期时间
-    let jwt_expiration_hours = std::env::var("JWT_EXPIRATION_HOURS")
-        .unwrap_or_else(|_| "24".to_string())
-        .parse::<i64>()
-        .unwrap_or(24);
-    
-    // 计算过期时间戳
-    let expiration = Utc::now()
-        .checked_add_signed(Duration::hours(jwt_expiration_hours))
-        .expect("valid timestamp")
-        .timestamp();
+pub async fn generate_token(user: &User, db: &Database, config: &AuthConfig) -> Result<String, jsonwebtoken::errors::Error> {
+    // 计算过期时间
+    let expires_at = Utc::now()
+        .checked_add_signed(Duration::hours(config.jwt_expiration_hours))
+        .expect("valid timestamp");
+    let expiration = expires_at.timestamp();
 
     // 创建声明
     let claims = Claims {
@@ -106,19 +189,17 @@ pub async fn generate_token(user: &User, db: &Database) -> Result<String, jsonwe
         exp: expiration as usize,
     };
 
-    // 从环境变量获取密钥
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your_jwt_secret".to_string());
     // 编码令牌
     let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
     )?;
     
     // 更新用户的最新令牌
     let collection = db.collection::<User>("users");
     let filter = doc! { "_id": user.id.unwrap() };
-    let update = doc! { "$set": { "last_token": &token } };
+    let update = doc! { "$set": { "last_token": &token, "token_expires_at_str": expires_at.to_rfc3339() } };
     
     match collection.update_one(filter, update, None).await {
         Ok(_) => Ok(token),
@@ -130,12 +211,11 @@ pub async fn generate_token(user: &User, db: &Database) -> Result<String, jsonwe
 }
 
 // 验证 JWT 令牌
-pub async fn validate_token(token: &str, db: &Database) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your_jwt_secret".to_string());
+pub async fn validate_token(token: &str, db: &Database, config: &AuthConfig) -> Result<Claims, jsonwebtoken::errors::Error> {
     let validation = Validation::default();
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(secret.as_bytes()),
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
         &validation,
     )?;
     
@@ -149,7 +229,8 @@ pub async fn validate_token(token: &str, db: &Database) -> Result<Claims, jsonwe
         Err(_) => return Err(jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)),
     };
     
-    let filter = doc! { "_id": object_id };
+    // 已删除的用户令牌失效
+    let filter = doc! { "_id": object_id, "deleted_at_str": null };
     match collection.find_one(filter, None).await {
         Ok(Some(user)) => {
             // 检查令牌是否是用户的最新令牌
@@ -164,6 +245,40 @@ pub async fn validate_token(token: &str, db: &Database) -> Result<Claims, jsonwe
     }
 }
 
+// 生成客户 JWT 令牌，有效期与员工令牌相同
+pub fn generate_customer_token(customer: &Customer, config: &AuthConfig) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = Utc::now()
+        .checked_add_signed(Duration::hours(config.jwt_expiration_hours))
+        .expect("valid timestamp")
+        .timestamp();
+
+    let claims = CustomerClaims {
+        sub: customer.id.unwrap().to_hex(),
+        email: customer.email.clone(),
+        aud: CUSTOMER_AUDIENCE.to_string(),
+        exp: expiration as usize,
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(config.jwt_secret.as_bytes()))
+}
+
+// 验证客户 JWT 令牌，客户账号不存在时令牌失效
+pub async fn validate_customer_token(token: &str, db: &Database, config: &AuthConfig) -> Result<CustomerClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[CUSTOMER_AUDIENCE]);
+    let token_data = decode::<CustomerClaims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &validation,
+    )?;
+
+    let invalid = || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
+    let object_id = ObjectId::parse_str(&token_data.claims.sub).map_err(|_| invalid())?;
+    match db.collection::<Customer>("customers").find_one(doc! { "_id": object_id }, None).await {
+        Ok(Some(_)) => Ok(token_data.claims),
+        _ => Err(invalid()),
+    }
+}
+
 // 检查用户是否为管理员
 pub fn require_admin(user: AuthenticatedUser) -> Result<AuthenticatedUser, Error> {
     if user.role == UserRole::Admin {
@@ -0,0 +1,348 @@
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use futures::stream::TryStreamExt;
+use log::info;
+use mongodb::bson::{doc, Document};
+use mongodb::Database;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::auth::hash_password;
+use crate::config::Config;
+use crate::handlers::application::find_application;
+use crate::handlers::card::{cards_to_csv, new_batch};
+use crate::handlers::stats::compute_stats;
+use crate::migrations::{self, MIGRATIONS};
+use crate::models::stats::StatsQuery;
+use crate::models::duration::parse_duration;
+use crate::models::{Card, CardQuery, CardSpec, CardStatus, User, UserRole};
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+// 命令行参数，未指定子命令时启动 HTTP 服务
+#[derive(Debug, Parser)]
+#[command(version, about = "Card platform server and management tools")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (default)
+    Serve,
+    /// Create an admin user
+    CreateAdmin(CredentialsArgs),
+    /// Reset a user's password and sign out their sessions
+    ResetPassword(CredentialsArgs),
+    /// Generate a batch of cards and write them to a CSV file
+    GenerateCards(GenerateCardsArgs),
+    /// Export cards to a CSV file
+    Export(ExportArgs),
+    /// Apply pending database migrations
+    Migrate(MigrateArgs),
+    /// Print card statistics as JSON
+    Stats(StatsArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CredentialsArgs {
+    /// Username
+    #[arg(long)]
+    pub username: String,
+    /// Password; read from stdin when omitted
+    #[arg(long)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateCardsArgs {
+    /// Validity period such as 30m, 2h or 7d (a bare number means days); defaults to the
+    /// application's default plan when --app-id is given. Use 0 together with --uses for cards without a time limit
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Option<i64>,
+    /// Number of uses per card; cards are metered by usage when given
+    #[arg(long)]
+    pub uses: Option<i64>,
+    /// Shelf life such as 90d; cards not activated within it expire on their own
+    #[arg(long, value_parser = parse_duration)]
+    pub shelf_life: Option<i64>,
+    /// Cards cannot be verified or activated before this RFC3339 time
+    #[arg(long)]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// Cards cannot be activated after this RFC3339 time
+    #[arg(long)]
+    pub activate_before: Option<DateTime<Utc>>,
+    /// Cards expire at this RFC3339 time regardless of when they are activated;
+    /// --duration may be 0 when given
+    #[arg(long)]
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Number of cards to generate
+    #[arg(long, default_value_t = 1)]
+    pub count: i32,
+    /// Output CSV file
+    #[arg(long)]
+    pub out: PathBuf,
+    /// Card number length; defaults to DEFAULT_CARD_LENGTH
+    #[arg(long)]
+    pub length: Option<usize>,
+    /// Username to record as the creator of the cards
+    #[arg(long)]
+    pub created_by: Option<String>,
+    /// Application the cards belong to
+    #[arg(long)]
+    pub app_id: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Output CSV file; written to stdout when omitted
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+    /// Only export cards with this status (unused, active, expired, revoked)
+    #[arg(long, value_parser = parse_card_status)]
+    pub status: Option<CardStatus>,
+    /// Only export cards from this batch
+    #[arg(long)]
+    pub batch_id: Option<String>,
+    /// Only export cards created by this username
+    #[arg(long)]
+    pub created_by: Option<String>,
+    /// Only export cards belonging to this application
+    #[arg(long)]
+    pub app_id: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateArgs {
+    /// List migrations and whether they have been applied, without running them
+    #[arg(long)]
+    pub status: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// Days of daily statistics to include
+    #[arg(long)]
+    pub days: Option<i64>,
+    /// Window in days for cards about to expire
+    #[arg(long)]
+    pub expiring_days: Option<i64>,
+    /// Number of entries in rankings
+    #[arg(long)]
+    pub top: Option<i64>,
+    /// Only include cards belonging to this application
+    #[arg(long)]
+    pub app_id: Option<String>,
+}
+
+// 生成卡密数量上限，避免误操作一次写入过多数据
+const MAX_GENERATE_COUNT: i32 = 100_000;
+
+fn parse_card_status(value: &str) -> Result<CardStatus, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map_err(|_| format!("invalid status '{}', expected unused, active, expired or revoked", value))
+}
+
+// 执行除 serve 以外的管理命令
+pub async fn run(command: Command, db: &Database, config: &Config) -> CliResult {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::CreateAdmin(args) => create_admin(db, args).await,
+        Command::ResetPassword(args) => reset_password(db, config, args).await,
+        Command::GenerateCards(args) => generate_cards(db, config, args).await,
+        Command::Export(args) => export(db, args).await,
+        Command::Migrate(args) => migrate(db, args).await,
+        Command::Stats(args) => stats(db, args).await,
+    }
+}
+
+// 未通过参数传入密码时从标准输入读取一行，避免密码出现在 shell 历史中
+fn read_password(password: Option<String>) -> Result<String, Box<dyn Error>> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            eprint!("Password: ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        return Err("Password must not be empty".into());
+    }
+    Ok(password)
+}
+
+async fn find_user(db: &Database, username: &str) -> Result<User, Box<dyn Error>> {
+    db.collection::<User>("users")
+        .find_one(doc! { "username": username, "deleted_at_str": null }, None)
+        .await?
+        .ok_or_else(|| format!("User '{}' not found", username).into())
+}
+
+async fn create_admin(db: &Database, args: CredentialsArgs) -> CliResult {
+    let collection = db.collection::<User>("users");
+    if collection.find_one(doc! { "username": &args.username }, None).await?.is_some() {
+        return Err(format!("Username '{}' already exists", args.username).into());
+    }
+
+    let password_hash = hash_password(&read_password(args.password)?)?;
+    let admin = User::new(args.username.clone(), password_hash, None, UserRole::Admin);
+    collection.insert_one(&admin, None).await?;
+
+    info!("Admin user '{}' created from command line", args.username);
+    println!("Admin user '{}' created", args.username);
+    Ok(())
+}
+
+async fn reset_password(db: &Database, config: &Config, args: CredentialsArgs) -> CliResult {
+    let user = find_user(db, &args.username).await?;
+    let password_hash = hash_password(&read_password(args.password)?)?;
+
+    // 将最新令牌置为空字符串使已登录的会话失效：没有最新令牌时任何未过期的令牌都能通过验证，
+    // 因此不能直接清除；过期时间取此前签发的令牌最晚的过期时间，之后再由定时任务清除
+    let now = Utc::now();
+    let sessions_expire_at = now + chrono::Duration::hours(config.auth.jwt_expiration_hours);
+    db.collection::<User>("users")
+        .update_one(
+            doc! { "_id": user.id },
+            doc! {
+                "$set": {
+                    "password_hash": password_hash,
+                    "updated_at_str": now.to_rfc3339(),
+                    "last_token": "",
+                    "token_expires_at_str": sessions_expire_at.to_rfc3339(),
+                },
+            },
+            None,
+        )
+        .await?;
+
+    info!("Password of user '{}' reset from command line", args.username);
+    println!("Password of user '{}' reset", args.username);
+    Ok(())
+}
+
+async fn generate_cards(db: &Database, config: &Config, args: GenerateCardsArgs) -> CliResult {
+    let app = match &args.app_id {
+        Some(app_id) => Some(find_application(db, app_id).await?.ok_or_else(|| format!("Application '{}' not found", app_id))?),
+        None => None,
+    };
+    let duration_seconds = args.duration.or(app.as_ref().map(|app| app.default_duration_seconds))
+        .ok_or("--duration is required, e.g. 30m, 2h or 7d")?;
+    let spec = CardSpec {
+        duration_seconds,
+        usage_limit: args.uses,
+        shelf_life_seconds: args.shelf_life,
+        valid_from: args.valid_from,
+        activate_before: args.activate_before,
+        valid_until: args.valid_until,
+    };
+    spec.validate()?;
+    if !(1..=MAX_GENERATE_COUNT).contains(&args.count) {
+        return Err(format!("--count must be between 1 and {}", MAX_GENERATE_COUNT).into());
+    }
+
+    let creator = match &args.created_by {
+        Some(username) => Some(find_user(db, username).await?),
+        None => None,
+    };
+    let created_by = creator
+        .as_ref()
+        .map(|user| (user.id.map(|id| id.to_hex()).unwrap_or_default(), user.username.clone()));
+
+    let length = args.length.unwrap_or(config.card.default_length);
+    let cards = new_batch(
+        args.count,
+        &spec,
+        length,
+        created_by.as_ref().map(|(id, name)| (id.as_str(), name.as_str())),
+        args.app_id.as_deref(),
+    );
+
+    // 先确认输出文件可写，再写入数据库，避免卡密入库后无处导出
+    let mut file = std::fs::File::create(&args.out)?;
+    let documents = cards.iter().map(Card::to_document).collect::<Result<Vec<_>, _>>()?;
+    db.collection::<Document>("cards").insert_many(documents, None).await?;
+    file.write_all(cards_to_csv(&cards, Utc::now()).as_bytes())?;
+
+    info!("Generated {} cards from command line, batch {}", cards.len(), cards[0].batch_id.as_deref().unwrap_or("-"));
+    println!(
+        "Generated {} cards (batch {}) -> {}",
+        cards.len(),
+        cards[0].batch_id.as_deref().unwrap_or("-"),
+        args.out.display()
+    );
+    Ok(())
+}
+
+async fn export(db: &Database, args: ExportArgs) -> CliResult {
+    let created_by = match &args.created_by {
+        Some(username) => find_user(db, username).await?.id.map(|id| id.to_hex()),
+        None => None,
+    };
+    let query = CardQuery {
+        status: args.status,
+        created_by,
+        batch_id: args.batch_id,
+        app_id: args.app_id,
+        ..Default::default()
+    };
+
+    let cards: Vec<Card> = db
+        .collection::<Card>("cards")
+        .find(query.to_filter(), None)
+        .await?
+        .try_collect()
+        .await?;
+    let csv_content = cards_to_csv(&cards, Utc::now());
+
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, csv_content)?;
+            println!("Exported {} cards -> {}", cards.len(), path.display());
+        }
+        None => io::stdout().write_all(csv_content.as_bytes())?,
+    }
+    Ok(())
+}
+
+async fn migrate(db: &Database, args: MigrateArgs) -> CliResult {
+    if args.status {
+        let pending = migrations::pending(db).await?;
+        for migration in MIGRATIONS {
+            let state = if pending.iter().any(|m| m.id == migration.id) { "pending" } else { "applied" };
+            println!("{:<8} {}  {}", state, migration.id, migration.description);
+        }
+        return Ok(());
+    }
+
+    let applied = migrations::run_pending(db).await?;
+    if applied.is_empty() {
+        println!("No pending migrations");
+    } else {
+        for id in &applied {
+            println!("Applied {}", id);
+        }
+    }
+    Ok(())
+}
+
+async fn stats(db: &Database, args: StatsArgs) -> CliResult {
+    let query = StatsQuery {
+        days: args.days,
+        expiring_days: args.expiring_days,
+        top: args.top,
+        app_id: args.app_id,
+    };
+    let mut scope = doc! { "deleted_at_str": null };
+    if let Some(app_id) = &query.app_id {
+        scope.insert("app_id", app_id);
+    }
+    let stats = compute_stats(db, scope, &query).await?;
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    Ok(())
+}
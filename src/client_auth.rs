@@ -0,0 +1,302 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{ErrorBadRequest, ErrorInternalServerError, InternalError},
+    http::header::{HeaderName, HeaderValue},
+    web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use chrono::{Duration, Utc};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use hmac::{Hmac, Mac};
+use log::{error, warn};
+use mongodb::bson::{self, doc};
+use mongodb::Database;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::envelope::with_code;
+use crate::errors::is_duplicate_key;
+use crate::models::application::Application;
+use crate::AppState;
+
+// 签名相关的请求头，响应中同样返回 X-Timestamp、X-Nonce 和 X-Signature
+pub const HEADER_APP_KEY: &str = "x-app-key";
+pub const HEADER_TIMESTAMP: &str = "x-timestamp";
+pub const HEADER_NONCE: &str = "x-nonce";
+pub const HEADER_SIGNATURE: &str = "x-signature";
+
+// 签名验证失败的错误码（v2 响应中的 code）
+pub const CODE_SIGNATURE_REQUIRED: &str = "SIGNATURE_REQUIRED";
+pub const CODE_SIGNATURE_INVALID: &str = "SIGNATURE_INVALID";
+pub const CODE_APP_INVALID: &str = "APP_INVALID";
+pub const CODE_TIMESTAMP_INVALID: &str = "TIMESTAMP_INVALID";
+pub const CODE_NONCE_REPLAYED: &str = "NONCE_REPLAYED";
+
+// nonce 的最大长度
+const MAX_NONCE_LEN: usize = 64;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 生成应用标识
+pub fn generate_app_key() -> String {
+    format!("app_{}", random_string(24))
+}
+
+// 生成应用签名密钥
+pub fn generate_secret() -> String {
+    format!("appsec_{}", random_string(40))
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+fn hmac_hex(secret: &str, prefix: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(prefix.as_bytes());
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// 响应签名：HMAC-SHA256(secret, "{status}\n{timestamp}\n{nonce}\n{body}")，status 为 HTTP 状态码，
+// nonce 为请求中的 nonce，防止重放旧响应或篡改状态码
+fn sign_response(secret: &str, status: u16, timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    hmac_hex(secret, &format!("{}\n{}\n{}\n", status, timestamp, nonce), body)
+}
+
+// 校验请求签名：HMAC-SHA256(secret, "{METHOD}\n{path}\n{timestamp}\n{nonce}\n{body}")，十六进制编码，常量时间比较
+fn signature_matches(secret: &str, message_prefix: &str, body: &[u8], signature: &str) -> bool {
+    let Ok(expected) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(message_prefix.as_bytes());
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+// 已使用的 nonce，过期后由 TTL 索引自动删除
+#[derive(Debug, Serialize, Deserialize)]
+struct UsedNonce {
+    #[serde(rename = "_id")]
+    id: String,  // "{app_key}:{nonce}"
+    expires_at: bson::DateTime,
+}
+
+// 记录 nonce，已使用过时返回 false；保留时间覆盖整个时间戳允许窗口
+async fn record_nonce(db: &Database, app_key: &str, nonce: &str, tolerance_secs: i64) -> Result<bool, mongodb::error::Error> {
+    let used = UsedNonce {
+        id: format!("{}:{}", app_key, nonce),
+        expires_at: bson::DateTime::from_chrono(Utc::now() + Duration::seconds(tolerance_secs * 2)),
+    };
+    match db.collection::<UsedNonce>("nonces").insert_one(used, None).await {
+        Ok(_) => Ok(true),
+        Err(e) if is_duplicate_key(&e) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// 签名验证失败的响应，v2 中附带具体错误码
+fn rejection(code: &'static str, message: &str) -> Error {
+    warn!("Rejected client request: {}", message);
+    InternalError::from_response(
+        message.to_string(),
+        with_code(HttpResponse::Unauthorized().body(message.to_string()), code, message.to_string()),
+    )
+    .into()
+}
+
+// 通过签名验证的请求，由 ResponseSigning 中间件用于签名响应
+#[derive(Clone)]
+struct SignedRequest {
+    secret: String,
+    nonce: String,
+}
+
+// 校验请求签名、时间戳和 nonce，返回发起请求的应用
+async fn verify_signature(req: &HttpRequest, body: &[u8], state: &AppState) -> Result<Application, Error> {
+    let header = |name: &str| req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let (Some(app_key), Some(timestamp), Some(nonce), Some(signature)) = (
+        header(HEADER_APP_KEY),
+        header(HEADER_TIMESTAMP),
+        header(HEADER_NONCE),
+        header(HEADER_SIGNATURE),
+    ) else {
+        return Err(rejection(CODE_SIGNATURE_REQUIRED, "X-App-Key, X-Timestamp, X-Nonce and X-Signature headers are required"));
+    };
+
+    let app = match state
+        .db
+        .collection::<Application>("applications")
+        .find_one(doc! { "app_key": &app_key, "enabled": true }, None)
+        .await
+    {
+        Ok(Some(app)) => app,
+        Ok(None) => return Err(rejection(CODE_APP_INVALID, "Unknown or disabled application")),
+        Err(e) => {
+            error!("Failed to look up application '{}': {}", app_key, e);
+            return Err(ErrorInternalServerError(e.to_string()));
+        }
+    };
+
+    let tolerance = state.config.client.signature_tolerance_secs;
+    let timestamp = match timestamp.parse::<i64>() {
+        Ok(timestamp) if (Utc::now().timestamp() - timestamp).abs() <= tolerance => timestamp,
+        _ => return Err(rejection(CODE_TIMESTAMP_INVALID, "Request timestamp is invalid or outside the allowed window")),
+    };
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+        return Err(rejection(CODE_SIGNATURE_INVALID, "Nonce must be 1 to 64 characters"));
+    }
+
+    let prefix = format!("{}\n{}\n{}\n{}\n", req.method(), req.path(), timestamp, nonce);
+    if !signature_matches(&app.secret, &prefix, body, &signature) {
+        return Err(rejection(CODE_SIGNATURE_INVALID, "Invalid request signature"));
+    }
+
+    // 签名通过后才记录 nonce，避免未持有密钥的请求占用 nonce
+    match record_nonce(&state.db, &app.app_key, &nonce, tolerance).await {
+        Ok(true) => {}
+        Ok(false) => return Err(rejection(CODE_NONCE_REPLAYED, "Nonce has already been used")),
+        Err(e) => {
+            error!("Failed to record nonce: {}", e);
+            return Err(ErrorInternalServerError(e.to_string()));
+        }
+    }
+
+    req.extensions_mut().insert(SignedRequest { secret: app.secret.clone(), nonce });
+    Ok(app)
+}
+
+// 客户端接口（验证卡密、心跳）的 JSON 请求体提取器：
+// 带有 X-App-Key 时校验签名，app 为发起请求的应用；未签名且配置了 require_signature 时拒绝
+pub struct ClientJson<T> {
+    pub app: Option<Application>,
+    pub body: T,
+}
+
+impl<T> Deref for ClientJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.body
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for ClientJson<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let body = web::Bytes::from_request(&req, payload);
+
+        Box::pin(async move {
+            let body = body.await?;
+            let state = req
+                .app_data::<web::Data<AppState>>()
+                .cloned()
+                .ok_or_else(|| ErrorInternalServerError("Application state missing"))?;
+
+            let app = if req.headers().contains_key(HEADER_APP_KEY) {
+                Some(verify_signature(&req, &body, &state).await?)
+            } else if state.config.client.require_signature {
+                return Err(rejection(CODE_SIGNATURE_REQUIRED, "Signed request required"));
+            } else {
+                None
+            };
+
+            let body = serde_json::from_slice(&body).map_err(|e| ErrorBadRequest(format!("Json deserialize error: {}", e)))?;
+            Ok(ClientJson { app, body })
+        })
+    }
+}
+
+// 要求请求必须签名，用于心跳等只接受签名请求的接口
+pub fn require_signed<T>(json: &ClientJson<T>) -> Result<&Application, Error> {
+    json.app
+        .as_ref()
+        .ok_or_else(|| rejection(CODE_SIGNATURE_REQUIRED, "Signed request required"))
+}
+
+// 签名请求的响应签名中间件：在最外层对最终响应体签名（v2 中为统一格式包装后的响应体），
+// 并通过 X-Timestamp、X-Nonce、X-Signature 响应头返回；未签名的请求原样返回
+pub struct ResponseSigning;
+
+impl<S, B> Transform<S, ServiceRequest> for ResponseSigning
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ResponseSigningService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResponseSigningService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ResponseSigningService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseSigningService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let res = service.call(req).await?.map_into_boxed_body();
+
+            let signed = res.request().extensions().get::<SignedRequest>().cloned();
+            let Some(signed) = signed else {
+                return Ok(res);
+            };
+
+            let (request, response) = res.into_parts();
+            let (mut head, response_body) = response.into_parts();
+            let bytes = body::to_bytes(response_body)
+                .await
+                .map_err(|_| ErrorInternalServerError("Failed to read response body"))?;
+
+            let timestamp = Utc::now().timestamp();
+            let signature = sign_response(&signed.secret, head.status().as_u16(), timestamp, &signed.nonce, &bytes);
+            let headers = head.headers_mut();
+            headers.insert(HeaderName::from_static(HEADER_TIMESTAMP), HeaderValue::from(timestamp));
+            if let Ok(nonce) = HeaderValue::from_str(&signed.nonce) {
+                headers.insert(HeaderName::from_static(HEADER_NONCE), nonce);
+            }
+            headers.insert(
+                HeaderName::from_static(HEADER_SIGNATURE),
+                HeaderValue::from_str(&signature).expect("hex signature is a valid header value"),
+            );
+
+            let response = head.set_body(BoxBody::new(bytes));
+            Ok(ServiceResponse::new(request, response))
+        })
+    }
+}
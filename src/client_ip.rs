@@ -0,0 +1,220 @@
+use actix_web::{
+    dev::Payload,
+    http::header::{HeaderMap, FORWARDED, X_FORWARDED_FOR},
+    FromRequest, HttpMessage, HttpRequest,
+};
+use futures::future::{ready, Ready};
+use ipnet::IpNet;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+// 解析后的客户端IP，由 RequestContextMiddleware 写入请求扩展，可直接作为提取器使用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientIp(pub IpAddr);
+
+impl fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromRequest for ClientIp {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ip = req
+            .extensions()
+            .get::<ClientIp>()
+            .copied()
+            .unwrap_or_else(|| ClientIp(peer_ip(req.peer_addr())));
+        ready(Ok(ip))
+    }
+}
+
+// 连接对端地址，IPv4 映射的 IPv6 地址转换为 IPv4
+fn peer_ip(peer: Option<SocketAddr>) -> IpAddr {
+    peer.map(|addr| addr.ip().to_canonical())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+// 解析 IP 或 CIDR，单个IP视为 /32 或 /128
+pub fn parse_net(item: &str) -> Option<IpNet> {
+    item.parse::<IpNet>()
+        .ok()
+        .or_else(|| item.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn is_trusted(ip: &IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|net| net.contains(ip))
+}
+
+// 解析客户端IP：
+// 只有当连接来自受信任代理时才读取转发头；从右向左遍历转发链，跳过受信任代理，
+// 返回第一个不受信任的地址。遇到无法解析的地址时停止，返回最后一个已验证的代理地址。
+pub fn resolve(peer: Option<SocketAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> ClientIp {
+    let peer = peer_ip(peer);
+    if !is_trusted(&peer, trusted) {
+        return ClientIp(peer);
+    }
+
+    // Forwarded（RFC 7239）优先于 X-Forwarded-For
+    let chain = match forwarded_chain(headers) {
+        Some(chain) => chain,
+        None => x_forwarded_for_chain(headers),
+    };
+
+    let mut current = peer;
+    for hop in chain.iter().rev() {
+        match hop {
+            Some(ip) if is_trusted(ip, trusted) => current = *ip,
+            Some(ip) => return ClientIp(*ip),
+            None => break,
+        }
+    }
+    ClientIp(current)
+}
+
+// 解析 X-Forwarded-For，多个头按出现顺序拼接
+fn x_forwarded_for_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .flat_map(|value| value.to_str().unwrap_or("").split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(parse_node)
+        .collect()
+}
+
+// 解析 Forwarded 头中各元素的 for= 参数，没有 Forwarded 头时返回 None
+fn forwarded_chain(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let mut values = headers.get_all(FORWARDED).peekable();
+    values.peek()?;
+
+    let chain = values
+        .flat_map(|value| value.to_str().unwrap_or("").split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, value)| parse_node(value.trim()))
+        })
+        .collect();
+    Some(chain)
+}
+
+// 解析节点标识，支持 "1.2.3.4"、"1.2.3.4:8080"、"[2001:db8::1]:4711" 和带引号的形式；
+// "unknown" 和混淆标识等无法解析的值返回 None
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse::<IpAddr>().ok().map(|ip| ip.to_canonical());
+    }
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+    node.parse::<SocketAddr>().ok().map(|addr| addr.ip().to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderValue;
+
+    fn trusted(items: &[&str]) -> Vec<IpNet> {
+        items.iter().map(|item| parse_net(item).unwrap()).collect()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name.parse().unwrap(), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn peer(ip: &str) -> Option<SocketAddr> {
+        Some(SocketAddr::new(ip.parse().unwrap(), 40000))
+    }
+
+    fn ip(ip: &str) -> ClientIp {
+        ClientIp(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_node_accepts_supported_forms() {
+        assert_eq!(parse_node("1.2.3.4"), Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(parse_node("1.2.3.4:8080"), Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(parse_node("\"[2001:db8::1]:4711\""), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(parse_node("[2001:db8::1]"), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(parse_node("::ffff:10.0.0.1"), Some("10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn parse_node_rejects_unknown_and_obfuscated() {
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+        assert_eq!(parse_node(""), None);
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_forwarded_headers() {
+        let headers = headers(&[("x-forwarded-for", "9.9.9.9")]);
+        assert_eq!(resolve(peer("5.6.7.8"), &headers, &trusted(&["10.0.0.1"])), ip("5.6.7.8"));
+        assert_eq!(resolve(peer("5.6.7.8"), &headers, &[]), ip("5.6.7.8"));
+    }
+
+    #[test]
+    fn trusted_peer_uses_x_forwarded_for() {
+        let headers = headers(&[("x-forwarded-for", "9.9.9.9")]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.1"])), ip("9.9.9.9"));
+    }
+
+    #[test]
+    fn skips_trusted_hops_from_the_right() {
+        // 客户端伪造的最左侧地址不会被采用
+        let headers = headers(&[("x-forwarded-for", "1.1.1.1, 9.9.9.9, 10.0.0.2")]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.0/24"])), ip("9.9.9.9"));
+    }
+
+    #[test]
+    fn joins_multiple_x_forwarded_for_headers() {
+        let headers = headers(&[("x-forwarded-for", "9.9.9.9"), ("x-forwarded-for", "10.0.0.2")]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.0/24"])), ip("9.9.9.9"));
+    }
+
+    #[test]
+    fn forwarded_takes_precedence() {
+        let headers = headers(&[
+            ("forwarded", "for=\"[2001:db8::1]:4711\";proto=https, for=10.0.0.2"),
+            ("x-forwarded-for", "9.9.9.9"),
+        ]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.0/24"])), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn stops_at_unparsable_hop() {
+        let headers = headers(&[("x-forwarded-for", "9.9.9.9, unknown, 10.0.0.2")]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.0/24"])), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn all_trusted_chain_returns_leftmost_proxy() {
+        let headers = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(resolve(peer("10.0.0.1"), &headers, &trusted(&["10.0.0.0/24"])), ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn trusted_peer_without_headers_returns_peer() {
+        assert_eq!(resolve(peer("10.0.0.1"), &HeaderMap::new(), &trusted(&["10.0.0.1"])), ip("10.0.0.1"));
+        assert_eq!(resolve(None, &HeaderMap::new(), &[]), ip("0.0.0.0"));
+    }
+
+    #[test]
+    fn ipv4_mapped_peer_is_canonicalized() {
+        let headers = headers(&[("x-forwarded-for", "9.9.9.9")]);
+        assert_eq!(resolve(peer("::ffff:10.0.0.1"), &headers, &trusted(&["10.0.0.1"])), ip("9.9.9.9"));
+    }
+}
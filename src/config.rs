@@ -0,0 +1,428 @@
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::client_ip;
+
+// 开发环境下使用的默认值，生产环境必须覆盖
+const DEFAULT_JWT_SECRET: &str = "your_jwt_secret";
+const DEFAULT_ADMIN_PASSWORD: &str = "admin123";
+// 示例配置文件中的占位值，同样不允许在生产环境使用
+const PLACEHOLDER_SECRETS: &[&str] = &[
+    "change_this_to_a_secure_secret",
+    "change_this_password",
+    "default_jwt_secret_please_change",
+    "your_jwt_secret_key_here",
+];
+
+// 配置加载与校验错误
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file '{0}': {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("Failed to parse config file '{0}': {1}")]
+    Parse(PathBuf, toml::de::Error),
+
+    #[error("Invalid value for {0}: {1}")]
+    Env(&'static str, String),
+
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+// 运行环境
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Development,
+    Production,
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(Environment::Development),
+            "production" | "prod" => Ok(Environment::Production),
+            _ => Err(format!("unknown environment '{}', expected development or production", s)),
+        }
+    }
+}
+
+// 日志输出格式
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,  // 人类可读的单行文本
+    Json,  // 每行一个 JSON 对象，便于日志系统采集
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}', expected text or json", s)),
+        }
+    }
+}
+
+// 受信任的反向代理网段，只有来自这些地址的连接才会读取 X-Forwarded-For / Forwarded
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(pub Vec<IpNet>);
+
+impl FromStr for TrustedProxies {
+    type Err = String;
+
+    // 逗号分隔的 CIDR 列表，单个IP视为 /32 或 /128
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                client_ip::parse_net(item).ok_or_else(|| format!("'{}' is not a valid IP or CIDR", item))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(TrustedProxies)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrustedProxies {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<String>::deserialize(deserializer)?;
+        items.join(",").parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// 应用配置，按 默认值 -> TOML 配置文件 -> 环境变量 的顺序逐层覆盖
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub environment: Environment,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub admin: AdminConfig,
+    pub log: LogConfig,
+    pub card: CardConfig,
+    pub webhook: WebhookConfig,
+    pub trash: TrashConfig,
+    pub audit: AuditConfig,
+    pub client: ClientConfig,
+}
+
+// 服务器配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub static_files_path: String,
+    pub trusted_proxies: TrustedProxies,
+    pub shutdown_timeout_secs: u64,  // 停机时等待请求和后台任务完成的超时时间
+}
+
+// 数据库配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    pub uri: String,
+    pub name: String,
+    pub connect_retries: u32,  // 启动时连接失败的重试次数
+}
+
+// 认证配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub jwt_expiration_hours: i64,
+}
+
+// 初始管理员账号配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub username: String,
+    pub password: String,
+}
+
+// 日志配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: String,
+    pub file: String,
+    pub file_size: u64,   // 单个日志文件大小上限（字节）
+    pub file_count: u32,  // 保留的日志文件数量（包括当前文件）
+    pub format: LogFormat,
+    pub stdout: bool,     // 是否同时输出到标准输出
+}
+
+// 卡密配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CardConfig {
+    pub default_length: usize,
+}
+
+// Webhook 配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub delivery_retention_days: i64,
+}
+
+// 回收站配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    pub retention_days: i64,
+}
+
+// 审计日志配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub retention_days: i64,
+}
+
+// 客户端签名配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub signature_tolerance_secs: i64,  // 签名请求的时间戳与服务器时间允许的最大偏差
+    pub require_signature: bool,        // 是否拒绝未签名的验证卡密请求
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            environment: Environment::Development,
+            server: ServerConfig::default(),
+            database: DatabaseConfig::default(),
+            auth: AuthConfig::default(),
+            admin: AdminConfig::default(),
+            log: LogConfig::default(),
+            card: CardConfig::default(),
+            webhook: WebhookConfig::default(),
+            trash: TrashConfig::default(),
+            audit: AuditConfig::default(),
+            client: ClientConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 5005,
+            static_files_path: "./static".to_string(),
+            trusted_proxies: TrustedProxies::default(),
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            uri: "mongodb://localhost:27017".to_string(),
+            name: "card_platform".to_string(),
+            connect_retries: 10,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            jwt_expiration_hours: 24,
+        }
+    }
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            username: "admin".to_string(),
+            password: DEFAULT_ADMIN_PASSWORD.to_string(),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: "./logs/card-platform.log".to_string(),
+            file_size: 10 * 1024 * 1024,
+            file_count: 10,
+            format: LogFormat::Text,
+            stdout: false,
+        }
+    }
+}
+
+impl Default for CardConfig {
+    fn default() -> Self {
+        Self { default_length: 16 }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self { delivery_retention_days: 30 }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self { retention_days: 90 }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            signature_tolerance_secs: 300,
+            require_signature: false,
+        }
+    }
+}
+
+impl Config {
+    // 加载配置：CONFIG_FILE 指定配置文件路径（默认 ./config.toml，不存在时跳过），环境变量优先级最高
+    pub fn load() -> Result<Self, ConfigError> {
+        let explicit_path = env::var("CONFIG_FILE").ok();
+        let path = PathBuf::from(explicit_path.as_deref().unwrap_or("config.toml"));
+
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| ConfigError::Parse(path.clone(), e))?,
+            // 未显式指定且默认文件不存在时只使用默认值和环境变量
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && explicit_path.is_none() => Config::default(),
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // 使用环境变量覆盖配置
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        env_parse("APP_ENV", &mut self.environment)?;
+
+        env_parse("SERVER_HOST", &mut self.server.host)?;
+        env_parse("SERVER_PORT", &mut self.server.port)?;
+        env_parse("STATIC_FILES_PATH", &mut self.server.static_files_path)?;
+        env_parse("TRUSTED_PROXIES", &mut self.server.trusted_proxies)?;
+        env_parse("SHUTDOWN_TIMEOUT", &mut self.server.shutdown_timeout_secs)?;
+
+        env_parse("MONGODB_URI", &mut self.database.uri)?;
+        env_parse("DATABASE_NAME", &mut self.database.name)?;
+        env_parse("MONGODB_CONNECT_RETRIES", &mut self.database.connect_retries)?;
+
+        env_parse("JWT_SECRET", &mut self.auth.jwt_secret)?;
+        env_parse("JWT_EXPIRATION_HOURS", &mut self.auth.jwt_expiration_hours)?;
+
+        env_parse("ADMIN_USERNAME", &mut self.admin.username)?;
+        env_parse("ADMIN_PASSWORD", &mut self.admin.password)?;
+
+        env_parse("LOG_LEVEL", &mut self.log.level)?;
+        env_parse("LOG_FILE", &mut self.log.file)?;
+        env_parse("LOG_FILE_SIZE", &mut self.log.file_size)?;
+        env_parse("LOG_FILE_COUNT", &mut self.log.file_count)?;
+        env_parse("LOG_FORMAT", &mut self.log.format)?;
+        env_parse("LOG_STDOUT", &mut self.log.stdout)?;
+
+        env_parse("DEFAULT_CARD_LENGTH", &mut self.card.default_length)?;
+        env_parse("WEBHOOK_DELIVERY_RETENTION_DAYS", &mut self.webhook.delivery_retention_days)?;
+        env_parse("TRASH_RETENTION_DAYS", &mut self.trash.retention_days)?;
+        env_parse("AUDIT_RETENTION_DAYS", &mut self.audit.retention_days)?;
+
+        env_parse("CLIENT_SIGNATURE_TOLERANCE_SECS", &mut self.client.signature_tolerance_secs)?;
+        env_parse("CLIENT_REQUIRE_SIGNATURE", &mut self.client.require_signature)?;
+        Ok(())
+    }
+
+    // 校验配置，生产环境下拒绝使用默认密钥和默认管理员密码
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+
+        if self.auth.jwt_secret.is_empty() {
+            return invalid("auth.jwt_secret must not be empty".to_string());
+        }
+        if self.auth.jwt_expiration_hours <= 0 {
+            return invalid("auth.jwt_expiration_hours must be positive".to_string());
+        }
+        if self.admin.username.is_empty() || self.admin.password.is_empty() {
+            return invalid("admin.username and admin.password must not be empty".to_string());
+        }
+        if self.log.level.parse::<log::LevelFilter>().is_err() {
+            return invalid(format!("log.level '{}' is not a valid level", self.log.level));
+        }
+        if self.log.file_count == 0 {
+            return invalid("log.file_count must be at least 1".to_string());
+        }
+        if self.log.file_size == 0 {
+            return invalid("log.file_size must be positive".to_string());
+        }
+        if !(4..=64).contains(&self.card.default_length) {
+            return invalid("card.default_length must be between 4 and 64".to_string());
+        }
+        if self.webhook.delivery_retention_days <= 0 || self.trash.retention_days <= 0 || self.audit.retention_days <= 0 {
+            return invalid("retention days must be positive".to_string());
+        }
+        if self.client.signature_tolerance_secs <= 0 {
+            return invalid("client.signature_tolerance_secs must be positive".to_string());
+        }
+
+        if self.environment == Environment::Production {
+            let jwt_secret = self.auth.jwt_secret.as_str();
+            if jwt_secret == DEFAULT_JWT_SECRET || PLACEHOLDER_SECRETS.contains(&jwt_secret) {
+                return invalid("JWT_SECRET must be changed from the default in production".to_string());
+            }
+            if jwt_secret.len() < 32 {
+                return invalid("JWT_SECRET must be at least 32 characters in production".to_string());
+            }
+            let password = self.admin.password.as_str();
+            if password == DEFAULT_ADMIN_PASSWORD || PLACEHOLDER_SECRETS.contains(&password) {
+                return invalid("ADMIN_PASSWORD must be changed from the default in production".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// 读取并解析环境变量，未设置时保留原值，格式错误时报错而不是静默回退
+fn env_parse<T>(key: &'static str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(key) {
+        Ok(value) => {
+            *target = value.trim().parse().map_err(|e: T::Err| ConfigError::Env(key, e.to_string()))?;
+            Ok(())
+        }
+        Err(env::VarError::NotPresent) => Ok(()),
+        Err(e) => Err(ConfigError::Env(key, e.to_string())),
+    }
+}
@@ -0,0 +1,147 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderValue},
+    Error, HttpMessage, HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::Serialize;
+use std::rc::Rc;
+use utoipa::ToSchema;
+
+use crate::middleware::RequestContext;
+
+// v2 接口的统一响应格式，成功与失败使用相同的结构
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Envelope<T> {
+    pub success: bool,
+    pub code: String,             // 成功为 OK，失败为具体的错误码（如 CARD_EXPIRED）或 HTTP 状态（如 NOT_FOUND）
+    pub message: Option<String>,  // 面向用户的提示信息
+    pub data: Option<T>,
+    pub request_id: Option<String>,
+}
+
+// 处理函数指定的错误码和提示信息，由 EnvelopeMiddleware 写入响应
+struct ResponseCode {
+    code: &'static str,
+    message: Option<String>,
+}
+
+// 为 v2 处理函数的响应附加错误码和提示信息，响应体作为 data 返回
+pub fn with_code(mut response: HttpResponse, code: &'static str, message: impl Into<Option<String>>) -> HttpResponse {
+    response.extensions_mut().insert(ResponseCode { code, message: message.into() });
+    response
+}
+
+// 未指定错误码时根据 HTTP 状态生成，例如 404 -> NOT_FOUND
+fn status_code_name(status: actix_web::http::StatusCode) -> String {
+    if status.is_success() {
+        return "OK".to_string();
+    }
+    status
+        .canonical_reason()
+        .map(|reason| reason.to_uppercase().replace([' ', '-'], "_"))
+        .unwrap_or_else(|| "ERROR".to_string())
+}
+
+// 统一响应格式中间件：将 JSON 和纯文本响应包装为 Envelope，其他类型（如 CSV 导出）原样返回
+pub struct EnvelopeMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for EnvelopeMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = EnvelopeMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(EnvelopeMiddlewareService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct EnvelopeMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for EnvelopeMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let res = service.call(req).await?.map_into_boxed_body();
+
+            let content_type = res
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("")
+                .to_string();
+            let is_json = content_type.starts_with("application/json");
+            if !(is_json || content_type.is_empty() || content_type.starts_with("text/plain")) {
+                return Ok(res);
+            }
+
+            let request_id = res
+                .request()
+                .extensions()
+                .get::<RequestContext>()
+                .map(|context| context.request_id().to_string());
+            let (request, mut response) = res.into_parts();
+            let status = response.status();
+            let response_code = response.extensions_mut().remove::<ResponseCode>();
+            let (mut head, response_body) = response.into_parts();
+            let bytes = body::to_bytes(response_body).await.map_err(|_| {
+                actix_web::error::ErrorInternalServerError("Failed to read response body")
+            })?;
+
+            // JSON 响应体作为 data；纯文本响应体作为 message
+            let (data, text) = if bytes.is_empty() {
+                (None, None)
+            } else if is_json {
+                (serde_json::from_slice::<serde_json::Value>(&bytes).ok(), None)
+            } else {
+                (None, Some(String::from_utf8_lossy(&bytes).into_owned()))
+            };
+            let data_message = data
+                .as_ref()
+                .and_then(|data| data.get("message"))
+                .and_then(|message| message.as_str())
+                .map(str::to_string);
+
+            let (code, message) = match response_code {
+                Some(ResponseCode { code, message }) => (code.to_string(), message.or(text).or(data_message)),
+                None => (status_code_name(status), text.or(data_message)),
+            };
+            let envelope = Envelope {
+                success: status.is_success(),
+                code,
+                message,
+                data,
+                request_id,
+            };
+
+            head.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            head.headers_mut().remove(header::CONTENT_LENGTH);
+            let response = head.set_body(BoxBody::new(serde_json::to_vec(&envelope)?));
+            Ok(ServiceResponse::new(request, response))
+        })
+    }
+}
@@ -1,5 +1,15 @@
+use mongodb::error::{ErrorKind, WriteFailure};
 use thiserror::Error;
 
+// MongoDB 唯一索引冲突的错误码
+const DUPLICATE_KEY: i32 = 11000;
+
+// 是否为唯一索引冲突（重复插入）
+pub fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(*e.kind, ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == DUPLICATE_KEY)
+}
+
+#[allow(dead_code)]
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("MongoDB error: {0}")]
@@ -0,0 +1,175 @@
+use actix_web::{web, HttpResponse, Scope};
+use chrono::{Duration, Utc};
+use futures::stream::TryStreamExt;
+use log::{info, warn, error};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+
+use crate::{
+    api_key::{generate_key, invalid_allowed_ip},
+    auth::AuthenticatedUser,
+    models::api_key::{ApiKey, ApiKeyCreated, ApiKeyInfo, CreateApiKeyRequest},
+    models::user::UserRole,
+    AppState,
+};
+
+// 配置 API 密钥相关的路由，只能使用登录令牌访问
+pub fn config() -> Scope {
+    web::scope("/api-keys")
+        .route("", web::post().to(create_api_key))        // 创建 API 密钥
+        .route("", web::get().to(get_api_keys))           // 获取 API 密钥列表
+        .route("/{id}", web::delete().to(revoke_api_key)) // 撤销 API 密钥
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "api-keys",
+    summary = "创建 API 密钥",
+    description = "密钥以创建者的身份和当前角色访问接口，并受 scopes 限制；明文密钥只在创建时返回一次。使用时通过 X-API-Key 头或 Authorization: Bearer <key> 传递",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "创建的 API 密钥和明文密钥", body = ApiKeyCreated),
+        (status = 400, description = "名称、权限范围、IP 白名单或有效天数无效"),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn create_api_key(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: web::Json<CreateApiKeyRequest>,
+) -> HttpResponse {
+    info!("User '{}' is creating API key. Request params: name={}, scopes={:?}, allowed_ips={:?}, expires_in_days={:?}",
+        user.username, req.name, req.scopes, req.allowed_ips, req.expires_in_days);
+
+    if user.api_key_id.is_some() {
+        return HttpResponse::Forbidden().body("API keys cannot be used to manage API keys");
+    }
+    if req.name.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Name is required");
+    }
+    if req.scopes.is_empty() {
+        return HttpResponse::BadRequest().body("At least one scope is required");
+    }
+    if let Some(item) = invalid_allowed_ip(&req.allowed_ips) {
+        warn!("Invalid API key allowed IP: {}", item);
+        return HttpResponse::BadRequest().body(format!("'{}' is not a valid IP or CIDR", item));
+    }
+    if req.expires_in_days.is_some_and(|days| days < 1) {
+        return HttpResponse::BadRequest().body("expires_in_days must be at least 1");
+    }
+
+    let req = req.into_inner();
+    let (key, key_hash, key_prefix) = generate_key();
+    let mut api_key = ApiKey::new(req.name.trim().to_string(), key_hash, key_prefix, user.user_id.clone(), user.username.clone());
+    api_key.scopes = req.scopes;
+    api_key.allowed_ips = req.allowed_ips;
+    api_key.expires_at_str = req.expires_in_days.map(|days| (Utc::now() + Duration::days(days)).to_rfc3339());
+
+    match state.db.collection::<ApiKey>("api_keys").insert_one(&api_key, None).await {
+        Ok(result) => {
+            api_key.id = result.inserted_id.as_object_id();
+            info!("API key '{}' created by user '{}'", api_key.key_prefix, user.username);
+            state.audit.record(&user, "api_key.create", api_key.id.map(|id| id.to_hex()), Some(serde_json::json!({
+                "name": api_key.name,
+                "scopes": api_key.scopes,
+            })));
+            // 仅在创建时返回一次明文密钥
+            HttpResponse::Created().json(ApiKeyCreated { api_key: ApiKeyInfo::from(api_key), key })
+        }
+        Err(e) => {
+            error!("Failed to create API key: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 普通用户只能看到自己的密钥，管理员可以看到所有用户的密钥
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    tag = "api-keys",
+    summary = "获取 API 密钥列表",
+    description = "普通用户返回自己的密钥，管理员返回所有用户的密钥；按创建时间倒序",
+    responses(
+        (status = 200, description = "API 密钥列表", body = Vec<ApiKeyInfo>),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn get_api_keys(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    if user.api_key_id.is_some() {
+        return HttpResponse::Forbidden().body("API keys cannot be used to manage API keys");
+    }
+
+    let filter = if user.role == UserRole::Admin {
+        doc! {}
+    } else {
+        doc! { "user_id": &user.user_id }
+    };
+    let options = FindOptions::builder().sort(doc! { "created_at_str": -1 }).build();
+
+    match state.db.collection::<ApiKey>("api_keys").find(filter, options).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<ApiKey>>().await {
+            Ok(keys) => {
+                let infos: Vec<ApiKeyInfo> = keys.into_iter().map(ApiKeyInfo::from).collect();
+                HttpResponse::Ok().json(infos)
+            }
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// 撤销后密钥立即失效，记录保留以便审计
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "api-keys",
+    summary = "撤销 API 密钥",
+    description = "普通用户只能撤销自己的密钥，管理员可以撤销任意密钥",
+    params(("id" = String, Path, description = "API 密钥ID")),
+    responses(
+        (status = 200, description = "撤销成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证"),
+        (status = 404, description = "密钥不存在或已撤销"),
+    ),
+    security(("bearer_auth" = [])),
+)]
+async fn revoke_api_key(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    info!("User '{}' attempting to revoke API key {}", user.username, id);
+
+    if user.api_key_id.is_some() {
+        return HttpResponse::Forbidden().body("API keys cannot be used to manage API keys");
+    }
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    let mut filter = doc! { "_id": object_id, "revoked_at_str": null };
+    if user.role != UserRole::Admin {
+        filter.insert("user_id", &user.user_id);
+    }
+    let update = doc! { "$set": { "revoked_at_str": Utc::now().to_rfc3339() } };
+
+    match state.db.collection::<ApiKey>("api_keys").update_one(filter, update, None).await {
+        Ok(result) if result.matched_count == 0 => HttpResponse::NotFound().body("API key not found or already revoked"),
+        Ok(_) => {
+            info!("API key {} revoked by user '{}'", id, user.username);
+            state.audit.record(&user, "api_key.revoke", Some(id), None);
+            HttpResponse::Ok().body("API key revoked successfully")
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
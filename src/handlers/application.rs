@@ -0,0 +1,324 @@
+use actix_web::{web, HttpResponse, Scope};
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::Database;
+
+use crate::{
+    auth::{require_admin, AuthenticatedUser},
+    client_auth::{generate_app_key, generate_secret},
+    models::application::{Application, ApplicationInfo, DevicePolicy, CreateApplicationRequest, UpdateApplicationRequest},
+    models::Card,
+    openapi::{ApplicationCreated, SecretRotated},
+    AppState,
+};
+
+// 按ID获取应用，ID 格式错误时视为不存在
+pub(crate) async fn find_application(db: &Database, id: &str) -> Result<Option<Application>, mongodb::error::Error> {
+    match ObjectId::parse_str(id) {
+        Ok(object_id) => db.collection::<Application>("applications").find_one(doc! { "_id": object_id }, None).await,
+        Err(_) => Ok(None),
+    }
+}
+
+// 配置客户端应用相关的路由（仅管理员）
+pub fn config() -> Scope {
+    web::scope("/applications")
+        .route("", web::post().to(create_application))        // 创建应用
+        .route("", web::get().to(get_all_applications))       // 获取所有应用
+        .route("/{id}", web::get().to(get_application))       // 获取单个应用
+        .route("/{id}", web::put().to(update_application))    // 更新应用
+        .route("/{id}", web::delete().to(delete_application)) // 删除应用
+}
+
+#[utoipa::path(
+    post,
+    path = "/applications",
+    tag = "applications",
+    summary = "创建应用",
+    description = "自动生成 app_key 和签名密钥，签名密钥只在创建时返回一次",
+    request_body = CreateApplicationRequest,
+    responses(
+        (status = 201, description = "创建的应用和签名密钥", body = ApplicationCreated),
+        (status = 400, description = "名称为空、默认套餐时长或设备策略无效"),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn create_application(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: web::Json<CreateApplicationRequest>,
+) -> HttpResponse {
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' is creating application. Request params: name={}", user.username, req.name);
+
+    if req.name.trim().is_empty() {
+        warn!("Invalid application request: empty name");
+        return HttpResponse::BadRequest().body("Name is required");
+    }
+    if req.default_duration.is_some_and(|seconds| seconds <= 0) {
+        return HttpResponse::BadRequest().body("default_duration must be positive, e.g. 2h or 30d");
+    }
+    if let Some(Err(e)) = req.device_policy.as_ref().map(DevicePolicy::validate) {
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    let req = req.into_inner();
+    let secret = generate_secret();
+    let mut app = Application::new(
+        req.name.trim().to_string(),
+        generate_app_key(),
+        secret.clone(),
+        req.description,
+        Some(user.user_id.clone()),
+    );
+    if let Some(seconds) = req.default_duration {
+        app.default_duration_seconds = seconds;
+    }
+    if let Some(policy) = req.device_policy {
+        app.device_policy = policy;
+    }
+    app.allow_owner_pause = req.allow_owner_pause;
+    app.require_signature = req.require_signature;
+
+    match state.db.collection::<Application>("applications").insert_one(&app, None).await {
+        Ok(result) => {
+            app.id = result.inserted_id.as_object_id();
+            info!("Application '{}' ({}) created by user '{}'", app.name, app.app_key, user.username);
+            state.audit.record(&user, "application.create", app.id.map(|id| id.to_hex()), Some(serde_json::json!({
+                "name": app.name,
+                "app_key": app.app_key,
+            })));
+            // 仅在创建时返回一次签名密钥
+            HttpResponse::Created().json(serde_json::json!({
+                "application": ApplicationInfo::from(app),
+                "secret": secret,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to create application: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/applications",
+    tag = "applications",
+    summary = "获取所有应用",
+    responses(
+        (status = 200, description = "应用列表", body = Vec<ApplicationInfo>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_all_applications(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    match state.db.collection::<Application>("applications").find(None, None).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<Application>>().await {
+            Ok(apps) => {
+                let infos: Vec<ApplicationInfo> = apps.into_iter().map(ApplicationInfo::from).collect();
+                HttpResponse::Ok().json(infos)
+            }
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/applications/{id}",
+    tag = "applications",
+    summary = "获取单个应用",
+    params(("id" = String, Path, description = "应用ID")),
+    responses(
+        (status = 200, description = "应用信息", body = ApplicationInfo),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "应用不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_application(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    let object_id = match ObjectId::parse_str(path.into_inner()) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    match state.db.collection::<Application>("applications").find_one(doc! { "_id": object_id }, None).await {
+        Ok(Some(app)) => HttpResponse::Ok().json(ApplicationInfo::from(app)),
+        Ok(None) => HttpResponse::NotFound().body("Application not found"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/applications/{id}",
+    tag = "applications",
+    summary = "更新应用",
+    description = "rotate_secret 为 true 时重新生成签名密钥并在响应中返回，旧密钥立即失效",
+    params(("id" = String, Path, description = "应用ID")),
+    request_body = UpdateApplicationRequest,
+    responses(
+        (status = 200, description = "更新成功；重新生成密钥时返回新密钥", body = SecretRotated),
+        (status = 400, description = "ID 格式错误、名称为空、默认套餐时长或设备策略无效"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "应用不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn update_application(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    req: web::Json<UpdateApplicationRequest>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' attempting to update application {}. Request params: name={:?}, enabled={:?}, default_duration={:?}, device_policy={:?}, allow_owner_pause={:?}, require_signature={:?}, rotate_secret={}",
+        user.username, id, req.name, req.enabled, req.default_duration, req.device_policy, req.allow_owner_pause, req.require_signature, req.rotate_secret);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    let mut update_doc = doc! { "updated_at_str": Utc::now().to_rfc3339() };
+    if let Some(name) = &req.name {
+        if name.trim().is_empty() {
+            return HttpResponse::BadRequest().body("Name must not be empty");
+        }
+        update_doc.insert("name", name.trim());
+    }
+    if let Some(enabled) = req.enabled {
+        update_doc.insert("enabled", enabled);
+    }
+    if let Some(seconds) = req.default_duration {
+        if seconds <= 0 {
+            return HttpResponse::BadRequest().body("default_duration must be positive, e.g. 2h or 30d");
+        }
+        update_doc.insert("default_duration_seconds", seconds);
+    }
+    if let Some(policy) = &req.device_policy {
+        if let Err(e) = policy.validate() {
+            return HttpResponse::BadRequest().body(e);
+        }
+        match bson::to_bson(policy) {
+            Ok(policy) => update_doc.insert("device_policy", policy),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        };
+    }
+    if let Some(allow_owner_pause) = req.allow_owner_pause {
+        update_doc.insert("allow_owner_pause", allow_owner_pause);
+    }
+    if let Some(require_signature) = req.require_signature {
+        update_doc.insert("require_signature", require_signature);
+    }
+    if let Some(description) = &req.description {
+        update_doc.insert("description", description);
+    }
+    let new_secret = if req.rotate_secret {
+        let secret = generate_secret();
+        update_doc.insert("secret", &secret);
+        Some(secret)
+    } else {
+        None
+    };
+
+    let collection = state.db.collection::<Application>("applications");
+    match collection.update_one(doc! { "_id": object_id }, doc! { "$set": update_doc }, None).await {
+        Ok(result) if result.matched_count == 0 => HttpResponse::NotFound().body("Application not found"),
+        Ok(_) => {
+            state.audit.record(&user, "application.update", Some(id), Some(serde_json::json!({
+                "name": req.name,
+                "enabled": req.enabled,
+                "default_duration_seconds": req.default_duration,
+                "rotate_secret": req.rotate_secret,
+            })));
+            match new_secret {
+                Some(secret) => HttpResponse::Ok().json(serde_json::json!({ "secret": secret })),
+                None => HttpResponse::Ok().body("Application updated successfully"),
+            }
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/applications/{id}",
+    tag = "applications",
+    summary = "删除应用",
+    description = "只能删除没有卡密（包括回收站中的卡密）归属的应用；仍有卡密时返回 409，可改为停用应用（enabled 为 false），停用后该应用的验证请求全部被拒绝",
+    params(("id" = String, Path, description = "应用ID")),
+    responses(
+        (status = 200, description = "删除成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "应用不存在"),
+        (status = 409, description = "仍有卡密归属该应用"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn delete_application(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' attempting to delete application {}", user.username, id);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    // 仍有卡密归属的应用不能删除，否则这些卡密无法再通过任何应用验证
+    match state.db.collection::<Card>("cards").count_documents(doc! { "app_id": &id }, None).await {
+        Ok(0) => {}
+        Ok(count) => {
+            warn!("Application {} still has {} cards, refusing to delete", id, count);
+            return HttpResponse::Conflict()
+                .body(format!("Application still has {} cards; disable it instead or delete its cards first", count));
+        }
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    match state.db.collection::<Application>("applications").delete_one(doc! { "_id": object_id }, None).await {
+        Ok(result) if result.deleted_count == 0 => HttpResponse::NotFound().body("Application not found"),
+        Ok(_) => {
+            state.audit.record(&user, "application.delete", Some(id), None);
+            HttpResponse::Ok().body("Application deleted successfully")
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,63 @@
+use actix_web::{web, HttpResponse, Scope};
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+
+use crate::{
+    auth::{require_admin, AuthenticatedUser},
+    models::audit::{AuditLog, AuditQuery},
+    AppState,
+};
+
+// 配置审计日志相关的路由（仅管理员）
+pub fn config() -> Scope {
+    web::scope("/audit-logs")
+        .route("", web::get().to(get_audit_logs))  // 查询审计日志
+}
+
+// 查询审计日志，按时间倒序
+#[utoipa::path(
+    get,
+    path = "/audit-logs",
+    tag = "audit",
+    summary = "查询审计日志",
+    description = "超过 AUDIT_RETENTION_DAYS 的记录由定时任务 prune_audit_logs 删除",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "审计日志，按时间倒序", body = Vec<AuditLog>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_audit_logs(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    query: web::Query<AuditQuery>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    let mut filter = doc! {};
+    if let Some(actor) = &query.actor {
+        filter.insert("actor", actor);
+    }
+    if let Some(action) = &query.action {
+        filter.insert("action", action);
+    }
+    if let Some(target) = &query.target {
+        filter.insert("target", target);
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at_str": -1 })
+        .limit(query.limit.unwrap_or(100).clamp(1, 500))
+        .build();
+
+    match state.db.collection::<AuditLog>("audit_logs").find(filter, options).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<AuditLog>>().await {
+            Ok(logs) => HttpResponse::Ok().json(logs),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
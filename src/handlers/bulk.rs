@@ -0,0 +1,435 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
+use mongodb::Database;
+
+use crate::{
+    auth::AuthenticatedUser,
+    shutdown::Shutdown,
+    handlers::card::scoped_filter,
+    models::bulk::{BulkAction, BulkCardRequest, BulkItemResult, BulkJob, BulkJobStatus, BulkResponse},
+    models::duration::format_duration,
+    models::user::User,
+    models::webhook::EVENT_CARD_REVOKED,
+    models::{Card, UserRole},
+    openapi::BulkJobAccepted,
+    webhook::{card_event_data, dispatch_event},
+    AppState,
+};
+
+// 超过该数量的批量操作转为后台任务执行
+const BULK_SYNC_LIMIT: usize = 100;
+// 单次批量操作的卡密数量上限
+const BULK_MAX_ITEMS: u64 = 10_000;
+// 后台任务每处理多少张卡密保存一次进度
+const BULK_PROGRESS_INTERVAL: usize = 100;
+
+// 批量操作参数
+#[derive(Clone)]
+struct BulkParams {
+    action: BulkAction,
+    seconds: i64,                       // extend: 延长的秒数
+    creator: Option<(String, String)>,  // 新创建者的 (用户ID, 用户名)
+    actor: String,                      // 操作者用户名
+}
+
+// 批量操作卡密处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/bulk",
+    tag = "cards",
+    summary = "批量操作卡密",
+    description = "按 ids 或 filter 指定目标卡密；不超过 100 张时同步执行并返回逐项结果，超过时转为后台任务",
+    request_body = BulkCardRequest,
+    responses(
+        (status = 200, description = "同步执行结果", body = BulkResponse),
+        (status = 202, description = "已创建后台任务", body = BulkJobAccepted),
+        (status = 400, description = "参数错误或目标卡密超过上限"),
+        (status = 403, description = "reassign 需要管理员权限"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+pub async fn bulk_cards(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: web::Json<BulkCardRequest>,
+) -> HttpResponse {
+    let req = req.into_inner();
+    info!("User '{}' is running bulk card operation. Request params: action={:?}, ids={}, has_filter={}, duration={:?}, creator_id={:?}",
+        user.username, req.action, req.ids.as_ref().map_or(0, |ids| ids.len()), req.filter.is_some(), req.duration.map(format_duration), req.creator_id);
+
+    if req.ids.is_some() == req.filter.is_some() {
+        return HttpResponse::BadRequest().body("Exactly one of 'ids' or 'filter' is required");
+    }
+
+    let mut params = BulkParams {
+        action: req.action,
+        seconds: 0,
+        creator: None,
+        actor: user.username.clone(),
+    };
+    match req.action {
+        BulkAction::Extend => match req.duration {
+            Some(seconds) if seconds != 0 => params.seconds = seconds,
+            _ => return HttpResponse::BadRequest().body("'duration' is required and must not be zero for extend"),
+        },
+        BulkAction::Reassign => {
+            if user.role != UserRole::Admin {
+                return HttpResponse::Forbidden().body("Admin privileges required");
+            }
+            let creator_id = match req.creator_id.as_deref().map(ObjectId::parse_str) {
+                Some(Ok(oid)) => oid,
+                _ => return HttpResponse::BadRequest().body("A valid 'creator_id' is required for reassign"),
+            };
+            let filter = doc! { "_id": creator_id, "deleted_at_str": null };
+            match state.db.collection::<User>("users").find_one(filter, None).await {
+                Ok(Some(creator)) => params.creator = Some((creator_id.to_hex(), creator.username)),
+                Ok(None) => return HttpResponse::BadRequest().body("Creator not found"),
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        _ => {}
+    }
+
+    // 确定目标卡密，按 ID 指定时记录无效或不存在的 ID
+    let collection = state.db.collection::<Card>("cards");
+    let mut results = Vec::new();
+    let filter = match (&req.ids, &req.filter) {
+        (Some(ids), _) => {
+            if ids.len() as u64 > BULK_MAX_ITEMS {
+                return HttpResponse::BadRequest().body(format!("At most {} cards per bulk operation", BULK_MAX_ITEMS));
+            }
+            let mut object_ids = Vec::with_capacity(ids.len());
+            for id in ids {
+                match ObjectId::parse_str(id) {
+                    Ok(oid) => object_ids.push(oid),
+                    Err(_) => results.push(failure(id.clone(), None, "Invalid ID format")),
+                }
+            }
+            let mut filter = scoped_filter(&user, &Default::default());
+            filter.insert("_id", doc! { "$in": &object_ids });
+            filter
+        }
+        (None, Some(query)) => scoped_filter(&user, query),
+        (None, None) => unreachable!(),
+    };
+
+    match collection.count_documents(filter.clone(), None).await {
+        Ok(count) if count > BULK_MAX_ITEMS => {
+            warn!("Bulk operation matched {} cards, exceeding the limit", count);
+            return HttpResponse::BadRequest().body(format!("At most {} cards per bulk operation, {} matched", BULK_MAX_ITEMS, count));
+        }
+        Ok(_) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let cards: Vec<Card> = match collection.find(filter, None).await {
+        Ok(cursor) => match cursor.try_collect().await {
+            Ok(cards) => cards,
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    if let Some(ids) = &req.ids {
+        for id in ids {
+            let found = cards.iter().any(|c| c.id.map(|oid| oid.to_hex()).as_deref() == Some(id.as_str()));
+            if !found && ObjectId::parse_str(id).is_ok() {
+                results.push(failure(id.clone(), None, "Card not found or you don't have permission to modify it"));
+            }
+        }
+    }
+
+    state.audit.record(&user, "card.bulk", None, Some(serde_json::json!({
+        "action": params.action,
+        "count": cards.len(),
+        "seconds": params.seconds,
+        "creator_id": req.creator_id,
+    })));
+
+    // 少量卡密直接处理并返回逐项结果
+    if cards.len() <= BULK_SYNC_LIMIT {
+        for card in &cards {
+            results.push(process_card(&state.db, card, &params).await);
+        }
+        let succeeded = results.iter().filter(|r| r.success).count() as i64;
+        info!("Bulk {:?} by '{}' finished: {} succeeded, {} failed", params.action, user.username, succeeded, results.len() as i64 - succeeded);
+        return HttpResponse::Ok().json(BulkResponse {
+            action: params.action,
+            total: results.len() as i64,
+            succeeded,
+            failed: results.len() as i64 - succeeded,
+            results,
+        });
+    }
+
+    // 大量卡密转为后台任务
+    let failed = results.len() as i64;
+    let job = BulkJob {
+        id: None,
+        action: params.action,
+        status: BulkJobStatus::Running,
+        total: cards.len() as i64 + failed,
+        processed: failed,
+        succeeded: 0,
+        failed,
+        results,
+        error: None,
+        created_by: user.user_id.clone(),
+        created_by_username: user.username.clone(),
+        created_at_str: Utc::now().to_rfc3339(),
+        finished_at_str: None,
+    };
+
+    let job_id = match state.db.collection::<BulkJob>("bulk_jobs").insert_one(&job, None).await {
+        Ok(result) => match result.inserted_id.as_object_id() {
+            Some(oid) => oid,
+            None => return HttpResponse::InternalServerError().body("Failed to create bulk job"),
+        },
+        Err(e) => {
+            error!("Failed to create bulk job: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    info!("Bulk {:?} by '{}' queued as job {} with {} cards", params.action, user.username, job_id.to_hex(), cards.len());
+    let db = state.db.clone();
+    let shutdown = state.shutdown.clone();
+    state.shutdown.spawn(run_bulk_job(db, job_id, cards, params, shutdown));
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "job_id": job_id.to_hex(),
+        "total": job.total,
+    }))
+}
+
+// 获取批量任务进度与结果
+#[utoipa::path(
+    get,
+    path = "/cards/bulk/{job_id}",
+    tag = "cards",
+    summary = "查询批量任务进度",
+    params(("job_id" = String, Path, description = "批量任务ID")),
+    responses(
+        (status = 200, description = "任务进度与结果", body = BulkJob),
+        (status = 400, description = "ID 格式错误"),
+        (status = 404, description = "任务不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+pub async fn get_bulk_job(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let object_id = match ObjectId::parse_str(path.into_inner()) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    // 普通用户只能查看自己发起的任务
+    let filter = match user.role {
+        UserRole::Admin => doc! { "_id": object_id },
+        _ => doc! { "_id": object_id, "created_by": &user.user_id },
+    };
+
+    match state.db.collection::<BulkJob>("bulk_jobs").find_one(filter, None).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => HttpResponse::NotFound().body("Bulk job not found"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// 在后台逐张处理卡密，定期保存进度；停机时在当前批次完成后中止并标记为失败
+async fn run_bulk_job(db: Database, job_id: ObjectId, cards: Vec<Card>, params: BulkParams, shutdown: Shutdown) {
+    let jobs = db.collection::<BulkJob>("bulk_jobs");
+
+    for chunk in cards.chunks(BULK_PROGRESS_INTERVAL) {
+        if shutdown.is_requested() {
+            warn!("Bulk job {} interrupted by shutdown", job_id.to_hex());
+            let status = match bson::to_bson(&BulkJobStatus::Failed) {
+                Ok(status) => status,
+                Err(e) => {
+                    error!("Failed to serialize bulk job {} status: {}", job_id.to_hex(), e);
+                    return;
+                }
+            };
+            let update = doc! {
+                "$set": {
+                    "status": status,
+                    "error": "Interrupted by server shutdown",
+                    "finished_at_str": Utc::now().to_rfc3339(),
+                }
+            };
+            if let Err(e) = jobs.update_one(doc! { "_id": job_id }, update, None).await {
+                error!("Failed to mark bulk job {} as interrupted: {}", job_id.to_hex(), e);
+            }
+            return;
+        }
+
+        let mut results = Vec::with_capacity(chunk.len());
+        for card in chunk {
+            results.push(process_card(&db, card, &params).await);
+        }
+        let succeeded = results.iter().filter(|r| r.success).count() as i64;
+        let failed = results.len() as i64 - succeeded;
+
+        let results = match bson::to_bson(&results) {
+            Ok(results) => results,
+            Err(e) => {
+                error!("Failed to serialize bulk job {} results: {}", job_id.to_hex(), e);
+                continue;
+            }
+        };
+        let update = doc! {
+            "$inc": { "processed": succeeded + failed, "succeeded": succeeded, "failed": failed },
+            "$push": { "results": { "$each": results } },
+        };
+        if let Err(e) = jobs.update_one(doc! { "_id": job_id }, update, None).await {
+            error!("Failed to update bulk job {} progress: {}", job_id.to_hex(), e);
+        }
+    }
+
+    let status = match bson::to_bson(&BulkJobStatus::Completed) {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Failed to serialize bulk job {} status: {}", job_id.to_hex(), e);
+            return;
+        }
+    };
+    let update = doc! {
+        "$set": {
+            "status": status,
+            "finished_at_str": Utc::now().to_rfc3339(),
+        }
+    };
+    match jobs.update_one(doc! { "_id": job_id }, update, None).await {
+        Ok(_) => info!("Bulk job {} completed", job_id.to_hex()),
+        Err(e) => error!("Failed to finish bulk job {}: {}", job_id.to_hex(), e),
+    }
+}
+
+// 启动时将上次运行中未结束的批量任务标记为失败（进程崩溃或被强制结束时未能执行停机中断），返回标记的数量；
+// 已处理的卡密保留在 results 中，未处理的卡密可以重新提交
+pub async fn fail_interrupted_jobs(db: &Database) -> Result<u64, mongodb::error::Error> {
+    let update = doc! {
+        "$set": {
+            "status": bson::to_bson(&BulkJobStatus::Failed)?,
+            "error": "Interrupted by server restart",
+            "finished_at_str": Utc::now().to_rfc3339(),
+        }
+    };
+    let result = db
+        .collection::<BulkJob>("bulk_jobs")
+        .update_many(doc! { "status": bson::to_bson(&BulkJobStatus::Running)? }, update, None)
+        .await?;
+    Ok(result.modified_count)
+}
+
+fn failure(id: String, card_number: Option<String>, error: &str) -> BulkItemResult {
+    BulkItemResult { id, card_number, success: false, error: Some(error.to_string()) }
+}
+
+// 对单张卡密执行批量操作
+async fn process_card(db: &Database, card: &Card, params: &BulkParams) -> BulkItemResult {
+    let Some(id) = card.id else {
+        return failure(String::new(), Some(card.card_number.clone()), "Card has no ID");
+    };
+    let now = Utc::now().to_rfc3339();
+
+    let (filter, update): (Document, Document) = match params.action {
+        BulkAction::Delete => (
+            doc! { "_id": id, "deleted_at_str": null },
+            doc! { "$set": { "deleted_at_str": &now, "deleted_by": &params.actor } },
+        ),
+        BulkAction::Revoke => (
+            doc! { "_id": id, "is_revoked": { "$ne": true } },
+            doc! { "$set": { "is_revoked": true, "revoked_at_str": &now, "revoked_by": &params.actor } },
+        ),
+        BulkAction::Extend => {
+            // 不限时间的卡密没有有效期可以延长
+            if !card.is_time_limited() {
+                return failure(id.to_hex(), Some(card.card_number.clone()), "Card has no time limit");
+            }
+            // 只有固定到期时间的卡密（有效期为0）只顺延固定到期时间
+            let duration_seconds = match card.duration_seconds {
+                0 => 0,
+                seconds => seconds + params.seconds,
+            };
+            if card.duration_seconds != 0 && duration_seconds <= 0 {
+                return failure(id.to_hex(), Some(card.card_number.clone()), "Resulting duration must be positive");
+            }
+            let mut set = doc! { "duration_seconds": duration_seconds, "expiry_notified": false };
+            if let Some(valid_until) = card.valid_until_str.as_deref().and_then(|s| s.parse::<DateTime<Utc>>().ok()) {
+                set.insert("valid_until_str", (valid_until + Duration::seconds(params.seconds)).to_rfc3339());
+            }
+            // 已暂停的卡密顺延剩余时间，已激活的卡密同步顺延过期时间
+            if let Some(remaining_seconds) = card.paused_remaining_seconds.filter(|_| card.is_paused) {
+                set.insert("paused_remaining_seconds", (remaining_seconds + params.seconds).max(0));
+            } else if let Some(expires_at) = card.expires_at_str.as_deref().and_then(|s| s.parse::<DateTime<Utc>>().ok()) {
+                let new_expires_at = expires_at + Duration::seconds(params.seconds);
+                set.insert("expires_at_str", new_expires_at.to_rfc3339());
+                if new_expires_at > Utc::now() {
+                    set.insert("is_expired", false);
+                }
+            }
+            (
+                doc! { "_id": id, "duration_seconds": card.duration_seconds },
+                doc! { "$set": set },
+            )
+        }
+        BulkAction::Reassign => {
+            let (creator_id, creator_username) = params.creator.clone().unwrap_or_default();
+            (
+                doc! { "_id": id },
+                doc! { "$set": { "created_by": creator_id, "created_by_username": creator_username } },
+            )
+        }
+        BulkAction::Unbind => (
+            doc! { "_id": id },
+            doc! { "$set": { "bindings": [] }, "$unset": { "used_by": "" } },
+        ),
+    };
+
+    match db.collection::<Card>("cards").update_one(filter, update, None).await {
+        Ok(result) if result.matched_count == 0 => {
+            let reason = match params.action {
+                BulkAction::Revoke => "Card already revoked",
+                BulkAction::Delete => "Card already deleted",
+                _ => "Card was modified concurrently",
+            };
+            failure(id.to_hex(), Some(card.card_number.clone()), reason)
+        }
+        Ok(_) => {
+            // 删除后卡密不能再使用，与撤销相同发送 card.revoked 事件
+            match params.action {
+                BulkAction::Revoke => {
+                    let mut revoked = card.clone();
+                    revoked.is_revoked = true;
+                    revoked.revoked_at_str = Some(now);
+                    revoked.revoked_by = Some(params.actor.clone());
+                    dispatch_event(db, EVENT_CARD_REVOKED, card_event_data(&revoked)).await;
+                }
+                BulkAction::Delete => {
+                    let mut deleted = card.clone();
+                    deleted.deleted_at_str = Some(now);
+                    deleted.deleted_by = Some(params.actor.clone());
+                    dispatch_event(db, EVENT_CARD_REVOKED, card_event_data(&deleted)).await;
+                }
+                _ => {}
+            }
+            BulkItemResult {
+                id: id.to_hex(),
+                card_number: Some(card.card_number.clone()),
+                success: true,
+                error: None,
+            }
+        }
+        Err(e) => {
+            error!("Bulk {:?} failed for card '{}': {}", params.action, card.card_number, e);
+            failure(id.to_hex(), Some(card.card_number.clone()), &e.to_string())
+        }
+    }
+}
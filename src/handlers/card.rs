@@ -1,27 +1,124 @@
 use actix_web::{web, HttpResponse, Scope};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use log::{info, warn, error};  // 添加日志级别导入
-use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::bson::{self, doc, oid::ObjectId, Bson, Document};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::{Collection, Database};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use crate::{
     auth::AuthenticatedUser,
-    models::{Card, CreateCardRequest, ActivateCardRequest, VerifyCardRequest, UserRole},
+    client_auth::{require_signed, ClientJson, CODE_APP_INVALID},
+    envelope::with_code,
+    handlers::application::find_application,
+    handlers::bulk,
+    handlers::pause,
+    handlers::rebind::{self, issue_owner_secret, rejection},
+    handlers::v2::{
+        CODE_CARD_CONFLICT, CODE_CARD_EXHAUSTED, CODE_CARD_EXPIRED, CODE_CARD_INVALID, CODE_CARD_NOT_ACTIVATED,
+        CODE_CARD_NOT_FOUND, CODE_CARD_NOT_METERED, CODE_CARD_PAUSED, CODE_CARD_REVOKED, CODE_CARD_USED_BY_OTHER,
+        CODE_IDENTIFIER_REQUIRED, CODE_CARD_NOT_YET_VALID, CODE_CARD_VALIDITY_ENDED, CODE_ACTIVATION_CLOSED,
+    },
+    client_ip::ClientIp,
+    metrics::{metrics, REJECT_ALREADY_ACTIVATED, REJECT_EXPIRED, REJECT_EXHAUSTED, REJECT_NOT_FOUND, REJECT_PAUSED, REJECT_REVOKED, REJECT_USED_BY_OTHER,
+        REJECT_NOT_YET_VALID, REJECT_VALIDITY_ENDED, REJECT_ACTIVATION_CLOSED, REJECT_NOT_METERED},
+    models::{
+        Card, CardQuery, CardSpec, ConsumeCardRequest, ConsumeCardResponse, CreateCardRequest, ActivateCardRequest, ActivateCardResponse,
+        VerifyCardRequest, UserRole,
+    },
+    models::application::{Application, HeartbeatRequest, HeartbeatResponse},
+    models::device::PresentedDevice,
+    models::duration::format_duration,
+    models::webhook::{EVENT_CARD_ACTIVATED, EVENT_CARD_EXHAUSTED, EVENT_CARD_REVOKED},
+    webhook::{card_event_data, dispatch_event},
+    openapi::{CardRejection, VerifyCardResponse},
     AppState,
 };
 
 // 配置卡密相关的路由
 pub fn config() -> Scope {
-    web::scope("/api/cards")
+    web::scope("/cards")
         .route("/generate", web::post().to(generate_card))     // 生成卡密
         .route("/activate", web::post().to(activate_card))     // 激活卡密
         .route("/verify", web::post().to(verify_card))         // 验证卡密
+        .route("/heartbeat", web::post().to(heartbeat))        // 客户端心跳（需签名）
+        .route("/consume", web::post().to(consume_card))       // 消耗次数（按次数计费的卡密）
+        .route("/rebind", web::post().to(rebind::rebind_card))  // 自助换绑设备
+        .route("/rebind/code", web::post().to(rebind::request_rebind_code))  // 申请换绑邮箱验证码
+        .route("/pause", web::post().to(pause::owner_pause_card))     // 所有者暂停卡密
+        .route("/resume", web::post().to(pause::owner_resume_card))   // 所有者恢复卡密
         .route("", web::get().to(get_all_cards))               // 获取所有卡密
         .route("/export", web::get().to(export_cards))         // 导出卡密
-        .route("/{id}", web::delete().to(delete_card))         // 删除卡密
+        .route("/trash", web::get().to(get_trash))             // 获取回收站中的卡密
+        .route("/bulk", web::post().to(bulk::bulk_cards))      // 批量操作卡密
+        .route("/bulk/{job_id}", web::get().to(bulk::get_bulk_job))  // 查询批量任务进度
+        .route("/{id}", web::delete().to(delete_card))         // 删除卡密（移入回收站）
+        .route("/{id}/restore", web::post().to(restore_card))  // 从回收站恢复卡密
+        .route("/{id}/pause", web::post().to(pause::pause_card))    // 暂停卡密
+        .route("/{id}/resume", web::post().to(pause::resume_card))  // 恢复已暂停的卡密
+        .route("/{id}/owner-secret", web::post().to(rebind::reissue_owner_secret))  // 重新签发所有者密钥
+}
+
+// 组合筛选条件与角色范围：普通用户只能操作自己创建的卡密
+pub(crate) fn scoped_filter(user: &AuthenticatedUser, query: &CardQuery) -> Document {
+    let mut filter = query.to_filter();
+    if user.role != UserRole::Admin {
+        filter.insert("created_by", &user.user_id);
+    }
+    filter
+}
+
+// 生成一批随机卡号的卡密，同一次生成的卡密属于同一批次，命令行生成时没有创建者
+pub(crate) fn new_batch(
+    count: i32,
+    spec: &CardSpec,
+    card_length: usize,
+    created_by: Option<(&str, &str)>,
+    app_id: Option<&str>,
+) -> Vec<Card> {
+    let batch_id = ObjectId::new().to_hex();
+    (0..count)
+        .map(|_| {
+            let card_number: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(card_length)
+                .map(char::from)
+                .collect();
+
+            let mut card = Card::new(card_number, spec.duration_seconds);
+            if let Some((user_id, username)) = created_by {
+                card.created_by = Some(user_id.to_string());
+                card.created_by_username = Some(username.to_string());
+            }
+            card.batch_id = Some(batch_id.clone());
+            card.app_id = app_id.map(str::to_string);
+            card.usage_limit = spec.usage_limit;
+            card.remaining_uses = spec.usage_limit;
+            card.shelf_expires_at_str = spec.shelf_life_seconds
+                .map(|seconds| (card.created_at + Duration::seconds(seconds)).to_rfc3339());
+            card.valid_from_str = spec.valid_from.map(|time| time.to_rfc3339());
+            card.activate_before_str = spec.activate_before.map(|time| time.to_rfc3339());
+            card.valid_until_str = spec.valid_until.map(|time| time.to_rfc3339());
+            card
+        })
+        .collect()
 }
 
 // 生成卡密处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/generate",
+    tag = "cards",
+    summary = "生成卡密",
+    description = "一次生成 1-100 张卡密，同一次生成的卡密属于同一批次；指定 app_id 时卡密归属该应用，未指定 duration 则使用应用的默认套餐。duration 和 shelf_life 支持 30m、2h、7d 等格式，数字按天计算；指定 usage_limit 时按次数计费，duration 为0表示不限时间；指定 shelf_life 时超过保质期仍未激活的卡密自动过期；valid_from、activate_before、valid_until 分别为生效时间、激活截止时间和固定到期时间（RFC3339），指定 valid_until 时 duration 可以为0",
+    request_body = CreateCardRequest,
+    responses(
+        (status = 200, description = "生成的卡密", body = Vec<Card>),
+        (status = 400, description = "未指定有效期、次数或时间窗口无效、应用不存在"),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn generate_card(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -30,39 +127,63 @@ async fn generate_card(
     // 使用 .0 或 .into_inner() 访问内部的 CreateCardRequest
     let req_inner = req.into_inner();
     
-    info!("User '{}' is generating card. Request params: duration_days={}, count={}", 
-        user.username, req_inner.duration_days, req_inner.count);
+    info!("User '{}' is generating card. Request params: duration={:?}, count={}, app_id={:?}, usage_limit={:?}, shelf_life={:?}, valid_from={:?}, activate_before={:?}, valid_until={:?}", 
+        user.username, req_inner.duration.map(format_duration), req_inner.count, req_inner.app_id, req_inner.usage_limit,
+        req_inner.shelf_life.map(format_duration), req_inner.valid_from, req_inner.activate_before, req_inner.valid_until);
+    
+    // 指定了应用时，未指定有效期则使用应用的默认套餐
+    let app = match &req_inner.app_id {
+        Some(app_id) => match find_application(&state.db, app_id).await {
+            Ok(Some(app)) => Some(app),
+            Ok(None) => return HttpResponse::BadRequest().body("Application not found"),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        None => None,
+    };
+    let Some(duration_seconds) = req_inner.duration.or(app.as_ref().map(|app| app.default_duration_seconds)) else {
+        return HttpResponse::BadRequest().body("duration must be positive, e.g. 30m, 2h or 7d");
+    };
+    let spec = CardSpec {
+        duration_seconds,
+        usage_limit: req_inner.usage_limit,
+        shelf_life_seconds: req_inner.shelf_life,
+        valid_from: req_inner.valid_from,
+        activate_before: req_inner.activate_before,
+        valid_until: req_inner.valid_until,
+    };
+    if let Err(e) = spec.validate() {
+        return HttpResponse::BadRequest().body(e);
+    }
     
-    let card_length = std::env::var("DEFAULT_CARD_LENGTH")
-        .unwrap_or_else(|_| "16".to_string())
-        .parse::<usize>()
-        .unwrap_or(16);
+    let card_length = state.config.card.default_length;
     
     // 限制生成数量在1-100之间
-    let count = req_inner.count.max(1).min(100);
-    let mut cards = Vec::with_capacity(count as usize);
-    
-    for _ in 0..count {
-        let card_number: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(card_length)
-            .map(char::from)
-            .collect();
-        
-        // 创建卡密时记录创建者ID
-        let mut card = Card::new(card_number.clone(), req_inner.duration_days);
-        card.created_by = Some(user.user_id.clone());
-        card.created_by_username = Some(user.username.clone());
-        
-        cards.push(card);
-    }
+    let count = req_inner.count.clamp(1, 100);
+    let cards = new_batch(
+        count,
+        &spec,
+        card_length,
+        Some((&user.user_id, &user.username)),
+        req_inner.app_id.as_deref(),
+    );
     
     // 批量插入卡密
-    match state.db.collection::<Card>("cards")
-        .insert_many(&cards, None)
+    let documents = match cards.iter().map(Card::to_document).collect::<Result<Vec<_>, _>>() {
+        Ok(documents) => documents,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match state.db.collection::<Document>("cards")
+        .insert_many(documents, None)
         .await {
             Ok(_) => {
                 info!("Generated {} cards successfully by user '{}'", count, user.username);
+                metrics().cards_generated.inc_by(count as u64);
+                state.audit.record(&user, "card.generate", cards.first().and_then(|card| card.batch_id.clone()), Some(serde_json::json!({
+                    "count": count,
+                    "duration_seconds": duration_seconds,
+                    "app_id": req_inner.app_id,
+                    "usage_limit": req_inner.usage_limit,
+                })));
                 HttpResponse::Ok().json(cards)
             },
             Err(e) => {
@@ -73,18 +194,60 @@ async fn generate_card(
 }
 
 // 激活卡密处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/activate",
+    tag = "cards",
+    summary = "激活卡密",
+    description = "激活未使用的卡密但不绑定使用者，有效期从激活时开始计算（有固定到期时间时不晚于该时间），并签发所有者密钥（只返回这一次）。应用与签名规则同验证卡密：归属应用的卡密只能通过该应用（签名请求或 app_key）激活。未到生效时间、超过固定到期时间、激活截止时间或保质期时拒绝，v2 的 code 分别为 CARD_NOT_YET_VALID、CARD_VALIDITY_ENDED、ACTIVATION_CLOSED、CARD_EXPIRED",
+    request_body = ActivateCardRequest,
+    params(
+        ("X-App-Key" = Option<String>, Header, description = "应用标识，签名请求必填"),
+        ("X-Timestamp" = Option<String>, Header, description = "Unix 时间戳（秒）"),
+        ("X-Nonce" = Option<String>, Header, description = "随机字符串，每个请求不同"),
+        ("X-Signature" = Option<String>, Header, description = "请求签名"),
+    ),
+    responses(
+        (status = 200, description = "激活后的卡密，附带签发的所有者密钥", body = ActivateCardResponse),
+        (status = 400, description = "卡密已激活、已撤销或不在使用时间窗口内", body = CardRejection),
+        (status = 401, description = "签名校验失败或应用无效"),
+        (status = 404, description = "卡密不存在或不属于该应用"),
+    ),
+)]
 async fn activate_card(
     state: web::Data<AppState>,
-    req: web::Json<ActivateCardRequest>,
+    req: ClientJson<ActivateCardRequest>,
 ) -> HttpResponse {
-    info!("Attempting to activate card. Request params: card_number={}", req.card_number);
-    
+    info!("Attempting to activate card. Request params: card_number={}, app={:?}", req.card_number, req.app.as_ref().map(|app| &app.name));
+
+    let ClientJson { app, body: req } = req;
+    let app = match resolve_app(&state.db, app, req.app_key.as_deref()).await {
+        Ok(Some(app)) => app,
+        Ok(None) => return rejection(HttpResponse::Unauthorized(), CODE_APP_INVALID, "Unknown or disabled application"),
+        Err(e) => {
+            error!("Error resolving application for card '{}': {}", req.card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    // 归属应用的卡密只能通过该应用激活，其他应用的卡密视为不存在
     let collection = state.db.collection::<Card>("cards");
-    let card_filter = doc! { "card_number": &req.card_number };
+    let card_filter = doc! { "card_number": &req.card_number, "deleted_at_str": null, "app_id": app_scope(app.as_ref()) };
 
     match collection.find_one(card_filter, None).await {
         Ok(Some(card)) => {
+            if card.is_revoked {
+                metrics().reject_card(REJECT_REVOKED);
+                warn!("Card '{}' has been revoked", req.card_number);
+                return HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": "Card revoked",
+                    "message": "This card has been revoked",
+                    "card": card
+                }));
+            }
+
             if card.is_activated {
+                metrics().reject_card(REJECT_ALREADY_ACTIVATED);
                 warn!("Card '{}' is already activated", req.card_number);
                 return HttpResponse::BadRequest().json(serde_json::json!({
                     "error": "Card already activated",
@@ -93,12 +256,37 @@ async fn activate_card(
                 }));
             }
 
+            // 未到生效时间、超过固定到期时间、激活截止时间或保质期的卡密不能激活
+            let now = Utc::now();
+            if let Some(reason) = window_rejection(&card, now) {
+                metrics().reject_card(reason.metric_reason());
+                warn!("Card '{}' rejected: {:?}", req.card_number, reason);
+                let (code, error, message) = match reason {
+                    VerifyRejection::NotYetValid => (CODE_CARD_NOT_YET_VALID, "Card not yet valid", "This card is not valid yet"),
+                    VerifyRejection::ValidityEnded => (CODE_CARD_VALIDITY_ENDED, "Card validity ended", "This card has passed its fixed expiry date"),
+                    VerifyRejection::ActivationClosed => (CODE_ACTIVATION_CLOSED, "Activation deadline passed", "This card can no longer be activated"),
+                    _ => (CODE_CARD_EXPIRED, "Card expired", "This card was not activated before its shelf life ended"),
+                };
+                return with_code(
+                    HttpResponse::BadRequest().json(serde_json::json!({
+                        "error": error,
+                        "message": message,
+                        "card": card
+                    })),
+                    code,
+                    message.to_string(),
+                );
+            }
+
             let filter = doc! {
                 "card_number": &req.card_number,
                 "is_activated": false,
+                "is_revoked": { "$ne": true },
+                "deleted_at_str": null,
+                "shelf_expires_at_str": { "$not": { "$lte": now.to_rfc3339() } },
+                "activate_before_str": { "$not": { "$lte": now.to_rfc3339() } },
             };
 
-            let now = Utc::now();
             let updated_card = card.with_activation(now, None, None);
             
             let update = doc! {
@@ -119,7 +307,10 @@ async fn activate_card(
                     match collection.find_one(doc! { "card_number": &req.card_number }, None).await {
                         Ok(Some(card)) => {
                             info!("Card '{}' activated successfully", req.card_number);
-                            HttpResponse::Ok().json(card)
+                            let owner_secret = issue_owner_secret(&state.db, &card, req.email.as_deref()).await;
+                            dispatch_event(&state.db, EVENT_CARD_ACTIVATED, card_event_data(&card)).await;
+                            metrics().cards_activated.inc();
+                            HttpResponse::Ok().json(ActivateCardResponse { card, owner_secret })
                         },
                         Ok(None) => {
                             error!("Card '{}' not found after update", req.card_number);
@@ -138,6 +329,7 @@ async fn activate_card(
             }
         },
         Ok(None) => {
+            metrics().reject_card(REJECT_NOT_FOUND);
             warn!("Card '{}' not found", req.card_number);
             HttpResponse::NotFound().body("Card not found")
         },
@@ -148,126 +340,591 @@ async fn activate_card(
     }
 }
 
-// 验证卡密处理函数
-async fn verify_card(
-    state: web::Data<AppState>,
-    req: web::Json<VerifyCardRequest>,
-    client_ip: web::ReqData<String>, // 获取客户端IP
-) -> HttpResponse {
-    info!("Verifying card. Request params: card_number={}", req.card_number);
-    
+// 卡密验证被拒绝的原因
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum VerifyRejection {
+    Revoked,      // 已撤销
+    Expired,      // 已过期
+    UsedByOther,  // 已绑定其他使用者
+    Paused,       // 已暂停
+    Exhausted,    // 次数已用完或不足
+    NotYetValid,      // 未到生效时间
+    ValidityEnded,    // 已超过固定到期时间
+    ActivationClosed, // 未激活且已超过激活截止时间
+}
+
+impl VerifyRejection {
+    // 指标中的拒绝原因
+    fn metric_reason(self) -> &'static str {
+        match self {
+            VerifyRejection::Revoked => REJECT_REVOKED,
+            VerifyRejection::Expired => REJECT_EXPIRED,
+            VerifyRejection::UsedByOther => REJECT_USED_BY_OTHER,
+            VerifyRejection::Paused => REJECT_PAUSED,
+            VerifyRejection::Exhausted => REJECT_EXHAUSTED,
+            VerifyRejection::NotYetValid => REJECT_NOT_YET_VALID,
+            VerifyRejection::ValidityEnded => REJECT_VALIDITY_ENDED,
+            VerifyRejection::ActivationClosed => REJECT_ACTIVATION_CLOSED,
+        }
+    }
+}
+
+// 检查卡密的使用时间窗口：生效时间和固定到期时间对所有卡密生效，激活截止时间和保质期只限制未激活的卡密
+pub(crate) fn window_rejection(card: &Card, now: DateTime<Utc>) -> Option<VerifyRejection> {
+    if card.is_not_yet_valid(now) {
+        Some(VerifyRejection::NotYetValid)
+    } else if card.is_validity_ended(now) {
+        Some(VerifyRejection::ValidityEnded)
+    } else if card.is_activation_closed(now) {
+        Some(VerifyRejection::ActivationClosed)
+    } else if card.is_shelf_expired(now) {
+        Some(VerifyRejection::Expired)
+    } else {
+        None
+    }
+}
+
+// 卡密验证结果，v1 与 v2 接口共用验证逻辑，只在响应格式上不同
+pub(crate) enum VerifyOutcome {
+    Verified(Card),                   // 已激活且属于当前使用者
+    Activated(Card, Option<String>),  // 本次验证激活了卡密，附带签发的所有者密钥
+    Rejected(VerifyRejection, Card),  // 验证被拒绝
+    NotFound,                         // 卡密不存在
+    InvalidExpiry,                    // 过期时间无法解析
+    ActivationConflict,               // 并发请求抢先激活了卡密
+    AppInvalid,                       // 请求中的应用标识不存在或应用已停用
+    IdentifierRequired,               // 应用的设备策略要求提供 user_identifier
+    NotMetered,                       // 只接受按次数计费的卡密时，卡密不按次数计费
+}
+
+// 卡密归属范围的查询条件：指定应用时匹配该应用及未归属应用的卡密，未指定时只匹配未归属应用的卡密
+pub(crate) fn app_scope(app: Option<&Application>) -> Bson {
+    match app.and_then(|app| app.id) {
+        Some(id) => Bson::Document(doc! { "$in": [id.to_hex(), Bson::Null] }),
+        None => Bson::Null,
+    }
+}
+
+// 确定请求所属的应用：签名请求使用签名的应用，未签名时按请求体中的 app_key 查找启用的应用；
+// app_key 无效或应用开启了 require_signature 时返回 None
+pub(crate) async fn resolve_app(
+    db: &Database,
+    signed_app: Option<Application>,
+    app_key: Option<&str>,
+) -> Result<Option<Option<Application>>, mongodb::error::Error> {
+    match (signed_app, app_key) {
+        (Some(app), _) => Ok(Some(Some(app))),
+        (None, Some(app_key)) => {
+            let filter = doc! { "app_key": app_key, "enabled": true };
+            let app = db.collection::<Application>("applications").find_one(filter, None).await?;
+            // 要求签名的应用不能通过请求体中的 app_key 绕过签名
+            Ok(app.filter(|app| !app.require_signature).map(Some))
+        }
+        (None, None) => Ok(Some(None)),
+    }
+}
+
+// 验证卡密：未激活的卡密在首次验证时激活并绑定使用者，已激活的卡密只允许绑定的使用者通过；
+// signed_app 为签名请求的应用，未签名时使用请求体中的 app_key；
+// 按次数计费的卡密剩余次数少于 required_uses 时拒绝（只检查，不扣减）；
+// metered_only 为 true 时拒绝不按次数计费的卡密，且不会激活这些卡密
+pub(crate) async fn verify(
+    state: &AppState,
+    req: &VerifyCardRequest,
+    signed_app: Option<Application>,
+    client_ip: ClientIp,
+    required_uses: i64,
+    metered_only: bool,
+) -> Result<VerifyOutcome, mongodb::error::Error> {
+    let outcome = verify_inner(state, req, signed_app, client_ip, required_uses, metered_only).await?;
+    match &outcome {
+        VerifyOutcome::Verified(_) => metrics().cards_verified.inc(),
+        VerifyOutcome::Activated(..) => {
+            metrics().cards_verified.inc();
+            metrics().cards_activated.inc();
+        }
+        VerifyOutcome::Rejected(reason, _) => {
+            metrics().reject_card(reason.metric_reason());
+            warn!("Card '{}' rejected: {:?}", req.card_number, reason);
+        }
+        VerifyOutcome::NotFound => metrics().reject_card(REJECT_NOT_FOUND),
+        VerifyOutcome::NotMetered => metrics().reject_card(REJECT_NOT_METERED),
+        _ => {}
+    }
+    Ok(outcome)
+}
+
+async fn verify_inner(
+    state: &AppState,
+    req: &VerifyCardRequest,
+    signed_app: Option<Application>,
+    client_ip: ClientIp,
+    required_uses: i64,
+    metered_only: bool,
+) -> Result<VerifyOutcome, mongodb::error::Error> {
+    let app = match resolve_app(&state.db, signed_app, req.app_key.as_deref()).await? {
+        Some(app) => app,
+        None => return Ok(VerifyOutcome::AppInvalid),
+    };
+    let presented = PresentedDevice::new(req.user_identifier.clone(), req.device.clone(), client_ip);
+    let policy = app.as_ref().map(|app| app.device_policy.clone()).unwrap_or_default();
+    if policy.require_identifier && req.user_identifier.is_none() && presented.device.is_none() {
+        return Ok(VerifyOutcome::IdentifierRequired);
+    }
+
+    // 归属应用的卡密只能通过该应用验证，其他应用的卡密视为不存在
     let collection = state.db.collection::<Card>("cards");
-    let card_filter = doc! { "card_number": &req.card_number };
+    let card_filter = doc! {
+        "card_number": &req.card_number,
+        "deleted_at_str": null,
+        "app_id": app_scope(app.as_ref()),
+    };
 
-    match collection.find_one(card_filter, None).await {
-        Ok(Some(card)) => {
-            // 如果卡密已激活
-            if card.is_activated {
-                // 检查卡密是否已过期
-                if let Some(expires_at_str) = &card.expires_at_str {
-                    if let Ok(expires_at) = expires_at_str.parse::<DateTime<Utc>>() {
-                        let mut card_with_dates = card.clone();
-                        card_with_dates.expires_at = Some(expires_at);
-                        
-                        // 检查是否过期
-                        if expires_at <= Utc::now() {
-                            warn!("Card '{}' has expired", req.card_number);
-                            return HttpResponse::BadRequest().json(serde_json::json!({
-                                "error": "Card expired",
-                                "message": "此卡密已过期",
-                                "card": card_with_dates
-                            }));
-                        }
-                        
-                        // 检查是否已被其他用户使用
-                        if let Some(used_by_identifier) = &card.used_by_identifier {
-                            let current_identifier = req.user_identifier.clone()
-                                .unwrap_or_else(|| client_ip.to_string());  // 简化为直接转换为字符串
-                            
-                            if used_by_identifier != &current_identifier {
-                                warn!("Card '{}' is already used by another user", req.card_number);
-                                return HttpResponse::BadRequest().json(serde_json::json!({
-                                    "error": "Card already used",
-                                    "message": "此卡密已被其他用户使用",
-                                    "card": card_with_dates
-                                }));
-                            }
-                        }
-                        
-                        // 卡密有效且属于当前用户
-                        return HttpResponse::Ok().json(card_with_dates);
-                    }
+    let card = match collection.find_one(card_filter, None).await? {
+        Some(card) => card,
+        None => return Ok(VerifyOutcome::NotFound),
+    };
+
+    // 已撤销的卡密不再通过验证
+    if card.is_revoked {
+        return Ok(VerifyOutcome::Rejected(VerifyRejection::Revoked, card));
+    }
+
+    // 已暂停的卡密恢复前不能使用
+    if card.is_paused {
+        return Ok(VerifyOutcome::Rejected(VerifyRejection::Paused, card));
+    }
+
+    // 消耗次数时不按次数计费的卡密直接拒绝，未激活的卡密不会被激活
+    if metered_only && card.usage_limit.is_none() {
+        return Ok(VerifyOutcome::NotMetered);
+    }
+
+    // 按次数计费的卡密次数不足时拒绝，未激活的卡密也不会被激活
+    if card.remaining_uses.is_some_and(|uses| uses < required_uses) {
+        return Ok(VerifyOutcome::Rejected(VerifyRejection::Exhausted, card));
+    }
+
+    // 未到生效时间、超过固定到期时间，或未激活的卡密超过激活截止时间、保质期时拒绝
+    let now = Utc::now();
+    if let Some(reason) = window_rejection(&card, now) {
+        return Ok(VerifyOutcome::Rejected(reason, card));
+    }
+
+    // 如果卡密已激活
+    if card.is_activated {
+        let mut card_with_dates = card.clone();
+
+        // 检查是否过期，不限时间的卡密没有过期时间
+        if card.is_time_limited() {
+            let expires_at = match card.expires_at_str.as_ref().and_then(|s| s.parse::<DateTime<Utc>>().ok()) {
+                Some(expires_at) => expires_at,
+                None => return Ok(VerifyOutcome::InvalidExpiry),
+            };
+            card_with_dates.expires_at = Some(expires_at);
+            if expires_at <= Utc::now() {
+                return Ok(VerifyOutcome::Rejected(VerifyRejection::Expired, card_with_dates));
+            }
+        }
+
+        // 检查是否为已绑定的设备
+        let now_str = Utc::now().to_rfc3339();
+        match card.bindings.iter().position(|binding| presented.matches(binding, &policy)) {
+            Some(index) => {
+                touch_binding(&collection, &mut card_with_dates, index, &presented, &now_str).await?;
+            }
+            None => {
+                // 设备数未达上限时绑定当前设备，并发请求中只有未超过上限的能绑定成功
+                let binding = presented.to_binding(&now_str);
+                let filter = doc! {
+                    "_id": card.id,
+                    format!("bindings.{}", policy.max_devices.saturating_sub(1)): { "$exists": false },
+                };
+                let update = doc! { "$push": { "bindings": bson::to_bson(&binding)? } };
+                if collection.update_one(filter, update, None).await?.modified_count == 0 {
+                    return Ok(VerifyOutcome::Rejected(VerifyRejection::UsedByOther, card_with_dates));
                 }
-                
-                return HttpResponse::BadRequest().body("无效的过期日期");
+                info!("Card '{}' bound to a new device", req.card_number);
+                card_with_dates.bindings.push(binding);
             }
-            
-            // 卡密未激活，进行激活
-            let now = Utc::now();
-            
-            // 获取用户标识符
-            let user_identifier = req.user_identifier.clone()
-                .unwrap_or_else(|| client_ip.to_string());  // 简化为直接转换为字符串
-            
-            // 更新卡密信息
-            let filter = doc! {
-                "card_number": &req.card_number,
-                "is_activated": false  // 确保只有未激活的卡密才能被激活
+        }
+
+        // 卡密有效且属于当前用户
+        return Ok(VerifyOutcome::Verified(card_with_dates));
+    }
+
+    // 卡密未激活，进行激活
+    let filter = doc! {
+        "card_number": &req.card_number,
+        "is_activated": false,  // 确保只有未激活的卡密才能被激活
+        "is_revoked": { "$ne": true },
+        "deleted_at_str": null,
+        "shelf_expires_at_str": { "$not": { "$lte": now.to_rfc3339() } },
+        "activate_before_str": { "$not": { "$lte": now.to_rfc3339() } },
+    };
+
+    let updated_card = card.with_activation(now, None, Some(presented.to_binding(&now.to_rfc3339())));
+
+    let update = doc! {
+        "$set": {
+            "is_activated": true,
+            "activated_at_str": updated_card.activated_at_str,
+            "expires_at_str": updated_card.expires_at_str,
+            "bindings": bson::to_bson(&updated_card.bindings)?,
+        }
+    };
+
+    if collection.update_one(filter, update, None).await?.modified_count == 0 {
+        warn!("Card '{}' could not be activated, may have been activated by another request", req.card_number);
+        return Ok(VerifyOutcome::ActivationConflict);
+    }
+
+    // 获取更新后的卡密信息
+    match collection.find_one(doc! { "card_number": &req.card_number }, None).await? {
+        Some(updated_card) => {
+            info!("Card '{}' activated successfully", req.card_number);
+            dispatch_event(&state.db, EVENT_CARD_ACTIVATED, card_event_data(&updated_card)).await;
+            let owner_secret = issue_owner_secret(&state.db, &updated_card, req.email.as_deref()).await;
+            Ok(VerifyOutcome::Activated(updated_card, owner_secret))
+        }
+        None => Ok(VerifyOutcome::NotFound),
+    }
+}
+
+// 记录已绑定设备的最近使用时间；本次上报的指纹字段合并到绑定中，使绑定逐步适应硬件变化，未上报的字段保持不变
+async fn touch_binding(
+    collection: &Collection<Card>,
+    card: &mut Card,
+    index: usize,
+    presented: &PresentedDevice,
+    now: &str,
+) -> Result<(), mongodb::error::Error> {
+    let binding = &mut card.bindings[index];
+    let mut set = doc! { format!("bindings.{}.last_seen_at_str", index): now };
+    if let Some(device) = &presented.device {
+        let merged = match &binding.device {
+            Some(bound) => bound.merged(device),
+            None => device.clone(),
+        };
+        if binding.device.as_ref() != Some(&merged) {
+            set.insert(format!("bindings.{}.device", index), bson::to_bson(&merged)?);
+            binding.device = Some(merged);
+        }
+    }
+    binding.last_seen_at_str = Some(now.to_string());
+    // 以绑定时间确认位置上仍是同一个绑定，期间被解绑时不更新
+    let filter = doc! { "_id": card.id, format!("bindings.{}.bound_at_str", index): &binding.bound_at_str };
+    collection.update_one(filter, doc! { "$set": set }, None).await?;
+    Ok(())
+}
+
+// 验证卡密处理函数（v1 响应格式）
+#[utoipa::path(
+    post,
+    path = "/cards/verify",
+    tag = "cards",
+    summary = "验证卡密",
+    description = "未激活的卡密在首次验证时激活并绑定当前设备（设备指纹 device 或 user_identifier，均未提供时使用客户端IP）；已激活的卡密只允许已绑定的设备通过验证，设备指纹按应用的设备策略模糊匹配，绑定数未达 max_devices 时绑定新设备。归属应用的卡密需要提供应用标识（签名请求的 X-App-Key 或请求体中的 app_key）。带有 X-App-Key 时校验请求签名并对响应签名",
+    request_body = VerifyCardRequest,
+    params(
+        ("X-App-Key" = Option<String>, Header, description = "应用标识，签名请求必填"),
+        ("X-Timestamp" = Option<String>, Header, description = "Unix 时间戳（秒）"),
+        ("X-Nonce" = Option<String>, Header, description = "随机字符串，每个请求不同"),
+        ("X-Signature" = Option<String>, Header, description = "请求签名"),
+    ),
+    responses(
+        (status = 200, description = "已激活的卡密验证通过时返回卡密；首次验证时返回激活结果", body = VerifyCardResponse),
+        (status = 400, description = "卡密已过期、已撤销、已暂停或已被其他用户使用", body = CardRejection),
+        (status = 401, description = "签名无效、时间戳超出范围、nonce 重复、要求签名但请求未签名，或应用不存在"),
+        (status = 404, description = "卡密不存在或不属于该应用"),
+    ),
+)]
+async fn verify_card(
+    state: web::Data<AppState>,
+    req: ClientJson<VerifyCardRequest>,
+    client_ip: ClientIp, // 获取客户端IP（已按受信任代理配置解析）
+) -> HttpResponse {
+    info!("Verifying card. Request params: card_number={}, app={:?}", req.card_number, req.app.as_ref().map(|app| &app.name));
+
+    let ClientJson { app, body: req } = req;
+    match verify(&state, &req, app, client_ip, 1, false).await {
+        Ok(VerifyOutcome::Verified(card)) => HttpResponse::Ok().json(card),
+        Ok(VerifyOutcome::Activated(card, owner_secret)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "卡密激活成功",
+            "card": card,
+            "owner_secret": owner_secret
+        })),
+        Ok(VerifyOutcome::Rejected(reason, card)) => {
+            let (error, message) = match reason {
+                VerifyRejection::Revoked => ("Card revoked", "此卡密已被撤销"),
+                VerifyRejection::Expired => ("Card expired", "此卡密已过期"),
+                VerifyRejection::UsedByOther => ("Card already used", "此卡密已被其他用户使用"),
+                VerifyRejection::Paused => ("Card paused", "此卡密已暂停"),
+                VerifyRejection::Exhausted => ("Card exhausted", "此卡密次数已用完"),
+                VerifyRejection::NotYetValid => ("Card not yet valid", "此卡密尚未到生效时间"),
+                VerifyRejection::ValidityEnded => ("Card validity ended", "此卡密已超过固定到期时间"),
+                VerifyRejection::ActivationClosed => ("Activation deadline passed", "此卡密已超过激活截止时间"),
             };
-            
-            let updated_card = card.with_activation(now, None, Some(user_identifier.clone()));
-            
-            let update = doc! {
-                "$set": {
-                    "is_activated": true,
-                    "activated_at_str": updated_card.activated_at_str,
-                    "expires_at_str": updated_card.expires_at_str,
-                    "used_by_identifier": user_identifier
-                }
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": error,
+                "message": message,
+                "card": card
+            }))
+        }
+        Ok(VerifyOutcome::NotFound) => HttpResponse::NotFound().body("卡密不存在"),
+        Ok(VerifyOutcome::InvalidExpiry) => HttpResponse::BadRequest().body("无效的过期日期"),
+        Ok(VerifyOutcome::ActivationConflict) => HttpResponse::BadRequest().body("卡密无法激活，可能已被其他请求激活"),
+        Ok(VerifyOutcome::AppInvalid) => HttpResponse::Unauthorized().body("应用不存在或已停用"),
+        Ok(VerifyOutcome::IdentifierRequired) => HttpResponse::BadRequest().body("缺少设备标识 user_identifier"),
+        Ok(VerifyOutcome::NotMetered) => HttpResponse::BadRequest().body("此卡密不按次数计费"),
+        Err(e) => {
+            error!("Error verifying card '{}': {}", req.card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 消耗次数处理函数：按验证卡密的规则确认卡密有效（未激活时激活），再原子扣减剩余次数
+#[utoipa::path(
+    post,
+    path = "/cards/consume",
+    tag = "cards",
+    summary = "消耗卡密次数",
+    description = "用于按次数计费的卡密。验证规则与验证卡密相同（未激活的卡密在首次消耗时激活并绑定当前设备），通过后将剩余次数原子减少 amount，剩余次数不足时拒绝且不扣减。同时限制时间的卡密过期后同样被拒绝。失败时 v2 的 code 为 CARD_NOT_FOUND、CARD_EXPIRED、CARD_REVOKED、CARD_PAUSED、CARD_EXHAUSTED、CARD_NOT_METERED、CARD_NOT_YET_VALID、CARD_VALIDITY_ENDED、ACTIVATION_CLOSED、CARD_USED_BY_OTHER、CARD_INVALID、CARD_CONFLICT、IDENTIFIER_REQUIRED 或 APP_INVALID",
+    request_body = ConsumeCardRequest,
+    params(
+        ("X-App-Key" = Option<String>, Header, description = "应用标识，签名请求必填"),
+        ("X-Timestamp" = Option<String>, Header, description = "Unix 时间戳（秒）"),
+        ("X-Nonce" = Option<String>, Header, description = "随机字符串，每个请求不同"),
+        ("X-Signature" = Option<String>, Header, description = "请求签名"),
+    ),
+    responses(
+        (status = 200, description = "消耗成功", body = ConsumeCardResponse),
+        (status = 400, description = "卡密不按次数计费、次数不足、已过期、已撤销、已暂停或已被其他用户使用", body = CardRejection),
+        (status = 401, description = "签名校验失败或应用无效"),
+        (status = 404, description = "卡密不存在或不属于该应用"),
+        (status = 409, description = "并发请求抢先激活了卡密"),
+    ),
+)]
+async fn consume_card(
+    state: web::Data<AppState>,
+    req: ClientJson<ConsumeCardRequest>,
+    client_ip: ClientIp,
+) -> HttpResponse {
+    info!("Consuming card. Request params: card_number={}, amount={}", req.verify.card_number, req.amount);
+
+    let ClientJson { app, body: req } = req;
+    if req.amount < 1 {
+        return HttpResponse::BadRequest().body("amount must be at least 1");
+    }
+    let card_number = &req.verify.card_number;
+    let (card, owner_secret) = match verify(&state, &req.verify, app, client_ip, req.amount, true).await {
+        Ok(VerifyOutcome::Verified(card)) => (card, None),
+        Ok(VerifyOutcome::Activated(card, owner_secret)) => (card, owner_secret),
+        Ok(VerifyOutcome::Rejected(reason, card)) => {
+            let (code, error, message) = match reason {
+                VerifyRejection::Revoked => (CODE_CARD_REVOKED, "Card revoked", "此卡密已被撤销"),
+                VerifyRejection::Expired => (CODE_CARD_EXPIRED, "Card expired", "此卡密已过期"),
+                VerifyRejection::UsedByOther => (CODE_CARD_USED_BY_OTHER, "Card already used", "此卡密已被其他用户使用"),
+                VerifyRejection::Paused => (CODE_CARD_PAUSED, "Card paused", "此卡密已暂停"),
+                VerifyRejection::Exhausted => (CODE_CARD_EXHAUSTED, "Card exhausted", "此卡密剩余次数不足"),
+                VerifyRejection::NotYetValid => (CODE_CARD_NOT_YET_VALID, "Card not yet valid", "此卡密尚未到生效时间"),
+                VerifyRejection::ValidityEnded => (CODE_CARD_VALIDITY_ENDED, "Card validity ended", "此卡密已超过固定到期时间"),
+                VerifyRejection::ActivationClosed => (CODE_ACTIVATION_CLOSED, "Activation deadline passed", "此卡密已超过激活截止时间"),
             };
+            return with_code(
+                HttpResponse::BadRequest().json(serde_json::json!({
+                    "error": error,
+                    "message": message,
+                    "card": card
+                })),
+                code,
+                message.to_string(),
+            );
+        }
+        Ok(VerifyOutcome::NotFound) => return rejection(HttpResponse::NotFound(), CODE_CARD_NOT_FOUND, "卡密不存在"),
+        Ok(VerifyOutcome::InvalidExpiry) => return rejection(HttpResponse::BadRequest(), CODE_CARD_INVALID, "无效的过期日期"),
+        Ok(VerifyOutcome::ActivationConflict) => {
+            return rejection(HttpResponse::Conflict(), CODE_CARD_CONFLICT, "卡密无法激活，可能已被其他请求激活");
+        }
+        Ok(VerifyOutcome::AppInvalid) => return rejection(HttpResponse::Unauthorized(), CODE_APP_INVALID, "应用不存在或已停用"),
+        Ok(VerifyOutcome::IdentifierRequired) => {
+            return rejection(HttpResponse::BadRequest(), CODE_IDENTIFIER_REQUIRED, "缺少设备标识 user_identifier");
+        }
+        Ok(VerifyOutcome::NotMetered) => return rejection(HttpResponse::BadRequest(), CODE_CARD_NOT_METERED, "此卡密不按次数计费"),
+        Err(e) => {
+            error!("Error verifying card '{}': {}", card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
 
-            match collection.update_one(filter, update, None).await {
-                Ok(update_result) => {
-                    if update_result.modified_count == 0 {
-                        warn!("Card '{}' could not be activated, may have been activated by another request", req.card_number);
-                        return HttpResponse::BadRequest().body("卡密无法激活，可能已被其他请求激活");
-                    }
-                    
-                    // 获取更新后的卡密信息
-                    match collection.find_one(doc! { "card_number": &req.card_number }, None).await {
-                        Ok(Some(updated_card)) => {
-                            info!("Card '{}' activated successfully", req.card_number);
-                            return HttpResponse::Ok().json(serde_json::json!({
-                                "message": "卡密激活成功",
-                                "card": updated_card
-                            }));
-                        },
-                        Ok(None) => return HttpResponse::NotFound().body("更新后找不到卡密"),
-                        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-                    }
-                },
-                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
-            }
-        },
-        Ok(None) => HttpResponse::NotFound().body("卡密不存在"),
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    // 以剩余次数足够为条件原子扣减，并发消耗时不会扣成负数
+    let filter = doc! {
+        "_id": card.id,
+        "remaining_uses": { "$gte": req.amount },
+        "is_revoked": { "$ne": true },
+        "is_paused": { "$ne": true },
+    };
+    let update = doc! { "$inc": { "remaining_uses": -req.amount } };
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    let updated = match state.db.collection::<Card>("cards").find_one_and_update(filter, update, options).await {
+        Ok(Some(updated)) => updated,
+        Ok(None) => {
+            metrics().reject_card(REJECT_EXHAUSTED);
+            warn!("Card '{}' has fewer than {} uses left", card_number, req.amount);
+            return rejection(HttpResponse::BadRequest(), CODE_CARD_EXHAUSTED, "此卡密剩余次数不足");
+        }
+        Err(e) => {
+            error!("Error consuming card '{}': {}", card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let remaining_uses = updated.remaining_uses.unwrap_or(0);
+    info!("Card '{}' consumed {} uses, {} left", card_number, req.amount, remaining_uses);
+    if remaining_uses == 0 {
+        // 记录次数用完的时间，统计每日过期数量时使用
+        let update = doc! { "$set": { "exhausted_at_str": Utc::now().to_rfc3339() } };
+        if let Err(e) = state.db.collection::<Card>("cards").update_one(doc! { "_id": updated.id }, update, None).await {
+            error!("Failed to record exhaustion time of card '{}': {}", card_number, e);
+        }
+        dispatch_event(&state.db, EVENT_CARD_EXHAUSTED, card_event_data(&updated)).await;
     }
+    // 返回验证时更新过的绑定信息，次数以扣减后的为准
+    let mut card = card;
+    card.remaining_uses = Some(remaining_uses);
+    HttpResponse::Ok().json(ConsumeCardResponse {
+        card,
+        consumed: req.amount,
+        remaining_uses,
+        owner_secret,
+    })
 }
 
-// 获取所有卡密处理函数
+// 客户端心跳：确认卡密仍然有效且绑定当前使用者，并记录心跳时间；只接受签名请求，不会激活卡密
+#[utoipa::path(
+    post,
+    path = "/cards/heartbeat",
+    tag = "cards",
+    summary = "客户端心跳",
+    description = "只接受签名请求。卡密有效且绑定当前使用者时返回剩余时间；v2 中失败的 code 为 CARD_NOT_FOUND、CARD_NOT_ACTIVATED、CARD_EXPIRED、CARD_REVOKED、CARD_PAUSED、CARD_EXHAUSTED 或 CARD_USED_BY_OTHER",
+    request_body = HeartbeatRequest,
+    params(
+        ("X-App-Key" = String, Header, description = "应用标识"),
+        ("X-Timestamp" = String, Header, description = "Unix 时间戳（秒）"),
+        ("X-Nonce" = String, Header, description = "随机字符串，每个请求不同"),
+        ("X-Signature" = String, Header, description = "请求签名"),
+    ),
+    responses(
+        (status = 200, description = "卡密有效", body = HeartbeatResponse),
+        (status = 400, description = "卡密未激活、已过期、已撤销、已暂停、次数已用完或已被其他用户使用", body = CardRejection),
+        (status = 401, description = "未签名、签名无效、时间戳超出范围或 nonce 重复"),
+        (status = 404, description = "卡密不存在"),
+    ),
+)]
+async fn heartbeat(
+    state: web::Data<AppState>,
+    req: ClientJson<HeartbeatRequest>,
+    client_ip: ClientIp,
+) -> HttpResponse {
+    let app = match require_signed(&req) {
+        Ok(app) => app,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("Card heartbeat. Request params: card_number={}, app={}", req.card_number, app.name);
+
+    let collection = state.db.collection::<Card>("cards");
+    let card_filter = doc! {
+        "card_number": &req.card_number,
+        "deleted_at_str": null,
+        "app_id": app_scope(Some(app)),
+    };
+    let mut card = match collection.find_one(card_filter, None).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return with_code(HttpResponse::NotFound().body("卡密不存在"), CODE_CARD_NOT_FOUND, None),
+        Err(e) => {
+            error!("Error finding card '{}': {}", req.card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+
+    let now = Utc::now();
+    let presented = PresentedDevice::new(req.user_identifier.clone(), req.device.clone(), client_ip);
+    let binding_index = card.bindings.iter().position(|binding| presented.matches(binding, &app.device_policy));
+    let expires_at = card.expires_at_str.as_ref().and_then(|s| s.parse::<DateTime<Utc>>().ok());
+    let rejection = if card.is_revoked {
+        Some((CODE_CARD_REVOKED, "Card revoked", "此卡密已被撤销"))
+    } else if card.is_paused {
+        Some((CODE_CARD_PAUSED, "Card paused", "此卡密已暂停"))
+    } else if !card.is_activated {
+        Some((CODE_CARD_NOT_ACTIVATED, "Card not activated", "此卡密尚未激活"))
+    } else if card.is_time_limited() && expires_at.is_none_or(|expires_at| expires_at <= now) {
+        Some((CODE_CARD_EXPIRED, "Card expired", "此卡密已过期"))
+    } else if card.is_exhausted() {
+        Some((CODE_CARD_EXHAUSTED, "Card exhausted", "此卡密次数已用完"))
+    } else if binding_index.is_none() {
+        Some((CODE_CARD_USED_BY_OTHER, "Card already used", "此卡密已被其他用户使用"))
+    } else {
+        None
+    };
+    if let Some((code, error, message)) = rejection {
+        warn!("Heartbeat for card '{}' rejected: {}", req.card_number, error);
+        return with_code(
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": error,
+                "message": message,
+                "card": card
+            })),
+            code,
+            message.to_string(),
+        );
+    }
+
+    let update = doc! { "$set": { "last_heartbeat_at_str": now.to_rfc3339() } };
+    if let Err(e) = collection.update_one(doc! { "_id": card.id }, update, None).await {
+        error!("Failed to record heartbeat for card '{}': {}", req.card_number, e);
+    }
+    if let Some(index) = binding_index {
+        if let Err(e) = touch_binding(&collection, &mut card, index, &presented, &now.to_rfc3339()).await {
+            error!("Failed to update device binding for card '{}': {}", req.card_number, e);
+        }
+    }
+
+    let remaining_seconds = expires_at.map(|expires_at| (expires_at - now).num_seconds());
+    HttpResponse::Ok().json(HeartbeatResponse {
+        card_number: card.card_number,
+        expires_at_str: card.expires_at_str,
+        remaining_seconds,
+        remaining_uses: card.remaining_uses,
+        server_time: now.to_rfc3339(),
+    })
+}
+
+// 获取所有卡密处理函数，支持按状态、创建者、批次、有效期和卡号筛选
+#[utoipa::path(
+    get,
+    path = "/cards",
+    tag = "cards",
+    summary = "获取卡密列表",
+    description = "管理员可查看所有卡密，普通用户只能查看自己创建的卡密",
+    params(CardQuery),
+    responses(
+        (status = 200, description = "卡密列表", body = Vec<Card>),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn get_all_cards(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
+    query: web::Query<CardQuery>,
 ) -> HttpResponse {
-    info!("User '{}' is fetching cards", user.username);
+    info!("User '{}' is fetching cards. Request params: {:?}", user.username, query);
     
     let collection = state.db.collection::<Card>("cards");
     
-    // 根据用户角色决定查询条件
-    let filter = match user.role {
-        UserRole::Admin => None, // 管理员可以查看所有卡密
-        _ => Some(doc! { "created_by": &user.user_id }), // 普通用户只能查看自己的卡密
-    };
+    // 管理员可以查看所有卡密，普通用户只能查看自己的卡密；已删除的卡密不在列表中显示
+    let filter = scoped_filter(&user, &query);
     
     // 查询卡密
     match collection.find(filter, None).await {
@@ -287,7 +944,21 @@ async fn get_all_cards(
     }
 }
 
-// 删除卡密处理函数
+// 删除卡密处理函数（软删除，卡密移入回收站）
+#[utoipa::path(
+    delete,
+    path = "/cards/{id}",
+    tag = "cards",
+    summary = "删除卡密",
+    description = "卡密移入回收站，可在保留期内恢复",
+    params(("id" = String, Path, description = "卡密ID")),
+    responses(
+        (status = 200, description = "删除成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 404, description = "卡密不存在或无权删除"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn delete_card(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -308,60 +979,190 @@ async fn delete_card(
     
     // 根据用户角色决定删除条件
     let filter = match user.role {
-        UserRole::Admin => doc! { "_id": object_id }, // 管理员可以删除任何卡密
-        _ => doc! { "_id": object_id, "created_by": &user.user_id }, // 普通用户只能删除自己的卡密
+        UserRole::Admin => doc! { "_id": object_id, "deleted_at_str": null }, // 管理员可以删除任何卡密
+        _ => doc! { "_id": object_id, "created_by": &user.user_id, "deleted_at_str": null }, // 普通用户只能删除自己的卡密
+    };
+    let update = doc! {
+        "$set": {
+            "deleted_at_str": Utc::now().to_rfc3339(),
+            "deleted_by": &user.username,
+        }
+    };
+
+    let options = FindOneAndUpdateOptions::builder().return_document(ReturnDocument::After).build();
+    match collection.find_one_and_update(filter, update, options).await {
+        Ok(None) => {
+            warn!("Card with ID '{}' not found for deletion or user lacks permission", id);
+            HttpResponse::NotFound().body("Card not found or you don't have permission to delete it")
+        }
+        Ok(Some(card)) => {
+            info!("Card with ID '{}' moved to trash by user '{}'", id, user.username);
+            state.audit.record(&user, "card.delete", Some(id), None);
+            // 删除后卡密不能再使用，与撤销相同发送 card.revoked 事件
+            dispatch_event(&state.db, EVENT_CARD_REVOKED, card_event_data(&card)).await;
+            HttpResponse::Ok().body("Card deleted successfully")
+        }
+        Err(e) => {
+            error!("Error deleting card '{}': {}", id, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 获取回收站中的卡密
+#[utoipa::path(
+    get,
+    path = "/cards/trash",
+    tag = "cards",
+    summary = "获取回收站中的卡密",
+    responses(
+        (status = 200, description = "已删除的卡密，按删除时间倒序", body = Vec<Card>),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_trash(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    info!("User '{}' is fetching trashed cards", user.username);
+
+    let collection = state.db.collection::<Card>("cards");
+
+    let filter = match user.role {
+        UserRole::Admin => doc! { "deleted_at_str": { "$ne": null } },
+        _ => doc! { "created_by": &user.user_id, "deleted_at_str": { "$ne": null } },
     };
+    let options = FindOptions::builder().sort(doc! { "deleted_at_str": -1 }).build();
 
-    match collection.delete_one(filter, None).await {
+    match collection.find(filter, options).await {
+        Ok(cursor) => {
+            match futures::stream::TryStreamExt::try_collect::<Vec<Card>>(cursor).await {
+                Ok(cards) => HttpResponse::Ok().json(cards),
+                Err(e) => {
+                    error!("Error collecting trashed cards: {}", e);
+                    HttpResponse::InternalServerError().body(e.to_string())
+                },
+            }
+        }
+        Err(e) => {
+            error!("Error finding trashed cards: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        },
+    }
+}
+
+// 从回收站恢复卡密
+#[utoipa::path(
+    post,
+    path = "/cards/{id}/restore",
+    tag = "cards",
+    summary = "从回收站恢复卡密",
+    params(("id" = String, Path, description = "卡密ID")),
+    responses(
+        (status = 200, description = "恢复成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 404, description = "回收站中不存在该卡密或无权恢复"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn restore_card(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    info!("User '{}' attempting to restore card. Request params: id={}", user.username, id);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(e) => {
+            warn!("Invalid card ID format: {}", e);
+            return HttpResponse::BadRequest().body(format!("Invalid ID format: {}", e));
+        }
+    };
+
+    let collection = state.db.collection::<Card>("cards");
+
+    let filter = match user.role {
+        UserRole::Admin => doc! { "_id": object_id, "deleted_at_str": { "$ne": null } },
+        _ => doc! { "_id": object_id, "created_by": &user.user_id, "deleted_at_str": { "$ne": null } },
+    };
+    let update = doc! { "$unset": { "deleted_at_str": "", "deleted_by": "" } };
+
+    match collection.update_one(filter, update, None).await {
         Ok(result) => {
-            if result.deleted_count == 0 {
-                warn!("Card with ID '{}' not found for deletion or user lacks permission", id);
-                HttpResponse::NotFound().body("Card not found or you don't have permission to delete it")
+            if result.matched_count == 0 {
+                warn!("Card with ID '{}' not found in trash or user lacks permission", id);
+                HttpResponse::NotFound().body("Card not found in trash or you don't have permission to restore it")
             } else {
-                info!("Card with ID '{}' deleted successfully by user '{}'", id, user.username);
-                HttpResponse::Ok().body("Card deleted successfully")
+                info!("Card with ID '{}' restored by user '{}'", id, user.username);
+                state.audit.record(&user, "card.restore", Some(id), None);
+                HttpResponse::Ok().body("Card restored successfully")
             }
         }
         Err(e) => {
-            error!("Error deleting card '{}': {}", id, e);
+            error!("Error restoring card '{}': {}", id, e);
             HttpResponse::InternalServerError().body(e.to_string())
         }
     }
 }
 
+// 将卡密转换为 CSV 内容，网页导出与命令行导出共用
+pub(crate) fn cards_to_csv(cards: &[Card], now: DateTime<Utc>) -> String {
+    let mut csv_content = String::from("卡号,有效期(天),有效期,剩余次数,状态,激活时间,过期时间,创建时间\n");
+    
+    // 遍历卡密生成 CSV 行，状态与卡密列表的筛选状态一致
+    // "有效期(天)" 保持 v1 的整数天数（不足一天按0计），"有效期" 为带单位的精确时长
+    for card in cards {
+        csv_content.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            card.card_number,
+            card.duration_days(),
+            format_duration(card.duration_seconds),
+            card.remaining_uses.map_or("-".to_string(), |uses| uses.to_string()),
+            card.status(now).label(),
+            card.activated_at_str.as_deref().unwrap_or("-"),
+            card.expires_at_str.as_deref().unwrap_or("-"),
+            card.created_at_str
+        ));
+    }
+    csv_content
+}
+
 // 导出卡密处理函数
+#[utoipa::path(
+    get,
+    path = "/cards/export",
+    tag = "cards",
+    summary = "导出卡密",
+    description = "导出符合筛选条件的卡密，筛选条件与获取卡密列表相同（如按 app_id 导出某个应用的卡密）；普通用户只能导出自己创建的卡密",
+    params(CardQuery),
+    responses(
+        (status = 200, description = "CSV 文件", body = String, content_type = "text/csv"),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn export_cards(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
+    query: web::Query<CardQuery>,
 ) -> HttpResponse {
-    info!("User '{}' is exporting all cards", user.username);
+    info!("User '{}' is exporting cards. Request params: {:?}", user.username, query);
     
     let collection = state.db.collection::<Card>("cards");
     
-    // 查询所有卡密
-    match collection.find(None, None).await {
+    // 查询符合条件的未删除卡密，普通用户只能导出自己的卡密
+    match collection.find(scoped_filter(&user, &query), None).await {
         Ok(cursor) => {
             match futures::stream::TryStreamExt::try_collect::<Vec<Card>>(cursor).await {
                 Ok(cards) => {
-                    // 构建 CSV 内容
-                    let mut csv_content = String::from("卡号,有效期(天),状态,激活时间,过期时间,创建时间\n");
-                    
-                    // 遍历卡密生成 CSV 行
-                    for card in cards {
-                        let status = if card.is_activated { "已激活" } else { "未激活" };
-                        let activated_at = card.activated_at_str.unwrap_or_else(|| "-".to_string());
-                        let expires_at = card.expires_at_str.unwrap_or_else(|| "-".to_string());
-                        
-                        csv_content.push_str(&format!(
-                            "{},{},{},{},{},{}\n",
-                            card.card_number,
-                            card.duration_days,
-                            status,
-                            activated_at,
-                            expires_at,
-                            card.created_at_str
-                        ));
-                    }
+                    let csv_content = cards_to_csv(&cards, Utc::now());
+                    state.audit.record(&user, "card.export", None, Some(serde_json::json!({
+                        "count": cards.len(),
+                        "query": format!("{:?}", query),
+                    })));
                     
                     // 设置响应头，使浏览器下载文件
                     HttpResponse::Ok()
@@ -374,4 +1175,34 @@ async fn export_cards(
         }
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_keeps_whole_day_column() {
+        let now = Utc::now();
+        let card = Card::new("HOURLY00000000000".to_string(), 36 * 3_600);
+
+        let csv = cards_to_csv(&[card], now);
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("卡号,有效期(天),有效期,"));
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(row[1], "1");
+        assert_eq!(row[2], format_duration(36 * 3_600));
+    }
+
+    #[test]
+    fn csv_status_follows_card_status() {
+        let now = Utc::now();
+        let expired = Card::new("EXPIRED0000000000".to_string(), 86_400).with_activation(now - Duration::days(2), None, None);
+        let active = Card::new("ACTIVE00000000000".to_string(), 86_400).with_activation(now, None, None);
+
+        let csv = cards_to_csv(&[expired, active], now);
+        let rows: Vec<Vec<&str>> = csv.lines().skip(1).map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows[0][0], "EXPIRED0000000000");
+        assert_eq!(rows[0][4], "已过期");
+        assert_eq!(rows[1][4], "已激活");
+    }
+}
@@ -0,0 +1,647 @@
+use actix_web::{web, HttpResponse, Scope};
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReplaceOptions, ReturnDocument};
+use std::collections::HashMap;
+
+use crate::{
+    api_key::hash_key,
+    auth::{generate_customer_token, hash_password, verify_password, AuthenticatedCustomer},
+    client_ip::ClientIp,
+    errors::is_duplicate_key,
+    handlers::application::find_application,
+    handlers::pause,
+    handlers::rebind::{check_rebind_quota, consume_code, numeric_code, rejection, CODE_RESEND_SECS, CODE_TTL_SECS},
+    handlers::v2::CODE_REBIND_TIME_INSUFFICIENT,
+    models::application::Application,
+    models::customer::{
+        Customer, CustomerCodeLoginRequest, CustomerCodeRequest, CustomerInfo, CustomerLicense, CustomerLoginCode,
+        CustomerLoginResponse, CustomerPasswordRequest, RedeemCardRequest,
+    },
+    models::rebind::{CardOwner, RebindMethod, RebindRecord},
+    models::webhook::{EVENT_CARD_REBOUND, EVENT_CUSTOMER_LOGIN_CODE},
+    models::Card,
+    webhook::{card_event_data, dispatch_event},
+    AppState,
+};
+
+// 客户密码的最小长度
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+// 配置终端客户相关的路由，与员工账号的接口分开
+pub fn config() -> Scope {
+    web::scope("/customer")
+        .route("/register", web::post().to(register))                 // 邮箱密码注册
+        .route("/login", web::post().to(login))                       // 邮箱密码登录
+        .route("/login/code", web::post().to(request_login_code))     // 申请邮箱登录验证码
+        .route("/login/code/verify", web::post().to(login_with_code)) // 邮箱验证码登录
+        .route("/me", web::get().to(get_me))                          // 获取当前客户
+        .route("/cards", web::get().to(get_licenses))                 // 获取账号中的授权
+        .route("/cards/redeem", web::post().to(redeem_card))          // 兑换卡密到账号
+        .route("/cards/{card_number}/bindings/{index}", web::delete().to(remove_binding))  // 解绑设备
+        .route("/cards/{card_number}/pause", web::post().to(pause_license))    // 暂停授权
+        .route("/cards/{card_number}/resume", web::post().to(resume_license))  // 恢复授权
+}
+
+// 规范化邮箱（去除空白并转为小写），格式无效时返回 None
+fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') => Some(email),
+        _ => None,
+    }
+}
+
+// 记录登录时间并签发令牌
+async fn login_response(state: &AppState, customer: Customer) -> HttpResponse {
+    let token = match generate_customer_token(&customer, &state.config.auth) {
+        Ok(token) => token,
+        Err(e) => {
+            error!("Error generating customer token: {}", e);
+            return HttpResponse::InternalServerError().body("Error generating token");
+        }
+    };
+    let update = doc! { "$set": { "last_login_at_str": Utc::now().to_rfc3339() } };
+    if let Err(e) = state.db.collection::<Customer>("customers").update_one(doc! { "_id": customer.id }, update, None).await {
+        error!("Failed to record customer login: {}", e);
+    }
+    HttpResponse::Ok().json(CustomerLoginResponse { token, customer: CustomerInfo::from(customer) })
+}
+
+// 将卡密转换为客户看到的授权信息
+fn to_license(card: Card, app: Option<&Application>, now: DateTime<Utc>) -> CustomerLicense {
+    // 暂停期间剩余时间保持不变
+    let remaining_seconds = match card.paused_remaining_seconds.filter(|_| card.is_paused) {
+        Some(remaining_seconds) => Some(remaining_seconds),
+        None => card.expires_at_str.as_ref()
+            .and_then(|s| s.parse::<DateTime<Utc>>().ok())
+            .map(|expires_at| (expires_at - now).num_seconds().max(0)),
+    };
+    CustomerLicense {
+        status: card.status(now),
+        card_number: card.card_number,
+        app_id: card.app_id,
+        app_name: app.map(|app| app.name.clone()),
+        duration_seconds: card.duration_seconds,
+        activated_at_str: card.activated_at_str,
+        expires_at_str: card.expires_at_str,
+        remaining_seconds,
+        usage_limit: card.usage_limit,
+        remaining_uses: card.remaining_uses,
+        max_devices: app.map(|app| app.device_policy.max_devices).unwrap_or(1),
+        bindings: card.bindings,
+        redeemed_at_str: card.redeemed_at_str,
+    }
+}
+
+// 查找客户账号中的卡密
+async fn find_customer_card(state: &AppState, customer: &AuthenticatedCustomer, card_number: &str) -> Result<Card, HttpResponse> {
+    let filter = doc! { "card_number": card_number, "customer_id": &customer.customer_id, "deleted_at_str": null };
+    match state.db.collection::<Card>("cards").find_one(filter, None).await {
+        Ok(Some(card)) => Ok(card),
+        Ok(None) => Err(HttpResponse::NotFound().body("卡密不存在")),
+        Err(e) => Err(HttpResponse::InternalServerError().body(e.to_string())),
+    }
+}
+
+// 查找卡密所属的应用，未归属应用时为 None
+async fn card_application(state: &AppState, card: &Card) -> Result<Option<Application>, mongodb::error::Error> {
+    match &card.app_id {
+        Some(app_id) => find_application(&state.db, app_id).await,
+        None => Ok(None),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/register",
+    tag = "customers",
+    summary = "客户注册",
+    description = "使用邮箱和密码注册终端客户账号，注册成功后直接返回令牌",
+    request_body = CustomerPasswordRequest,
+    responses(
+        (status = 200, description = "注册成功", body = CustomerLoginResponse),
+        (status = 400, description = "邮箱格式无效或密码过短"),
+        (status = 409, description = "邮箱已注册"),
+    ),
+)]
+async fn register(
+    state: web::Data<AppState>,
+    req: web::Json<CustomerPasswordRequest>,
+) -> HttpResponse {
+    let Some(email) = normalize_email(&req.email) else {
+        return HttpResponse::BadRequest().body("Invalid email");
+    };
+    info!("Customer registration attempt: {}", email);
+    if req.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return HttpResponse::BadRequest().body(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+
+    let collection = state.db.collection::<Customer>("customers");
+    match collection.find_one(doc! { "email": &email }, None).await {
+        Ok(Some(_)) => return HttpResponse::Conflict().body("Email already registered"),
+        Ok(None) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let password_hash = match hash_password(&req.password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            error!("Failed to hash password: {}", e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    };
+    let mut customer = Customer::new(email, Some(password_hash));
+    match collection.insert_one(&customer, None).await {
+        Ok(result) => {
+            customer.id = result.inserted_id.as_object_id();
+            info!("Customer '{}' registered", customer.email);
+            login_response(&state, customer).await
+        }
+        // 并发注册同一邮箱时由唯一索引拒绝
+        Err(e) if is_duplicate_key(&e) => HttpResponse::Conflict().body("Email already registered"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/login",
+    tag = "customers",
+    summary = "客户密码登录",
+    request_body = CustomerPasswordRequest,
+    responses(
+        (status = 200, description = "登录成功", body = CustomerLoginResponse),
+        (status = 401, description = "邮箱或密码错误"),
+    ),
+)]
+async fn login(
+    state: web::Data<AppState>,
+    req: web::Json<CustomerPasswordRequest>,
+) -> HttpResponse {
+    let email = normalize_email(&req.email).unwrap_or_default();
+    info!("Customer login attempt: {}", email);
+
+    let customer = match state.db.collection::<Customer>("customers").find_one(doc! { "email": &email }, None).await {
+        Ok(Some(customer)) => customer,
+        Ok(None) => return HttpResponse::Unauthorized().body("Invalid email or password"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    // 只使用验证码登录的账号没有密码
+    let Some(password_hash) = &customer.password_hash else {
+        return HttpResponse::Unauthorized().body("Invalid email or password");
+    };
+    match verify_password(&req.password, password_hash) {
+        Ok(true) => login_response(&state, customer).await,
+        Ok(false) => {
+            warn!("Invalid password for customer: {}", email);
+            HttpResponse::Unauthorized().body("Invalid email or password")
+        }
+        Err(e) => {
+            error!("Error verifying password: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/login/code",
+    tag = "customers",
+    summary = "申请邮箱登录验证码",
+    description = "生成6位验证码（10分钟内有效，最多尝试5次），通过 customer.login_code Webhook 事件投递，由接入方发送邮件；60秒内不会重复发送",
+    request_body = CustomerCodeRequest,
+    responses(
+        (status = 202, description = "已受理"),
+        (status = 400, description = "邮箱格式无效"),
+    ),
+)]
+async fn request_login_code(
+    state: web::Data<AppState>,
+    req: web::Json<CustomerCodeRequest>,
+) -> HttpResponse {
+    let Some(email) = normalize_email(&req.email) else {
+        return HttpResponse::BadRequest().body("Invalid email");
+    };
+    info!("Customer login code requested: {}", email);
+    let accepted = || HttpResponse::Accepted().body("验证码已发送");
+
+    // 距离上次发送不足间隔时不重复发送
+    let codes = state.db.collection::<CustomerLoginCode>("customer_login_codes");
+    let now = Utc::now();
+    match codes.find_one(doc! { "_id": &email }, None).await {
+        Ok(Some(existing)) => {
+            let recent = existing.created_at_str.parse::<DateTime<Utc>>()
+                .is_ok_and(|created_at| now - created_at < Duration::seconds(CODE_RESEND_SECS));
+            if recent {
+                return accepted();
+            }
+        }
+        Ok(None) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let code = numeric_code();
+    let expires_at = now + Duration::seconds(CODE_TTL_SECS);
+    let login_code = CustomerLoginCode {
+        email: email.clone(),
+        code_hash: hash_key(&code),
+        attempts: 0,
+        created_at_str: now.to_rfc3339(),
+        expires_at: bson::DateTime::from_chrono(expires_at),
+    };
+    let options = ReplaceOptions::builder().upsert(true).build();
+    if let Err(e) = codes.replace_one(doc! { "_id": &email }, &login_code, options).await {
+        error!("Failed to save login code for customer '{}': {}", email, e);
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    dispatch_event(&state.db, EVENT_CUSTOMER_LOGIN_CODE, serde_json::json!({
+        "email": email,
+        "code": code,
+        "expires_at": expires_at.to_rfc3339(),
+    })).await;
+    accepted()
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/login/code/verify",
+    tag = "customers",
+    summary = "邮箱验证码登录",
+    description = "验证码正确时登录，邮箱未注册时自动创建没有密码的账号",
+    request_body = CustomerCodeLoginRequest,
+    responses(
+        (status = 200, description = "登录成功", body = CustomerLoginResponse),
+        (status = 401, description = "验证码错误或已过期"),
+    ),
+)]
+async fn login_with_code(
+    state: web::Data<AppState>,
+    req: web::Json<CustomerCodeLoginRequest>,
+) -> HttpResponse {
+    let email = normalize_email(&req.email).unwrap_or_default();
+    info!("Customer code login attempt: {}", email);
+
+    match consume_code(&state.db, "customer_login_codes", &email, &req.code).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Unauthorized().body("Invalid or expired code"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    // 查找账号，不存在时创建
+    let now = Utc::now().to_rfc3339();
+    let update = doc! {
+        "$setOnInsert": {
+            "email": &email,
+            "password_hash": null,
+            "created_at_str": &now,
+            "updated_at_str": &now,
+            "last_login_at_str": null,
+        }
+    };
+    let options = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::After).build();
+    match state.db.collection::<Customer>("customers").find_one_and_update(doc! { "email": &email }, update, options).await {
+        Ok(Some(customer)) => login_response(&state, customer).await,
+        Ok(None) => HttpResponse::InternalServerError().body("Customer not found after upsert"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/customer/me",
+    tag = "customers",
+    summary = "获取当前客户",
+    responses(
+        (status = 200, description = "当前登录的客户", body = CustomerInfo),
+        (status = 401, description = "未认证"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn get_me(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+) -> HttpResponse {
+    let Ok(object_id) = ObjectId::parse_str(&customer.customer_id) else {
+        return HttpResponse::Unauthorized().finish();
+    };
+    match state.db.collection::<Customer>("customers").find_one(doc! { "_id": object_id }, None).await {
+        Ok(Some(customer)) => HttpResponse::Ok().json(CustomerInfo::from(customer)),
+        Ok(None) => HttpResponse::NotFound().body("Customer not found"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/customer/cards",
+    tag = "customers",
+    summary = "获取账号中的授权",
+    description = "返回兑换到账号中的全部卡密，包括状态、剩余时间和已绑定的设备",
+    responses(
+        (status = 200, description = "授权列表，按兑换时间倒序", body = Vec<CustomerLicense>),
+        (status = 401, description = "未认证"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn get_licenses(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+) -> HttpResponse {
+    let filter = doc! { "customer_id": &customer.customer_id, "deleted_at_str": null };
+    let options = FindOptions::builder().sort(doc! { "redeemed_at_str": -1 }).build();
+    let cards = match state.db.collection::<Card>("cards").find(filter, options).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<Card>>().await {
+            Ok(cards) => cards,
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    // 一次查出授权涉及的应用，用于显示应用名称和设备数上限
+    let app_ids: Vec<ObjectId> = cards.iter()
+        .filter_map(|card| card.app_id.as_deref().and_then(|id| ObjectId::parse_str(id).ok()))
+        .collect();
+    let apps: HashMap<String, Application> = match state.db.collection::<Application>("applications")
+        .find(doc! { "_id": { "$in": app_ids } }, None)
+        .await
+    {
+        Ok(cursor) => match cursor.try_collect::<Vec<Application>>().await {
+            Ok(apps) => apps.into_iter().filter_map(|app| Some((app.id?.to_hex(), app))).collect(),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let now = Utc::now();
+    let licenses: Vec<CustomerLicense> = cards.into_iter()
+        .map(|card| {
+            let app = card.app_id.as_ref().and_then(|id| apps.get(id));
+            to_license(card, app, now)
+        })
+        .collect();
+    HttpResponse::Ok().json(licenses)
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/cards/redeem",
+    tag = "customers",
+    summary = "兑换卡密到账号",
+    description = "未激活的卡密凭卡号即可兑换；已激活的卡密需要提供激活时返回的所有者密钥。每张卡密只能兑换到一个账号",
+    request_body = RedeemCardRequest,
+    responses(
+        (status = 200, description = "兑换成功", body = CustomerLicense),
+        (status = 400, description = "卡密已撤销"),
+        (status = 401, description = "未认证"),
+        (status = 403, description = "已激活的卡密未提供所有者密钥或密钥错误"),
+        (status = 404, description = "卡密不存在"),
+        (status = 409, description = "卡密已兑换到其他账号"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn redeem_card(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+    req: web::Json<RedeemCardRequest>,
+) -> HttpResponse {
+    info!("Customer '{}' is redeeming card '{}'", customer.email, req.card_number);
+
+    let collection = state.db.collection::<Card>("cards");
+    let card = match collection.find_one(doc! { "card_number": &req.card_number, "deleted_at_str": null }, None).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return HttpResponse::NotFound().body("卡密不存在"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match &card.customer_id {
+        Some(owner) if owner == &customer.customer_id => {}
+        Some(_) => return HttpResponse::Conflict().body("卡密已兑换到其他账号"),
+        None => {
+            if card.is_revoked {
+                return HttpResponse::BadRequest().body("此卡密已被撤销");
+            }
+            // 已激活的卡密只有所有者能兑换
+            if card.is_activated {
+                let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+                let Some(secret) = &req.owner_secret else {
+                    return HttpResponse::Forbidden().body("已激活的卡密需要提供所有者密钥");
+                };
+                let filter = doc! { "card_id": card_id, "secret_hash": hash_key(secret) };
+                match state.db.collection::<CardOwner>("card_owners").find_one(filter, None).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => return HttpResponse::Forbidden().body("所有者密钥错误"),
+                    Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+                }
+            }
+            let now = Utc::now().to_rfc3339();
+            let filter = doc! { "_id": card.id, "customer_id": null };
+            let update = doc! { "$set": { "customer_id": &customer.customer_id, "redeemed_at_str": &now } };
+            match collection.update_one(filter, update, None).await {
+                Ok(result) if result.modified_count == 0 => return HttpResponse::Conflict().body("卡密已兑换到其他账号"),
+                Ok(_) => info!("Card '{}' redeemed by customer '{}'", req.card_number, customer.email),
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+    }
+
+    let mut card = card;
+    card.customer_id = Some(customer.customer_id.clone());
+    card.redeemed_at_str.get_or_insert_with(|| Utc::now().to_rfc3339());
+    match card_application(&state, &card).await {
+        Ok(app) => HttpResponse::Ok().json(to_license(card, app.as_ref(), Utc::now())),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/customer/cards/{card_number}/bindings/{index}",
+    tag = "customers",
+    summary = "解绑设备",
+    description = "从账号中的卡密上移除一个已绑定的设备，之后可以在新设备上验证卡密。解绑计入应用设备策略的换绑次数，并按 rebind_penalty_hours 扣除有效时间",
+    params(
+        ("card_number" = String, Path, description = "卡号"),
+        ("index" = usize, Path, description = "绑定在 bindings 中的位置，从0开始"),
+    ),
+    responses(
+        (status = 200, description = "解绑后的授权信息", body = CustomerLicense),
+        (status = 401, description = "未认证"),
+        (status = 400, description = "卡密已暂停，或剩余有效时间不足以扣除（REBIND_TIME_INSUFFICIENT）"),
+        (status = 403, description = "应用不允许自助换绑"),
+        (status = 404, description = "卡密不在账号中或绑定不存在"),
+        (status = 409, description = "绑定被并发修改，可重试"),
+        (status = 429, description = "当前周期内的换绑次数已用完"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn remove_binding(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+    path: web::Path<(String, usize)>,
+    client_ip: ClientIp,
+) -> HttpResponse {
+    let (card_number, index) = path.into_inner();
+    info!("Customer '{}' is removing binding {} of card '{}'", customer.email, index, card_number);
+
+    let collection = state.db.collection::<Card>("cards");
+    let mut card = match find_customer_card(&state, &customer, &card_number).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    // 暂停期间不扣除时间，恢复后才能解绑
+    if card.is_paused {
+        return HttpResponse::BadRequest().body("卡密已暂停，请恢复后再解绑");
+    }
+    if index >= card.bindings.len() {
+        return HttpResponse::NotFound().body("Binding not found");
+    }
+    let app = match card_application(&state, &card).await {
+        Ok(app) => app,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let policy = app.as_ref().map(|app| app.device_policy.clone()).unwrap_or_default();
+    match check_rebind_quota(&state.db, &card, &policy).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(response)) => return response,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    // 剩余有效时间不足以扣除时拒绝，不会因解绑直接过期
+    let expires_at = card.expires_at_str.as_ref()
+        .and_then(|s| s.parse::<DateTime<Utc>>().ok())
+        .map(|expires_at| expires_at - Duration::hours(policy.rebind_penalty_hours as i64));
+    if policy.rebind_penalty_hours > 0 && expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return rejection(
+            HttpResponse::BadRequest(),
+            CODE_REBIND_TIME_INSUFFICIENT,
+            &format!("剩余有效时间不足 {} 小时，无法解绑", policy.rebind_penalty_hours),
+        );
+    }
+    let expires_at = expires_at.map(|expires_at| expires_at.to_rfc3339());
+
+    // 移除绑定并扣除有效时间；以原绑定为条件，绑定被并发修改时拒绝
+    let previous_bindings = card.bindings.clone();
+    card.bindings.remove(index);
+    let (filter, update) = match (bson::to_bson(&previous_bindings), bson::to_bson(&card.bindings)) {
+        (Ok(previous), Ok(bindings)) => (
+            doc! { "_id": card.id, "bindings": previous },
+            doc! { "$set": { "bindings": bindings, "expires_at_str": &expires_at } },
+        ),
+        (Err(e), _) | (_, Err(e)) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match collection.update_one(filter, update, None).await {
+        Ok(result) if result.modified_count == 0 => {
+            return HttpResponse::Conflict().body("卡密绑定已被其他请求修改，请重试");
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error removing binding of card '{}': {}", card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    }
+    card.expires_at_str = expires_at;
+
+    let record = RebindRecord {
+        id: None,
+        card_id: card.id.map(|id| id.to_hex()).unwrap_or_default(),
+        card_number: card.card_number.clone(),
+        method: RebindMethod::Customer,
+        previous_bindings,
+        binding: None,
+        penalty_hours: policy.rebind_penalty_hours,
+        expires_at_str: card.expires_at_str.clone(),
+        client_ip: client_ip.to_string(),
+        created_at_str: Utc::now().to_rfc3339(),
+    };
+    if let Err(e) = state.db.collection::<RebindRecord>("card_rebinds").insert_one(&record, None).await {
+        error!("Failed to record binding removal for card '{}': {}", card_number, e);
+    }
+    dispatch_event(&state.db, EVENT_CARD_REBOUND, card_event_data(&card)).await;
+
+    HttpResponse::Ok().json(to_license(card, app.as_ref(), Utc::now()))
+}
+
+// 暂停或恢复账号中的授权，需要卡密所属应用开启 allow_owner_pause
+async fn toggle_pause(state: &AppState, customer: &AuthenticatedCustomer, card_number: &str, paused: bool) -> HttpResponse {
+    let card = match find_customer_card(state, customer, card_number).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    let app = match card_application(state, &card).await {
+        Ok(app) => app,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if !app.as_ref().is_some_and(|app| app.allow_owner_pause) {
+        return HttpResponse::Forbidden().body("该卡密不支持自行暂停");
+    }
+    let result = if paused {
+        pause::pause(&state.db, &card, &customer.email).await
+    } else {
+        pause::resume(&state.db, &card, &customer.email).await
+    };
+    match result {
+        Ok(Ok(card)) => HttpResponse::Ok().json(to_license(card, app.as_ref(), Utc::now())),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            error!("Error toggling pause of card '{}': {}", card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/cards/{card_number}/pause",
+    tag = "customers",
+    summary = "暂停授权",
+    description = "冻结账号中的卡密，暂停期间不计时，客户端验证时返回 CARD_PAUSED；需要卡密所属应用开启 allow_owner_pause",
+    params(("card_number" = String, Path, description = "卡号")),
+    responses(
+        (status = 200, description = "暂停后的授权信息", body = CustomerLicense),
+        (status = 400, description = "卡密未激活、已过期、已撤销或已暂停"),
+        (status = 401, description = "未认证"),
+        (status = 403, description = "卡密所属应用不允许自行暂停"),
+        (status = 404, description = "卡密不在账号中"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn pause_license(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let card_number = path.into_inner();
+    info!("Customer '{}' is pausing card '{}'", customer.email, card_number);
+    toggle_pause(&state, &customer, &card_number, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/customer/cards/{card_number}/resume",
+    tag = "customers",
+    summary = "恢复授权",
+    description = "恢复已暂停的卡密，过期时间重新计算为当前时间加上暂停时的剩余时间",
+    params(("card_number" = String, Path, description = "卡号")),
+    responses(
+        (status = 200, description = "恢复后的授权信息", body = CustomerLicense),
+        (status = 400, description = "卡密未暂停或已撤销"),
+        (status = 401, description = "未认证"),
+        (status = 403, description = "卡密所属应用不允许自行恢复"),
+        (status = 404, description = "卡密不在账号中"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+    security(("customer_auth" = [])),
+)]
+async fn resume_license(
+    state: web::Data<AppState>,
+    customer: AuthenticatedCustomer,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let card_number = path.into_inner();
+    info!("Customer '{}' is resuming card '{}'", customer.email, card_number);
+    toggle_pause(&state, &customer, &card_number, false).await
+}
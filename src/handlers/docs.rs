@@ -0,0 +1,9 @@
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::openapi::ApiDoc;
+
+// 配置接口文档路由：/api/openapi.json 返回 OpenAPI 文档，/api/docs/ 为内置的 Swagger UI 页面
+pub fn config() -> SwaggerUi {
+    SwaggerUi::new("/api/docs/{_:.*}").url("/api/openapi.json", ApiDoc::openapi())
+}
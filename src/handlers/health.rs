@@ -0,0 +1,119 @@
+use actix_web::{web, HttpResponse};
+use mongodb::bson::doc;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::{migrations, AppState};
+
+// 就绪检查中 MongoDB ping 的超时时间
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+// 配置健康检查路由，挂在根路径下且无需认证
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/healthz", web::get().to(liveness))  // 存活检查
+        .route("/readyz", web::get().to(readiness)); // 就绪检查
+}
+
+// 单个依赖组件的检查结果
+#[derive(Debug, Serialize)]
+struct ComponentStatus {
+    status: &'static str,  // ok 或 error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ComponentStatus {
+    fn ok() -> Self {
+        Self { status: "ok", latency_ms: None, pending: None, error: None }
+    }
+
+    fn error(error: impl Into<String>) -> Self {
+        Self { status: "error", latency_ms: None, pending: None, error: Some(error.into()) }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Components {
+    mongodb: ComponentStatus,
+    migrations: ComponentStatus,
+    scheduler: ComponentStatus,
+}
+
+// 存活检查：进程能处理请求即返回成功，不检查外部依赖
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    summary = "存活检查",
+    responses((status = 200, description = "进程正常运行，返回状态和版本号")),
+)]
+async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+// 就绪检查：MongoDB 可用、迁移已全部执行、定时任务调度器正常运行
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    summary = "就绪检查",
+    description = "检查 MongoDB、数据库迁移和定时任务调度器，components 中给出各项检查结果",
+    responses(
+        (status = 200, description = "服务可以接收流量"),
+        (status = 503, description = "有依赖组件不可用"),
+    ),
+)]
+async fn readiness(state: web::Data<AppState>) -> HttpResponse {
+    let started = Instant::now();
+    let mongodb = match actix_web::rt::time::timeout(PING_TIMEOUT, state.db.run_command(doc! { "ping": 1 }, None)).await {
+        Ok(Ok(_)) => ComponentStatus {
+            latency_ms: Some(started.elapsed().as_secs_f64() * 1000.0),
+            ..ComponentStatus::ok()
+        },
+        Ok(Err(e)) => ComponentStatus::error(e.to_string()),
+        Err(_) => ComponentStatus::error(format!("ping timed out after {}s", PING_TIMEOUT.as_secs())),
+    };
+
+    let migrations = if mongodb.is_ok() {
+        match migrations::pending(&state.db).await {
+            Ok(pending) if pending.is_empty() => ComponentStatus::ok(),
+            Ok(pending) => ComponentStatus {
+                pending: Some(pending.iter().map(|m| m.id).collect()),
+                ..ComponentStatus::error("pending migrations")
+            },
+            Err(e) => ComponentStatus::error(e.to_string()),
+        }
+    } else {
+        ComponentStatus::error("mongodb unavailable")
+    };
+
+    let scheduler = if state.scheduler.is_alive() {
+        ComponentStatus::ok()
+    } else {
+        ComponentStatus::error("scheduler not running or jobs overdue")
+    };
+
+    let components = Components { mongodb, migrations, scheduler };
+    let ready = components.mongodb.is_ok() && components.migrations.is_ok() && components.scheduler.is_ok();
+    let body = serde_json::json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "components": components,
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
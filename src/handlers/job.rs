@@ -0,0 +1,80 @@
+use actix_web::{web, HttpResponse, Scope};
+use log::{info, warn};
+
+use crate::{
+    auth::{require_admin, AuthenticatedUser},
+    scheduler::{Job, JobStatus},
+    AppState,
+};
+
+// 配置定时任务相关的路由（仅管理员）
+pub fn config() -> Scope {
+    web::scope("/jobs")
+        .route("", web::get().to(get_jobs))                  // 获取任务状态
+        .route("/{name}/run", web::post().to(run_job))       // 立即执行任务
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    tag = "jobs",
+    summary = "获取定时任务状态",
+    responses(
+        (status = 200, description = "所有定时任务的状态", body = Vec<JobStatus>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_jobs(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    match require_admin(user) {
+        Ok(_) => HttpResponse::Ok().json(state.scheduler.statuses()),
+        Err(e) => HttpResponse::from_error(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs/{name}/run",
+    tag = "jobs",
+    summary = "立即执行定时任务",
+    description = "任务在后台执行，结果通过任务状态查看",
+    params(("name" = String, Path, description = "任务名称，如 expire_cards")),
+    responses(
+        (status = 202, description = "任务已开始执行"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "任务不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn run_job(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let name = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' triggered job '{}'", user.username, name);
+
+    let job = match Job::from_name(&name) {
+        Some(job) => job,
+        None => {
+            warn!("Unknown job '{}'", name);
+            return HttpResponse::NotFound().body("Job not found");
+        }
+    };
+
+    state.audit.record(&user, "job.run", Some(name), None);
+
+    // 在后台执行，结果通过任务状态查看
+    let scheduler = state.scheduler.clone();
+    state.shutdown.spawn(async move {
+        scheduler.run(job).await;
+    });
+    HttpResponse::Accepted().body("Job started")
+}
@@ -0,0 +1,30 @@
+use actix_web::{web, HttpResponse, Scope};
+use log::error;
+
+use crate::metrics::metrics;
+
+// 配置 Prometheus 指标路由，供 Prometheus 抓取，无需认证
+pub fn config() -> Scope {
+    web::scope("/metrics")
+        .route("", web::get().to(get_metrics))  // 导出所有指标
+}
+
+// 以 Prometheus 文本格式返回指标
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    summary = "Prometheus 指标",
+    responses((status = 200, description = "Prometheus 文本格式的指标", body = String, content_type = "text/plain")),
+)]
+async fn get_metrics() -> HttpResponse {
+    match metrics().render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(body),
+        Err(e) => {
+            error!("Failed to render metrics: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
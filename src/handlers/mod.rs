@@ -1,2 +1,39 @@
+use actix_web::middleware::DefaultHeaders;
+use actix_web::web;
+
+pub mod api_key;
+pub mod application;
+pub mod audit;
+pub mod bulk;
 pub mod card;
-pub mod user; 
\ No newline at end of file
+pub mod customer;
+pub mod docs;
+pub mod health;
+pub mod job;
+pub mod metrics;
+pub mod pause;
+pub mod rebind;
+pub mod user;
+pub mod stats;
+pub mod v2;
+pub mod webhook;
+
+// 注册各版本共用的业务路由，挂在 /api/v1、/api/v2 和未带版本的 /api 下
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(card::config())      // 注册卡密相关路由
+        .service(user::config())     // 注册用户相关路由
+        .service(stats::config())    // 注册统计相关路由
+        .service(webhook::config())  // 注册 Webhook 相关路由
+        .service(job::config())      // 注册定时任务相关路由
+        .service(api_key::config())  // 注册 API 密钥相关路由
+        .service(application::config())  // 注册客户端应用相关路由
+        .service(customer::config())     // 注册终端客户相关路由
+        .service(audit::config());       // 注册审计日志相关路由
+}
+
+// v1 已废弃，响应中附加 Deprecation 头并通过 Link 头指向 v2
+pub fn v1_deprecation_headers() -> DefaultHeaders {
+    DefaultHeaders::new()
+        .add(("Deprecation", "true"))
+        .add(("Link", "</api/v2>; rel=\"successor-version\""))
+}
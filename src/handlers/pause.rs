@@ -0,0 +1,342 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn, error};
+use mongodb::bson::{doc, oid::ObjectId, Bson};
+use mongodb::Database;
+
+use crate::{
+    api_key::hash_key,
+    auth::AuthenticatedUser,
+    client_auth::{ClientJson, CODE_APP_INVALID},
+    handlers::card::{app_scope, resolve_app},
+    handlers::rebind::rejection,
+    handlers::v2::{
+        CODE_CARD_CONFLICT, CODE_CARD_EXPIRED, CODE_CARD_INVALID, CODE_CARD_NOT_ACTIVATED, CODE_CARD_NOT_FOUND, CODE_CARD_NOT_PAUSED,
+        CODE_CARD_PAUSED, CODE_CARD_REVOKED, CODE_PAUSE_DISABLED, CODE_REBIND_UNAUTHORIZED,
+    },
+    models::application::Application,
+    models::rebind::CardOwner,
+    models::webhook::{EVENT_CARD_PAUSED, EVENT_CARD_RESUMED},
+    models::{Card, OwnerPauseRequest, UserRole},
+    webhook::{card_event_data, dispatch_event},
+    AppState,
+};
+
+// 所有者操作时记录的操作者
+const OWNER_ACTOR: &str = "owner";
+
+// 暂停卡密：记录剩余有效时间并清空过期时间，暂停期间不计时、不会被标记过期；
+// 以读取到的过期时间为条件，并发修改时拒绝
+pub(crate) async fn pause(
+    db: &Database,
+    card: &Card,
+    actor: &str,
+) -> Result<Result<Card, HttpResponse>, mongodb::error::Error> {
+    if card.is_revoked {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_REVOKED, "此卡密已被撤销")));
+    }
+    if !card.is_activated {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_NOT_ACTIVATED, "此卡密尚未激活")));
+    }
+    if card.is_paused {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_PAUSED, "此卡密已暂停")));
+    }
+    if !card.is_time_limited() {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_INVALID, "不限时间的卡密无需暂停")));
+    }
+    let now = Utc::now();
+    let expires_at = card.expires_at_str.as_ref().and_then(|s| s.parse::<DateTime<Utc>>().ok());
+    let Some(expires_at) = expires_at.filter(|expires_at| *expires_at > now) else {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_EXPIRED, "此卡密已过期")));
+    };
+
+    let remaining_seconds = (expires_at - now).num_seconds();
+    let filter = doc! {
+        "_id": card.id,
+        "is_paused": { "$ne": true },
+        "is_revoked": { "$ne": true },
+        "expires_at_str": &card.expires_at_str,
+    };
+    let update = doc! {
+        "$set": {
+            "is_paused": true,
+            "paused_at_str": now.to_rfc3339(),
+            "paused_remaining_seconds": remaining_seconds,
+            "paused_by": actor,
+            "expires_at_str": Bson::Null,
+        }
+    };
+    if db.collection::<Card>("cards").update_one(filter, update, None).await?.modified_count == 0 {
+        return Ok(Err(rejection(HttpResponse::Conflict(), CODE_CARD_CONFLICT, "卡密已被其他请求修改，请重试")));
+    }
+
+    let mut card = card.clone();
+    card.is_paused = true;
+    card.paused_at_str = Some(now.to_rfc3339());
+    card.paused_remaining_seconds = Some(remaining_seconds);
+    card.paused_by = Some(actor.to_string());
+    card.expires_at_str = None;
+    info!("Card '{}' paused by '{}' with {}s remaining", card.card_number, actor, remaining_seconds);
+    dispatch_event(db, EVENT_CARD_PAUSED, card_event_data(&card)).await;
+    Ok(Ok(card))
+}
+
+// 恢复卡密：按暂停时记录的剩余时间重新计算过期时间
+pub(crate) async fn resume(
+    db: &Database,
+    card: &Card,
+    actor: &str,
+) -> Result<Result<Card, HttpResponse>, mongodb::error::Error> {
+    if card.is_revoked {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_REVOKED, "此卡密已被撤销")));
+    }
+    if !card.is_paused {
+        return Ok(Err(rejection(HttpResponse::BadRequest(), CODE_CARD_NOT_PAUSED, "此卡密未暂停")));
+    }
+
+    let now = Utc::now();
+    // 有固定到期时间的卡密恢复后不晚于固定到期时间
+    let mut expires_at = now + Duration::seconds(card.paused_remaining_seconds.unwrap_or(0));
+    if let Some(valid_until) = card.valid_until_str.as_deref().and_then(|s| s.parse::<DateTime<Utc>>().ok()) {
+        expires_at = expires_at.min(valid_until);
+    }
+    let expires_at = expires_at.to_rfc3339();
+    let filter = doc! { "_id": card.id, "is_paused": true, "paused_at_str": &card.paused_at_str };
+    let update = doc! {
+        "$set": {
+            "is_paused": false,
+            "expires_at_str": &expires_at,
+            "is_expired": false,
+            "expiry_notified": false,
+        },
+        "$unset": { "paused_at_str": "", "paused_remaining_seconds": "", "paused_by": "" },
+    };
+    if db.collection::<Card>("cards").update_one(filter, update, None).await?.modified_count == 0 {
+        return Ok(Err(rejection(HttpResponse::Conflict(), CODE_CARD_CONFLICT, "卡密已被其他请求修改，请重试")));
+    }
+
+    let mut card = card.clone();
+    card.is_paused = false;
+    card.expires_at_str = Some(expires_at);
+    card.is_expired = false;
+    card.expiry_notified = false;
+    card.paused_at_str = None;
+    card.paused_remaining_seconds = None;
+    card.paused_by = None;
+    info!("Card '{}' resumed by '{}', expires at {:?}", card.card_number, actor, card.expires_at_str);
+    dispatch_event(db, EVENT_CARD_RESUMED, card_event_data(&card)).await;
+    Ok(Ok(card))
+}
+
+// 查找当前用户可管理的卡密：管理员可操作任何卡密，普通用户只能操作自己创建的卡密
+pub(crate) async fn find_managed_card(state: &AppState, user: &AuthenticatedUser, id: &str) -> Result<Card, HttpResponse> {
+    let object_id = ObjectId::parse_str(id).map_err(|e| {
+        warn!("Invalid card ID format: {}", e);
+        HttpResponse::BadRequest().body(format!("Invalid ID format: {}", e))
+    })?;
+    let filter = match user.role {
+        UserRole::Admin => doc! { "_id": object_id, "deleted_at_str": null },
+        _ => doc! { "_id": object_id, "created_by": &user.user_id, "deleted_at_str": null },
+    };
+    match state.db.collection::<Card>("cards").find_one(filter, None).await {
+        Ok(Some(card)) => Ok(card),
+        Ok(None) => Err(HttpResponse::NotFound().body("Card not found or you don't have permission to modify it")),
+        Err(e) => Err(HttpResponse::InternalServerError().body(e.to_string())),
+    }
+}
+
+// 暂停卡密处理函数（后台）
+#[utoipa::path(
+    post,
+    path = "/cards/{id}/pause",
+    tag = "cards",
+    summary = "暂停卡密",
+    description = "冻结已激活的卡密，记录剩余有效时间并清空过期时间；暂停期间验证和心跳返回 CARD_PAUSED，恢复后按剩余时间重新计算过期时间。失败时 v2 的 code 为 CARD_NOT_ACTIVATED、CARD_EXPIRED、CARD_REVOKED、CARD_PAUSED 或 CARD_CONFLICT",
+    params(("id" = String, Path, description = "卡密ID")),
+    responses(
+        (status = 200, description = "暂停后的卡密", body = Card),
+        (status = 400, description = "卡密未激活、已过期、已撤销或已暂停"),
+        (status = 404, description = "卡密不存在或无权操作"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+pub(crate) async fn pause_card(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    info!("User '{}' attempting to pause card. Request params: id={}", user.username, id);
+
+    let card = match find_managed_card(&state, &user, &id).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    match pause(&state.db, &card, &user.username).await {
+        Ok(Ok(card)) => {
+            state.audit.record(&user, "card.pause", Some(id), None);
+            HttpResponse::Ok().json(card)
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            error!("Error pausing card '{}': {}", id, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 恢复卡密处理函数（后台）
+#[utoipa::path(
+    post,
+    path = "/cards/{id}/resume",
+    tag = "cards",
+    summary = "恢复已暂停的卡密",
+    description = "过期时间重新计算为当前时间加上暂停时的剩余时间。失败时 v2 的 code 为 CARD_REVOKED、CARD_NOT_PAUSED 或 CARD_CONFLICT",
+    params(("id" = String, Path, description = "卡密ID")),
+    responses(
+        (status = 200, description = "恢复后的卡密", body = Card),
+        (status = 400, description = "卡密未暂停或已撤销"),
+        (status = 404, description = "卡密不存在或无权操作"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+pub(crate) async fn resume_card(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    info!("User '{}' attempting to resume card. Request params: id={}", user.username, id);
+
+    let card = match find_managed_card(&state, &user, &id).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    match resume(&state.db, &card, &user.username).await {
+        Ok(Ok(card)) => {
+            state.audit.record(&user, "card.resume", Some(id), None);
+            HttpResponse::Ok().json(card)
+        }
+        Ok(Err(response)) => response,
+        Err(e) => {
+            error!("Error resuming card '{}': {}", id, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 查找所有者请求的卡密并校验所有者密钥；应用未开启 allow_owner_pause 时拒绝
+async fn find_owned_card(
+    state: &AppState,
+    signed_app: Option<Application>,
+    req: &OwnerPauseRequest,
+) -> Result<Card, HttpResponse> {
+    let app = match resolve_app(&state.db, signed_app, req.app_key.as_deref()).await {
+        Ok(Some(app)) => app,
+        Ok(None) => return Err(rejection(HttpResponse::Unauthorized(), CODE_APP_INVALID, "应用不存在或已停用")),
+        Err(e) => return Err(HttpResponse::InternalServerError().body(e.to_string())),
+    };
+
+    let card_filter = doc! {
+        "card_number": &req.card_number,
+        "deleted_at_str": null,
+        "app_id": app_scope(app.as_ref()),
+    };
+    let card = match state.db.collection::<Card>("cards").find_one(card_filter, None).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return Err(rejection(HttpResponse::NotFound(), CODE_CARD_NOT_FOUND, "卡密不存在")),
+        Err(e) => return Err(HttpResponse::InternalServerError().body(e.to_string())),
+    };
+
+    let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+    let filter = doc! { "card_id": card_id, "secret_hash": hash_key(&req.owner_secret) };
+    match state.db.collection::<CardOwner>("card_owners").find_one(filter, None).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(rejection(HttpResponse::Unauthorized(), CODE_REBIND_UNAUTHORIZED, "所有者密钥错误")),
+        Err(e) => return Err(HttpResponse::InternalServerError().body(e.to_string())),
+    }
+
+    // 卡密归属的应用决定是否可暂停（查询条件已保证卡密属于该应用），未归属应用的卡密只能由后台暂停
+    if card.app_id.is_none() || !app.is_some_and(|app| app.allow_owner_pause) {
+        return Err(rejection(HttpResponse::Forbidden(), CODE_PAUSE_DISABLED, "该卡密不支持自行暂停"));
+    }
+    Ok(card)
+}
+
+// 所有者暂停卡密处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/pause",
+    tag = "cards",
+    summary = "所有者暂停卡密",
+    description = "凭激活时返回的所有者密钥暂停卡密，需要卡密所属应用开启 allow_owner_pause。失败时 v2 的 code 为 CARD_NOT_FOUND、REBIND_UNAUTHORIZED、PAUSE_DISABLED、CARD_NOT_ACTIVATED、CARD_EXPIRED、CARD_REVOKED、CARD_PAUSED 或 CARD_CONFLICT",
+    request_body = OwnerPauseRequest,
+    responses(
+        (status = 200, description = "暂停后的卡密", body = Card),
+        (status = 400, description = "卡密未激活、已过期、已撤销或已暂停"),
+        (status = 401, description = "所有者密钥错误，签名校验失败或应用无效"),
+        (status = 403, description = "卡密所属应用不允许所有者暂停"),
+        (status = 404, description = "卡密不存在"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+)]
+pub(crate) async fn owner_pause_card(
+    state: web::Data<AppState>,
+    req: ClientJson<OwnerPauseRequest>,
+) -> HttpResponse {
+    info!("Owner pausing card. Request params: card_number={}", req.card_number);
+
+    let ClientJson { app, body: req } = req;
+    let card = match find_owned_card(&state, app, &req).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    match pause(&state.db, &card, OWNER_ACTOR).await {
+        Ok(Ok(card)) => HttpResponse::Ok().json(card),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            error!("Error pausing card '{}': {}", req.card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 所有者恢复卡密处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/resume",
+    tag = "cards",
+    summary = "所有者恢复卡密",
+    description = "凭所有者密钥恢复已暂停的卡密，需要卡密所属应用开启 allow_owner_pause。失败时 v2 的 code 为 CARD_NOT_FOUND、REBIND_UNAUTHORIZED、PAUSE_DISABLED、CARD_REVOKED、CARD_NOT_PAUSED 或 CARD_CONFLICT",
+    request_body = OwnerPauseRequest,
+    responses(
+        (status = 200, description = "恢复后的卡密", body = Card),
+        (status = 400, description = "卡密未暂停或已撤销"),
+        (status = 401, description = "所有者密钥错误，签名校验失败或应用无效"),
+        (status = 403, description = "卡密所属应用不允许所有者恢复"),
+        (status = 404, description = "卡密不存在"),
+        (status = 409, description = "卡密被并发修改，可重试"),
+    ),
+)]
+pub(crate) async fn owner_resume_card(
+    state: web::Data<AppState>,
+    req: ClientJson<OwnerPauseRequest>,
+) -> HttpResponse {
+    info!("Owner resuming card. Request params: card_number={}", req.card_number);
+
+    let ClientJson { app, body: req } = req;
+    let card = match find_owned_card(&state, app, &req).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    match resume(&state.db, &card, OWNER_ACTOR).await {
+        Ok(Ok(card)) => HttpResponse::Ok().json(card),
+        Ok(Err(response)) => response,
+        Err(e) => {
+            error!("Error resuming card '{}': {}", req.card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
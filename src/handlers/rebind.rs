@@ -0,0 +1,433 @@
+use actix_web::{web, HttpResponse, HttpResponseBuilder};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::{ReplaceOptions, UpdateOptions};
+use mongodb::Database;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::{
+    api_key::hash_key,
+    auth::AuthenticatedUser,
+    client_auth::{ClientJson, CODE_APP_INVALID},
+    client_ip::ClientIp,
+    envelope::with_code,
+    handlers::card::{app_scope, resolve_app},
+    handlers::pause::find_managed_card,
+    handlers::v2::{
+        CODE_CARD_CONFLICT, CODE_CARD_EXPIRED, CODE_CARD_NOT_ACTIVATED, CODE_CARD_NOT_FOUND, CODE_CARD_PAUSED,
+        CODE_CARD_REVOKED, CODE_IDENTIFIER_REQUIRED, CODE_REBIND_DISABLED, CODE_REBIND_LIMIT_EXCEEDED,
+        CODE_REBIND_TIME_INSUFFICIENT, CODE_REBIND_UNAUTHORIZED,
+    },
+    models::application::DevicePolicy,
+    models::device::PresentedDevice,
+    models::rebind::{
+        CardOwner, OwnerSecretResponse, RebindCode, RebindCodeRequest, RebindMethod, RebindRecord, RebindRequest,
+        RebindResponse, OWNER_SECRET_PREFIX,
+    },
+    models::webhook::{EVENT_CARD_REBIND_CODE, EVENT_CARD_REBOUND},
+    models::Card,
+    webhook::{card_event_data, dispatch_event},
+    AppState,
+};
+
+// 验证码有效期（秒）
+pub(crate) const CODE_TTL_SECS: i64 = 600;
+// 重新发送验证码的最小间隔（秒）
+pub(crate) const CODE_RESEND_SECS: i64 = 60;
+// 验证码最多尝试次数，超过后需要重新申请
+const CODE_MAX_ATTEMPTS: i32 = 5;
+
+// 生成所有者密钥
+fn new_owner_secret() -> String {
+    format!(
+        "{}{}",
+        OWNER_SECRET_PREFIX,
+        rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect::<String>()
+    )
+}
+
+// 为首次激活的卡密签发所有者密钥，只保存哈希；失败时不影响激活，返回 None
+pub(crate) async fn issue_owner_secret(db: &Database, card: &Card, email: Option<&str>) -> Option<String> {
+    let card_id = card.id?.to_hex();
+    let email = email.map(str::trim).filter(|email| {
+        let valid = email.contains('@');
+        if !valid {
+            warn!("Ignoring invalid owner email for card '{}'", card.card_number);
+        }
+        valid
+    });
+    let secret = new_owner_secret();
+    let owner = CardOwner {
+        id: None,
+        card_id,
+        card_number: card.card_number.clone(),
+        secret_hash: hash_key(&secret),
+        email: email.map(str::to_string),
+        created_at_str: Utc::now().to_rfc3339(),
+    };
+    match db.collection::<CardOwner>("card_owners").insert_one(&owner, None).await {
+        Ok(_) => Some(secret),
+        Err(e) => {
+            error!("Failed to issue owner secret for card '{}': {}", card.card_number, e);
+            None
+        }
+    }
+}
+
+// 重新签发所有者密钥：替换已有的密钥（原密钥失效，保留邮箱），没有所有者记录的卡密补发一条记录
+pub(crate) async fn reset_owner_secret(db: &Database, card: &Card) -> Result<String, mongodb::error::Error> {
+    let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+    let secret = new_owner_secret();
+    let update = doc! {
+        "$set": { "card_number": &card.card_number, "secret_hash": hash_key(&secret) },
+        "$setOnInsert": { "email": Bson::Null, "created_at_str": Utc::now().to_rfc3339() },
+    };
+    let options = UpdateOptions::builder().upsert(true).build();
+    db.collection::<CardOwner>("card_owners")
+        .update_one(doc! { "card_id": &card_id }, update, options)
+        .await?;
+    Ok(secret)
+}
+
+// 生成6位数字验证码
+pub(crate) fn numeric_code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+// 换绑被拒绝时的响应，v2 中附带错误码
+pub(crate) fn rejection(mut response: HttpResponseBuilder, code: &'static str, message: &str) -> HttpResponse {
+    with_code(response.body(message.to_string()), code, message.to_string())
+}
+
+// 申请换绑邮箱验证码处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/rebind/code",
+    tag = "cards",
+    summary = "申请换绑邮箱验证码",
+    description = "卡密激活时记录了所有者邮箱时生成6位验证码（10分钟内有效），通过 card.rebind_code Webhook 事件投递，由接入方发送邮件。为避免泄露卡密信息，无论卡密是否存在、是否记录了邮箱都返回相同的响应；60秒内不会重复发送",
+    request_body = RebindCodeRequest,
+    responses(
+        (status = 202, description = "已受理"),
+        (status = 401, description = "签名校验失败或应用无效"),
+    ),
+)]
+pub(crate) async fn request_rebind_code(
+    state: web::Data<AppState>,
+    req: ClientJson<RebindCodeRequest>,
+) -> HttpResponse {
+    info!("Rebind code requested. Request params: card_number={}", req.card_number);
+    let accepted = || HttpResponse::Accepted().body("如果该卡密记录了所有者邮箱，验证码已发送");
+
+    let ClientJson { app, body: req } = req;
+    let app = match resolve_app(&state.db, app, req.app_key.as_deref()).await {
+        Ok(Some(app)) => app,
+        Ok(None) => return rejection(HttpResponse::Unauthorized(), CODE_APP_INVALID, "应用不存在或已停用"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let card_filter = doc! {
+        "card_number": &req.card_number,
+        "deleted_at_str": null,
+        "is_activated": true,
+        "is_revoked": { "$ne": true },
+        "app_id": app_scope(app.as_ref()),
+    };
+    let card = match state.db.collection::<Card>("cards").find_one(card_filter, None).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return accepted(),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+    let email = match state.db.collection::<CardOwner>("card_owners").find_one(doc! { "card_id": &card_id }, None).await {
+        Ok(Some(CardOwner { email: Some(email), .. })) => email,
+        Ok(_) => return accepted(),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    // 距离上次发送不足间隔时不重复发送
+    let codes = state.db.collection::<RebindCode>("rebind_codes");
+    let now = Utc::now();
+    match codes.find_one(doc! { "_id": &card_id }, None).await {
+        Ok(Some(existing)) => {
+            let recent = existing.created_at_str.parse::<DateTime<Utc>>()
+                .is_ok_and(|created_at| now - created_at < Duration::seconds(CODE_RESEND_SECS));
+            if recent {
+                return accepted();
+            }
+        }
+        Ok(None) => {}
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    }
+
+    let code = numeric_code();
+    let expires_at = now + Duration::seconds(CODE_TTL_SECS);
+    let rebind_code = RebindCode {
+        card_id: card_id.clone(),
+        code_hash: hash_key(&code),
+        attempts: 0,
+        created_at_str: now.to_rfc3339(),
+        expires_at: bson::DateTime::from_chrono(expires_at),
+    };
+    let options = ReplaceOptions::builder().upsert(true).build();
+    if let Err(e) = codes.replace_one(doc! { "_id": &card_id }, &rebind_code, options).await {
+        error!("Failed to save rebind code for card '{}': {}", req.card_number, e);
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    dispatch_event(&state.db, EVENT_CARD_REBIND_CODE, serde_json::json!({
+        "card_id": card_id,
+        "card_number": card.card_number,
+        "email": email,
+        "code": code,
+        "expires_at": expires_at.to_rfc3339(),
+    })).await;
+    info!("Rebind code issued for card '{}'", req.card_number);
+    accepted()
+}
+
+// 校验并使用验证码（换绑验证码、客户登录验证码），成功后删除验证码；失败时累计尝试次数
+pub(crate) async fn consume_code(db: &Database, collection: &str, id: &str, code: &str) -> Result<bool, mongodb::error::Error> {
+    let codes = db.collection::<Document>(collection);
+    let filter = doc! {
+        "_id": id,
+        "expires_at": { "$gt": bson::DateTime::now() },
+        "attempts": { "$lt": CODE_MAX_ATTEMPTS },
+    };
+    let Some(stored) = codes.find_one(filter, None).await? else {
+        return Ok(false);
+    };
+    let code_hash = hash_key(code);
+    if stored.get_str("code_hash").ok() != Some(code_hash.as_str()) {
+        codes.update_one(doc! { "_id": id }, doc! { "$inc": { "attempts": 1 } }, None).await?;
+        return Ok(false);
+    }
+    // 并发使用同一验证码时只有一个请求能删除成功
+    Ok(codes.delete_one(doc! { "_id": id, "code_hash": code_hash }, None).await?.deleted_count == 1)
+}
+
+// 检查换绑次数限制，返回当前周期内已使用的次数；禁止换绑或次数已用完时返回拒绝响应
+pub(crate) async fn check_rebind_quota(
+    db: &Database,
+    card: &Card,
+    policy: &DevicePolicy,
+) -> Result<Result<u32, HttpResponse>, mongodb::error::Error> {
+    if policy.rebind_limit == 0 {
+        return Ok(Err(rejection(HttpResponse::Forbidden(), CODE_REBIND_DISABLED, "该应用不允许自助换绑")));
+    }
+    let period_start = (Utc::now() - Duration::days(policy.rebind_period_days as i64)).to_rfc3339();
+    let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+    let used = db.collection::<RebindRecord>("card_rebinds")
+        .count_documents(doc! { "card_id": card_id, "created_at_str": { "$gte": period_start } }, None)
+        .await? as u32;
+    if used >= policy.rebind_limit {
+        warn!("Rebind limit reached for card '{}'", card.card_number);
+        return Ok(Err(rejection(HttpResponse::TooManyRequests(), CODE_REBIND_LIMIT_EXCEEDED, "换绑次数已用完，请稍后再试")));
+    }
+    Ok(Ok(used))
+}
+
+// 自助换绑处理函数
+#[utoipa::path(
+    post,
+    path = "/cards/rebind",
+    tag = "cards",
+    summary = "自助换绑设备",
+    description = "重装系统或更换设备后，使用激活时返回的所有者密钥或邮箱验证码，将卡密的设备绑定替换为当前设备。换绑次数受应用设备策略的 rebind_limit / rebind_period_days 限制，并可按 rebind_penalty_hours 扣除有效时间；v2 中失败的 code 为 CARD_NOT_FOUND、CARD_NOT_ACTIVATED、CARD_EXPIRED、CARD_REVOKED、CARD_PAUSED、REBIND_UNAUTHORIZED、REBIND_DISABLED、REBIND_LIMIT_EXCEEDED、REBIND_TIME_INSUFFICIENT、IDENTIFIER_REQUIRED 或 CARD_CONFLICT。次数、设备标识和扣除时间在校验凭证之前检查，被拒绝时不会消耗邮箱验证码",
+    request_body = RebindRequest,
+    responses(
+        (status = 200, description = "换绑成功", body = RebindResponse),
+        (status = 400, description = "未提供凭证、卡密未激活、已过期、已撤销、已暂停、缺少设备标识或剩余时间不足以扣除"),
+        (status = 401, description = "所有者密钥或验证码错误，签名校验失败或应用无效"),
+        (status = 403, description = "应用不允许自助换绑"),
+        (status = 404, description = "卡密不存在"),
+        (status = 409, description = "卡密绑定被并发修改，可重试"),
+        (status = 429, description = "当前周期内的换绑次数已用完"),
+    ),
+)]
+pub(crate) async fn rebind_card(
+    state: web::Data<AppState>,
+    req: ClientJson<RebindRequest>,
+    client_ip: ClientIp,
+) -> HttpResponse {
+    info!("Rebinding card. Request params: card_number={}", req.card_number);
+
+    let ClientJson { app, body: req } = req;
+    let app = match resolve_app(&state.db, app, req.app_key.as_deref()).await {
+        Ok(Some(app)) => app,
+        Ok(None) => return rejection(HttpResponse::Unauthorized(), CODE_APP_INVALID, "应用不存在或已停用"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let policy = app.as_ref().map(|app| app.device_policy.clone()).unwrap_or_default();
+
+    let collection = state.db.collection::<Card>("cards");
+    let card_filter = doc! {
+        "card_number": &req.card_number,
+        "deleted_at_str": null,
+        "app_id": app_scope(app.as_ref()),
+    };
+    let card = match collection.find_one(card_filter, None).await {
+        Ok(Some(card)) => card,
+        Ok(None) => return rejection(HttpResponse::NotFound(), CODE_CARD_NOT_FOUND, "卡密不存在"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let now = Utc::now();
+    let expires_at = card.expires_at_str.as_ref().and_then(|s| s.parse::<DateTime<Utc>>().ok());
+    if card.is_revoked {
+        return rejection(HttpResponse::BadRequest(), CODE_CARD_REVOKED, "此卡密已被撤销");
+    }
+    if !card.is_activated {
+        return rejection(HttpResponse::BadRequest(), CODE_CARD_NOT_ACTIVATED, "此卡密尚未激活");
+    }
+    if card.is_paused {
+        return rejection(HttpResponse::BadRequest(), CODE_CARD_PAUSED, "此卡密已暂停，请恢复后再换绑");
+    }
+    // 不限时间的卡密没有过期时间，换绑时也不扣除时间
+    let expires_at = match expires_at.filter(|expires_at| *expires_at > now) {
+        Some(expires_at) => Some(expires_at),
+        None if !card.is_time_limited() => None,
+        None => return rejection(HttpResponse::BadRequest(), CODE_CARD_EXPIRED, "此卡密已过期"),
+    };
+
+    // 先检查次数限制、设备标识和扣除时间，避免被拒绝的换绑消耗邮箱验证码
+    let used = match check_rebind_quota(&state.db, &card, &policy).await {
+        Ok(Ok(used)) => used,
+        Ok(Err(response)) => return response,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let presented = PresentedDevice::new(req.user_identifier.clone(), req.device.clone(), client_ip);
+    if policy.require_identifier && req.user_identifier.is_none() && presented.device.is_none() {
+        return rejection(HttpResponse::BadRequest(), CODE_IDENTIFIER_REQUIRED, "缺少设备标识 user_identifier");
+    }
+
+    // 剩余有效时间不足以扣除时拒绝，不会因换绑直接过期
+    let new_expires_at = expires_at.map(|expires_at| expires_at - Duration::hours(policy.rebind_penalty_hours as i64));
+    if new_expires_at.is_some_and(|new_expires_at| new_expires_at <= now) {
+        return rejection(
+            HttpResponse::BadRequest(),
+            CODE_REBIND_TIME_INSUFFICIENT,
+            &format!("剩余有效时间不足 {} 小时，无法换绑", policy.rebind_penalty_hours),
+        );
+    }
+    let new_expires_at = new_expires_at.map(|new_expires_at| new_expires_at.to_rfc3339());
+
+    // 校验所有者密钥或邮箱验证码
+    let card_id = card.id.map(|id| id.to_hex()).unwrap_or_default();
+    let method = match (&req.owner_secret, &req.email_code) {
+        (Some(secret), _) => {
+            let filter = doc! { "card_id": &card_id, "secret_hash": hash_key(secret) };
+            match state.db.collection::<CardOwner>("card_owners").find_one(filter, None).await {
+                Ok(Some(_)) => RebindMethod::OwnerSecret,
+                Ok(None) => return rejection(HttpResponse::Unauthorized(), CODE_REBIND_UNAUTHORIZED, "所有者密钥错误"),
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        (None, Some(code)) => match consume_code(&state.db, "rebind_codes", &card_id, code).await {
+            Ok(true) => RebindMethod::EmailCode,
+            Ok(false) => return rejection(HttpResponse::Unauthorized(), CODE_REBIND_UNAUTHORIZED, "验证码错误或已过期"),
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        (None, None) => return HttpResponse::BadRequest().body("需要提供 owner_secret 或 email_code"),
+    };
+
+    // 替换全部绑定为当前设备并扣除有效时间；以原绑定为条件，并发换绑中只有一个能成功
+    let binding = presented.to_binding(&now.to_rfc3339());
+    let filter = match bson::to_bson(&card.bindings) {
+        Ok(bindings) => doc! { "_id": card.id, "bindings": bindings },
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let update = match bson::to_bson(&binding) {
+        Ok(new_binding) => doc! { "$set": { "bindings": [new_binding], "expires_at_str": &new_expires_at } },
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    match collection.update_one(filter, update, None).await {
+        Ok(result) if result.modified_count == 0 => {
+            return rejection(HttpResponse::Conflict(), CODE_CARD_CONFLICT, "卡密绑定已被其他请求修改，请重试");
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error rebinding card '{}': {}", req.card_number, e);
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    }
+
+    let record = RebindRecord {
+        id: None,
+        card_id,
+        card_number: card.card_number.clone(),
+        method,
+        previous_bindings: card.bindings.clone(),
+        binding: Some(binding.clone()),
+        penalty_hours: policy.rebind_penalty_hours,
+        expires_at_str: new_expires_at.clone(),
+        client_ip: client_ip.to_string(),
+        created_at_str: now.to_rfc3339(),
+    };
+    if let Err(e) = state.db.collection::<RebindRecord>("card_rebinds").insert_one(&record, None).await {
+        error!("Failed to record rebind for card '{}': {}", req.card_number, e);
+    }
+
+    let mut card = card;
+    card.bindings = vec![binding];
+    card.expires_at_str = new_expires_at;
+    info!("Card '{}' rebound via {:?}, penalty {}h", req.card_number, method, policy.rebind_penalty_hours);
+    dispatch_event(&state.db, EVENT_CARD_REBOUND, card_event_data(&card)).await;
+
+    HttpResponse::Ok().json(RebindResponse {
+        card,
+        penalty_hours: policy.rebind_penalty_hours,
+        remaining_rebinds: policy.rebind_limit - used - 1,
+    })
+}
+
+// 重新签发所有者密钥处理函数（后台）
+#[utoipa::path(
+    post,
+    path = "/cards/{id}/owner-secret",
+    tag = "cards",
+    summary = "重新签发所有者密钥",
+    description = "为已激活的卡密签发新的所有者密钥，原密钥立即失效，已记录的邮箱保留。用于所有者遗失密钥，或在签发所有者密钥之前激活、没有所有者记录的卡密。管理员可操作任何卡密，普通用户只能操作自己创建的卡密。失败时 v2 的 code 为 CARD_NOT_ACTIVATED 或 CARD_REVOKED",
+    params(("id" = String, Path, description = "卡密ID")),
+    responses(
+        (status = 200, description = "新的所有者密钥", body = OwnerSecretResponse),
+        (status = 400, description = "卡密未激活或已撤销"),
+        (status = 404, description = "卡密不存在或无权操作"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+pub(crate) async fn reissue_owner_secret(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    info!("User '{}' attempting to reissue owner secret. Request params: id={}", user.username, id);
+
+    let card = match find_managed_card(&state, &user, &id).await {
+        Ok(card) => card,
+        Err(response) => return response,
+    };
+    // 未激活的卡密在激活时签发所有者密钥
+    if !card.is_activated {
+        return rejection(HttpResponse::BadRequest(), CODE_CARD_NOT_ACTIVATED, "此卡密尚未激活");
+    }
+    if card.is_revoked {
+        return rejection(HttpResponse::BadRequest(), CODE_CARD_REVOKED, "此卡密已被撤销");
+    }
+
+    match reset_owner_secret(&state.db, &card).await {
+        Ok(owner_secret) => {
+            info!("Owner secret of card '{}' reissued by user '{}'", card.card_number, user.username);
+            state.audit.record(&user, "card.owner_secret", Some(id), None);
+            HttpResponse::Ok().json(OwnerSecretResponse { card_number: card.card_number, owner_secret })
+        }
+        Err(e) => {
+            error!("Failed to reissue owner secret for card '{}': {}", card.card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use actix_web::{web, HttpResponse, Scope};
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use log::{info, error};
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::FindOptions;
+use mongodb::{Collection, Database};
+use std::collections::BTreeMap;
+
+use crate::{
+    auth::AuthenticatedUser,
+    models::{
+        stats::{ActivationRate, DailyStats, ResellerStats, StatsQuery, StatsResponse},
+        Card, CardQuery, CardStatus, UserRole,
+    },
+    AppState,
+};
+
+// 配置统计相关的路由
+pub fn config() -> Scope {
+    web::scope("/stats")
+        .route("", web::get().to(get_stats))   // 获取统计数据
+}
+
+// 获取统计数据处理函数
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "stats",
+    summary = "获取统计数据",
+    description = "管理员统计全部卡密，普通用户只统计自己创建的卡密；指定 app_id 时只统计该应用的卡密",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "统计数据", body = StatsResponse),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_stats(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    query: web::Query<StatsQuery>,
+) -> HttpResponse {
+    info!("User '{}' is fetching stats. Request params: days={:?}, expiring_days={:?}, top={:?}, app_id={:?}",
+        user.username, query.days, query.expiring_days, query.top, query.app_id);
+
+    // 与 get_all_cards 一致：管理员统计全部卡密，普通用户只统计自己创建的卡密，不含已删除的卡密
+    let mut scope = match user.role {
+        UserRole::Admin => doc! { "deleted_at_str": null },
+        _ => doc! { "created_by": &user.user_id, "deleted_at_str": null },
+    };
+    if let Some(app_id) = &query.app_id {
+        scope.insert("app_id", app_id);
+    }
+
+    match compute_stats(&state.db, scope, &query).await {
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => {
+            error!("Error computing stats: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+// 计算统计数据，scope 为限定卡密范围的过滤条件
+pub async fn compute_stats(
+    db: &Database,
+    scope: Document,
+    query: &StatsQuery,
+) -> Result<StatsResponse, mongodb::error::Error> {
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+    let expiring_days = query.expiring_days.unwrap_or(7).clamp(1, 365);
+    let top = query.top.unwrap_or(10).clamp(1, 100);
+
+    let collection = db.collection::<Document>("cards");
+    let now = Utc::now();
+    let now_str = now.to_rfc3339();
+    // 从回溯起始日的零点开始统计
+    let since_str = format!("{}T00:00:00", (now - Duration::days(days - 1)).format("%Y-%m-%d"));
+    let until_str = (now + Duration::days(expiring_days)).to_rfc3339();
+
+    // 汇总数量
+    let total_cards = collection.count_documents(scope.clone(), None).await? as i64;
+    let activated_cards = collection
+        .count_documents(with_conditions(&scope, doc! { "is_activated": true }), None)
+        .await? as i64;
+    // 有效授权与卡密列表的 active 状态一致：未过期或不限时间，且次数未用完
+    let active_filter = CardQuery { status: Some(CardStatus::Active), ..Default::default() }.to_filter();
+    let active_licenses = collection
+        .count_documents(with_conditions(&scope, active_filter), None)
+        .await? as i64;
+    let expiring_filter = with_conditions(&scope, doc! {
+        "is_activated": true,
+        "expires_at_str": { "$gt": &now_str, "$lte": &until_str },
+    });
+    let expiring_soon = collection.count_documents(expiring_filter.clone(), None).await? as i64;
+
+    // 每日生成/激活/过期数量
+    let mut daily: BTreeMap<String, DailyStats> = BTreeMap::new();
+    for offset in (0..days).rev() {
+        let date = (now - Duration::days(offset)).format("%Y-%m-%d").to_string();
+        daily.insert(date.clone(), DailyStats { date, ..Default::default() });
+    }
+    for (date, count) in daily_counts(&collection, &scope, "created_at_str", &since_str, &now_str).await? {
+        if let Some(entry) = daily.get_mut(&date) {
+            entry.generated = count;
+        }
+    }
+    for (date, count) in daily_counts(&collection, &scope, "activated_at_str", &since_str, &now_str).await? {
+        if let Some(entry) = daily.get_mut(&date) {
+            entry.activated = count;
+        }
+    }
+    for (date, count) in daily_expired(db, &scope, &since_str, &now_str, now).await? {
+        if let Some(entry) = daily.get_mut(&date) {
+            entry.expired = count;
+        }
+    }
+
+    // 激活率
+    let activation_by_batch = activation_rates(&collection, &scope, Bson::String("$batch_id".to_string()), top).await?;
+    let activation_by_plan = activation_rates(&collection, &scope, Bson::String("$duration_seconds".to_string()), top).await?;
+    let activation_by_creator = activation_rates(&collection, &scope, Bson::String("$created_by_username".to_string()), top).await?;
+
+    // 即将过期的卡密，按过期时间升序
+    let find_options = FindOptions::builder()
+        .sort(doc! { "expires_at_str": 1 })
+        .limit(100)
+        .build();
+    let upcoming_expirations: Vec<Card> = db
+        .collection::<Card>("cards")
+        .find(expiring_filter, find_options)
+        .await?
+        .try_collect()
+        .await?;
+
+    let top_resellers = top_resellers(&collection, &scope, top).await?;
+
+    Ok(StatsResponse {
+        total_cards,
+        activated_cards,
+        active_licenses,
+        expiring_soon,
+        daily: daily.into_values().collect(),
+        activation_by_batch,
+        activation_by_plan,
+        activation_by_creator,
+        upcoming_expirations,
+        top_resellers,
+    })
+}
+
+// 在范围过滤条件上追加额外条件
+fn with_conditions(scope: &Document, conditions: Document) -> Document {
+    let mut filter = scope.clone();
+    filter.extend(conditions);
+    filter
+}
+
+// 按日期（RFC3339 字符串的前10位）统计某个时间字段的数量
+async fn daily_counts(
+    collection: &Collection<Document>,
+    scope: &Document,
+    field: &str,
+    since: &str,
+    until: &str,
+) -> Result<Vec<(String, i64)>, mongodb::error::Error> {
+    let mut range = Document::new();
+    range.insert(field, doc! { "$gte": since, "$lte": until });
+
+    let pipeline = vec![
+        doc! { "$match": with_conditions(scope, range) },
+        doc! { "$group": {
+            "_id": { "$substrBytes": [format!("${}", field), 0, 10] },
+            "count": { "$sum": 1 },
+        }},
+    ];
+
+    let docs: Vec<Document> = collection.aggregate(pipeline, None).await?.try_collect().await?;
+    Ok(docs
+        .into_iter()
+        .filter_map(|d| {
+            let date = d.get_str("_id").ok()?.to_string();
+            let count = match d.get("count")? {
+                Bson::Int32(n) => *n as i64,
+                Bson::Int64(n) => *n,
+                _ => return None,
+            };
+            Some((date, count))
+        })
+        .collect())
+}
+
+// 按失效日期统计过期卡密，与卡密列表的过期状态一致：激活后到期，超过保质期、激活截止时间或固定到期时间仍未激活，
+// 以及次数用完的卡密
+async fn daily_expired(
+    db: &Database,
+    scope: &Document,
+    since: &str,
+    until: &str,
+    now: DateTime<Utc>,
+) -> Result<BTreeMap<String, i64>, mongodb::error::Error> {
+    let range = doc! { "$gte": since, "$lte": until };
+    let filter = with_conditions(scope, doc! { "$or": [
+        { "is_activated": true, "expires_at_str": range.clone() },
+        { "is_activated": false, "shelf_expires_at_str": range.clone() },
+        { "is_activated": false, "activate_before_str": range.clone() },
+        { "is_activated": false, "valid_until_str": range.clone() },
+        { "exhausted_at_str": range },
+    ]});
+    let cards: Vec<Card> = db.collection::<Card>("cards").find(filter, None).await?.try_collect().await?;
+    Ok(count_expired_by_date(&cards, now))
+}
+
+// 按失效时间的日期（UTC）统计数量，仍有效的卡密不计入
+fn count_expired_by_date(cards: &[Card], now: DateTime<Utc>) -> BTreeMap<String, i64> {
+    let mut counts = BTreeMap::new();
+    for expired_at in cards.iter().filter_map(|card| card.expired_at(now)) {
+        *counts.entry(expired_at.format("%Y-%m-%d").to_string()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// 按指定表达式分组统计激活率
+async fn activation_rates(
+    collection: &Collection<Document>,
+    scope: &Document,
+    group_by: Bson,
+    limit: i64,
+) -> Result<Vec<ActivationRate>, mongodb::error::Error> {
+    let pipeline = vec![
+        doc! { "$match": scope.clone() },
+        doc! { "$group": {
+            "_id": group_by,
+            "total": { "$sum": 1 },
+            "activated": { "$sum": { "$cond": ["$is_activated", 1, 0] } },
+        }},
+        doc! { "$sort": { "total": -1 } },
+        doc! { "$limit": limit },
+        doc! { "$project": {
+            "_id": 0,
+            "key": { "$toString": { "$ifNull": ["$_id", "-"] } },
+            "total": 1,
+            "activated": 1,
+        }},
+    ];
+
+    let docs: Vec<Document> = collection.aggregate(pipeline, None).await?.try_collect().await?;
+    let mut rates = Vec::with_capacity(docs.len());
+    for d in docs {
+        let mut rate: ActivationRate = bson::from_document(d)?;
+        if rate.total > 0 {
+            rate.rate = rate.activated as f64 / rate.total as f64;
+        }
+        rates.push(rate);
+    }
+    Ok(rates)
+}
+
+// 按创建者统计生成与激活数量，按激活数降序
+async fn top_resellers(
+    collection: &Collection<Document>,
+    scope: &Document,
+    limit: i64,
+) -> Result<Vec<ResellerStats>, mongodb::error::Error> {
+    let pipeline = vec![
+        doc! { "$match": scope.clone() },
+        doc! { "$group": {
+            "_id": "$created_by",
+            "username": { "$first": "$created_by_username" },
+            "generated": { "$sum": 1 },
+            "activated": { "$sum": { "$cond": ["$is_activated", 1, 0] } },
+        }},
+        doc! { "$sort": { "activated": -1, "generated": -1 } },
+        doc! { "$limit": limit },
+        doc! { "$project": {
+            "_id": 0,
+            "created_by": "$_id",
+            "username": { "$ifNull": ["$username", "-"] },
+            "generated": 1,
+            "activated": 1,
+        }},
+    ];
+
+    let docs: Vec<Document> = collection.aggregate(pipeline, None).await?.try_collect().await?;
+    docs.into_iter()
+        .map(|d| bson::from_document(d).map_err(mongodb::error::Error::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_card_past_shelf_life_counts_as_expired() {
+        let now = Utc::now();
+        let shelf_expires_at = now - Duration::days(2);
+        let mut shelved = Card::new("SHELVED000000000".to_string(), 86_400);
+        shelved.shelf_expires_at_str = Some(shelf_expires_at.to_rfc3339());
+        let unused = Card::new("UNUSED0000000000".to_string(), 86_400);
+
+        let counts = count_expired_by_date(&[shelved, unused], now);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&shelf_expires_at.format("%Y-%m-%d").to_string()], 1);
+    }
+}
@@ -5,26 +5,44 @@ use log::{info, warn, error};
 
 use crate::{
     auth::{generate_token, hash_password, require_admin, verify_password, AuthenticatedUser},
+    metrics::metrics,
     models::user::{CreateUserRequest, LoginRequest, LoginResponse, UpdateUserRequest, User, UserInfo},
+    openapi::TrashedUser,
     AppState,
 };
 
+#[allow(deprecated)]  // /current 仅为兼容旧版前端保留
 pub fn config() -> Scope {
-    web::scope("/api/users")
+    web::scope("/users")
         .route("/register", web::post().to(register_user))
         .route("/login", web::post().to(login))
         .route("/me", web::get().to(get_current_user))
-        .route("/current", web::get().to(get_current_user))
+        .route("/current", web::get().to(get_current_user_legacy))
         .route("", web::get().to(get_all_users))
+        .route("/trash", web::get().to(get_trash))
         .route("/{id}", web::get().to(get_user))
         .route("/{id}", web::put().to(update_user))
         .route("/{id}", web::delete().to(delete_user))
+        .route("/{id}/restore", web::post().to(restore_user))
 }
 
+#[utoipa::path(
+    post,
+    path = "/users/register",
+    tag = "users",
+    summary = "创建用户",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "创建的用户", body = UserInfo),
+        (status = 400, description = "用户名已存在"),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn register_user(
     state: web::Data<AppState>,
     req: web::Json<CreateUserRequest>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
 ) -> HttpResponse {
     info!("Attempting to register new user. Request params: username={}, role={:?}, has_email={}", 
         req.username, req.role, req.email.is_some());
@@ -56,19 +74,23 @@ async fn register_user(
 
     let email = req.email.as_ref().filter(|e| !e.is_empty()).cloned();
 
-    let user = User::new(
+    let new_user = User::new(
         req.username.clone(),
         password_hash,
         email,
         req.role.clone(),
     );
 
-    match collection.insert_one(&user, None).await {
+    match collection.insert_one(&new_user, None).await {
         Ok(result) => {
-            let mut created_user = user;
+            let mut created_user = new_user;
             created_user.id = Some(result.inserted_id.as_object_id().unwrap());
             let user_info = UserInfo::from(created_user);
             info!("User '{}' registered successfully", req.username);
+            state.audit.record(&user, "user.create", Some(user_info.id.clone()), Some(serde_json::json!({
+                "username": user_info.username,
+                "role": user_info.role,
+            })));
             HttpResponse::Created().json(user_info)
         }
         Err(e) => {
@@ -78,6 +100,18 @@ async fn register_user(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/users/login",
+    tag = "users",
+    summary = "登录",
+    description = "登录成功后返回 JWT，之后的请求通过 Authorization: Bearer <token> 认证；同一用户只有最近一次登录的令牌有效",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "登录成功", body = LoginResponse),
+        (status = 401, description = "用户名或密码错误"),
+    ),
+)]
 async fn login(
     state: web::Data<AppState>,
     req: web::Json<LoginRequest>,
@@ -85,15 +119,16 @@ async fn login(
     info!("Login attempt for user: {}", req.username);
     
     let collection = state.db.collection::<User>("users");
-    let filter = doc! { "username": &req.username };
+    let filter = doc! { "username": &req.username, "deleted_at_str": null };
 
     match collection.find_one(filter, None).await {
         Ok(Some(user)) => {
             match verify_password(&req.password, &user.password_hash) {
                 Ok(true) => {
                     // 使用新的 generate_token 函数
-                    match generate_token(&user, &state.db).await {
+                    match generate_token(&user, &state.db, &state.config.auth).await {
                         Ok(token) => {
+                            metrics().login(true);
                             let user_info = UserInfo::from(user);
                             let response = LoginResponse { token, user: user_info };
                             HttpResponse::Ok().json(response)
@@ -106,6 +141,7 @@ async fn login(
                 }
                 Ok(false) => {
                     warn!("Invalid password for user: {}", req.username);
+                    metrics().login(false);
                     HttpResponse::Unauthorized().body("Invalid username or password")
                 }
                 Err(e) => {
@@ -116,6 +152,7 @@ async fn login(
         }
         Ok(None) => {
             warn!("User not found: {}", req.username);
+            metrics().login(false);
             HttpResponse::Unauthorized().body("Invalid username or password")
         }
         Err(e) => {
@@ -125,10 +162,49 @@ async fn login(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/users/me",
+    tag = "users",
+    summary = "获取当前用户",
+    responses(
+        (status = 200, description = "当前登录的用户", body = AuthenticatedUser),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn get_current_user(user: AuthenticatedUser) -> HttpResponse {
     HttpResponse::Ok().json(user)
 }
 
+// 旧版前端使用的路径，与 /me 相同
+#[utoipa::path(
+    get,
+    path = "/users/current",
+    tag = "users",
+    summary = "获取当前用户（已废弃，请使用 /api/users/me）",
+    responses(
+        (status = 200, description = "当前登录的用户", body = AuthenticatedUser),
+        (status = 401, description = "未认证"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+#[deprecated]
+async fn get_current_user_legacy(user: AuthenticatedUser) -> HttpResponse {
+    get_current_user(user).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    summary = "获取用户列表（仅管理员）",
+    responses(
+        (status = 200, description = "未删除的用户", body = Vec<UserInfo>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn get_all_users(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -136,7 +212,7 @@ async fn get_all_users(
     match require_admin(user) {
         Ok(_) => {
             let collection = state.db.collection::<User>("users");
-            match collection.find(None, None).await {
+            match collection.find(doc! { "deleted_at_str": null }, None).await {
                 Ok(cursor) => {
                     match futures::stream::TryStreamExt::try_collect::<Vec<User>>(cursor).await {
                         Ok(users) => {
@@ -153,6 +229,20 @@ async fn get_all_users(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/users/{id}",
+    tag = "users",
+    summary = "获取用户（仅管理员）",
+    params(("id" = String, Path, description = "用户ID")),
+    responses(
+        (status = 200, description = "用户信息", body = UserInfo),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "用户不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn get_user(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -167,7 +257,7 @@ async fn get_user(
             };
 
             let collection = state.db.collection::<User>("users");
-            let filter = doc! { "_id": object_id };
+            let filter = doc! { "_id": object_id, "deleted_at_str": null };
 
             match collection.find_one(filter, None).await {
                 Ok(Some(user)) => {
@@ -182,6 +272,22 @@ async fn get_user(
     }
 }
 
+#[utoipa::path(
+    put,
+    path = "/users/{id}",
+    tag = "users",
+    summary = "更新用户（仅管理员）",
+    description = "只更新请求中提供的字段",
+    params(("id" = String, Path, description = "用户ID")),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "更新成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "用户不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn update_user(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -193,14 +299,14 @@ async fn update_user(
         user.username, user_id, req.password.is_some(), req.email.is_some(), req.role);
     
     match require_admin(user) {
-        Ok(_) => {
+        Ok(admin) => {
             let object_id = match ObjectId::parse_str(&user_id) {
                 Ok(oid) => oid,
                 Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
             };
 
             let collection = state.db.collection::<User>("users");
-            let filter = doc! { "_id": object_id };
+            let filter = doc! { "_id": object_id, "deleted_at_str": null };
 
             let now = Utc::now();
             let now_str = now.to_rfc3339();
@@ -238,6 +344,12 @@ async fn update_user(
                     if result.matched_count == 0 {
                         HttpResponse::NotFound().body("User not found")
                     } else {
+                        state.audit.record(&admin, "user.update", Some(user_id), Some(serde_json::json!({
+                            "username": req.username,
+                            "email": req.email,
+                            "role": req.role,
+                            "password_changed": req.password.is_some(),
+                        })));
                         HttpResponse::Ok().body("User updated successfully")
                     }
                 }
@@ -248,6 +360,21 @@ async fn update_user(
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/users/{id}",
+    tag = "users",
+    summary = "删除用户（仅管理员）",
+    description = "用户移入回收站，同时使其登录令牌失效",
+    params(("id" = String, Path, description = "用户ID")),
+    responses(
+        (status = 200, description = "删除成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "用户不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
 async fn delete_user(
     state: web::Data<AppState>,
     user: AuthenticatedUser,
@@ -257,20 +384,30 @@ async fn delete_user(
     info!("User '{}' attempting to delete user with id: {}", user.username, user_id);
     
     match require_admin(user) {
-        Ok(_) => {
+        Ok(admin) => {
             let object_id = match ObjectId::parse_str(&user_id) {
                 Ok(oid) => oid,
                 Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
             };
 
             let collection = state.db.collection::<User>("users");
-            let filter = doc! { "_id": object_id };
+            let filter = doc! { "_id": object_id, "deleted_at_str": null };
+            // 软删除：移入回收站并使当前令牌失效
+            let update = doc! {
+                "$set": {
+                    "deleted_at_str": Utc::now().to_rfc3339(),
+                    "deleted_by": &admin.username,
+                },
+                "$unset": { "last_token": "", "token_expires_at_str": "" },
+            };
 
-            match collection.delete_one(filter, None).await {
+            match collection.update_one(filter, update, None).await {
                 Ok(result) => {
-                    if result.deleted_count == 0 {
+                    if result.matched_count == 0 {
                         HttpResponse::NotFound().body("User not found")
                     } else {
+                        info!("User '{}' moved to trash by '{}'", user_id, admin.username);
+                        state.audit.record(&admin, "user.delete", Some(user_id), None);
                         HttpResponse::Ok().body("User deleted successfully")
                     }
                 }
@@ -279,4 +416,99 @@ async fn delete_user(
         }
         Err(e) => HttpResponse::from_error(e),
     }
-} 
\ No newline at end of file
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/trash",
+    tag = "users",
+    summary = "获取回收站中的用户（仅管理员）",
+    responses(
+        (status = 200, description = "已删除的用户", body = Vec<TrashedUser>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_trash(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    match require_admin(user) {
+        Ok(_) => {
+            let collection = state.db.collection::<User>("users");
+            match collection.find(doc! { "deleted_at_str": { "$ne": null } }, None).await {
+                Ok(cursor) => {
+                    match futures::stream::TryStreamExt::try_collect::<Vec<User>>(cursor).await {
+                        Ok(users) => {
+                            let trashed: Vec<serde_json::Value> = users
+                                .into_iter()
+                                .map(|u| {
+                                    let deleted_at_str = u.deleted_at_str.clone();
+                                    let deleted_by = u.deleted_by.clone();
+                                    serde_json::json!({
+                                        "user": UserInfo::from(u),
+                                        "deleted_at_str": deleted_at_str,
+                                        "deleted_by": deleted_by,
+                                    })
+                                })
+                                .collect();
+                            HttpResponse::Ok().json(trashed)
+                        }
+                        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(e) => HttpResponse::from_error(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/restore",
+    tag = "users",
+    summary = "从回收站恢复用户（仅管理员）",
+    params(("id" = String, Path, description = "用户ID")),
+    responses(
+        (status = 200, description = "恢复成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "回收站中不存在该用户"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn restore_user(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    info!("User '{}' attempting to restore user with id: {}", user.username, user_id);
+
+    match require_admin(user) {
+        Ok(admin) => {
+            let object_id = match ObjectId::parse_str(&user_id) {
+                Ok(oid) => oid,
+                Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+            };
+
+            let collection = state.db.collection::<User>("users");
+            let filter = doc! { "_id": object_id, "deleted_at_str": { "$ne": null } };
+            let update = doc! { "$unset": { "deleted_at_str": "", "deleted_by": "" } };
+
+            match collection.update_one(filter, update, None).await {
+                Ok(result) => {
+                    if result.matched_count == 0 {
+                        HttpResponse::NotFound().body("User not found in trash")
+                    } else {
+                        state.audit.record(&admin, "user.restore", Some(user_id), None);
+                        HttpResponse::Ok().body("User restored successfully")
+                    }
+                }
+                Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+            }
+        }
+        Err(e) => HttpResponse::from_error(e),
+    }
+}
@@ -0,0 +1,132 @@
+use actix_web::{web, HttpResponse};
+use log::{error, info};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    client_auth::{ClientJson, CODE_APP_INVALID},
+    client_ip::ClientIp,
+    envelope::{with_code, Envelope},
+    handlers::card::{verify, VerifyOutcome, VerifyRejection},
+    models::{Card, VerifyCardRequest},
+    AppState,
+};
+
+// 卡密验证相关的错误码
+pub const CODE_CARD_NOT_FOUND: &str = "CARD_NOT_FOUND";
+pub const CODE_CARD_EXPIRED: &str = "CARD_EXPIRED";
+pub const CODE_CARD_REVOKED: &str = "CARD_REVOKED";
+pub const CODE_CARD_USED_BY_OTHER: &str = "CARD_USED_BY_OTHER";
+pub const CODE_CARD_INVALID: &str = "CARD_INVALID";
+pub const CODE_CARD_CONFLICT: &str = "CARD_CONFLICT";
+pub const CODE_CARD_NOT_ACTIVATED: &str = "CARD_NOT_ACTIVATED";
+pub const CODE_IDENTIFIER_REQUIRED: &str = "IDENTIFIER_REQUIRED";
+pub const CODE_REBIND_UNAUTHORIZED: &str = "REBIND_UNAUTHORIZED";
+pub const CODE_REBIND_DISABLED: &str = "REBIND_DISABLED";
+pub const CODE_REBIND_LIMIT_EXCEEDED: &str = "REBIND_LIMIT_EXCEEDED";
+pub const CODE_REBIND_TIME_INSUFFICIENT: &str = "REBIND_TIME_INSUFFICIENT";
+pub const CODE_CARD_PAUSED: &str = "CARD_PAUSED";
+pub const CODE_CARD_NOT_PAUSED: &str = "CARD_NOT_PAUSED";
+pub const CODE_PAUSE_DISABLED: &str = "PAUSE_DISABLED";
+pub const CODE_CARD_EXHAUSTED: &str = "CARD_EXHAUSTED";
+pub const CODE_CARD_NOT_METERED: &str = "CARD_NOT_METERED";
+pub const CODE_CARD_NOT_YET_VALID: &str = "CARD_NOT_YET_VALID";
+pub const CODE_CARD_VALIDITY_ENDED: &str = "CARD_VALIDITY_ENDED";
+pub const CODE_ACTIVATION_CLOSED: &str = "ACTIVATION_CLOSED";
+
+// 配置 v2 中响应格式发生变化的路由，需在共用的 v1 路由之前注册以覆盖同路径的 v1 处理函数
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/cards/verify", web::post().to(verify_card));  // 验证卡密
+}
+
+// v2 验证卡密的结果，无论是否本次激活都返回相同的结构
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VerifyResult {
+    pub activated_now: bool,  // 是否由本次验证激活
+    pub card: Card,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_secret: Option<String>,  // 激活时签发的所有者密钥，只返回这一次，用于自助换绑
+}
+
+// 验证卡密处理函数（v2 统一响应格式）
+#[utoipa::path(
+    post,
+    path = "/api/v2/cards/verify",
+    tag = "cards",
+    summary = "验证卡密（v2）",
+    description = "与 v1 验证逻辑相同，响应统一为 Envelope 格式；失败时 code 为 CARD_NOT_FOUND、CARD_EXPIRED、CARD_REVOKED、CARD_USED_BY_OTHER、CARD_PAUSED、CARD_EXHAUSTED、CARD_NOT_YET_VALID、CARD_VALIDITY_ENDED、ACTIVATION_CLOSED、CARD_INVALID、CARD_CONFLICT 或 IDENTIFIER_REQUIRED，data 中包含卡密信息（如有）；签名校验失败或应用无效时 code 为 SIGNATURE_REQUIRED、SIGNATURE_INVALID、APP_INVALID、TIMESTAMP_INVALID 或 NONCE_REPLAYED",
+    request_body = VerifyCardRequest,
+    params(
+        ("X-App-Key" = Option<String>, Header, description = "应用标识，签名请求必填"),
+        ("X-Timestamp" = Option<String>, Header, description = "Unix 时间戳（秒）"),
+        ("X-Nonce" = Option<String>, Header, description = "随机字符串，每个请求不同"),
+        ("X-Signature" = Option<String>, Header, description = "请求签名"),
+    ),
+    responses(
+        (status = 200, description = "验证通过", body = Envelope<VerifyResult>),
+        (status = 400, description = "卡密已过期、已撤销、已暂停、次数已用完、不在使用时间窗口内、已被其他用户使用、数据无效或缺少设备标识", body = Envelope<Card>),
+        (status = 401, description = "签名校验失败或应用无效", body = Envelope<Card>),
+        (status = 404, description = "卡密不存在", body = Envelope<Card>),
+        (status = 409, description = "并发请求抢先激活了卡密", body = Envelope<Card>),
+    ),
+)]
+async fn verify_card(
+    state: web::Data<AppState>,
+    req: ClientJson<VerifyCardRequest>,
+    client_ip: ClientIp,
+) -> HttpResponse {
+    info!("Verifying card (v2). Request params: card_number={}, app={:?}", req.card_number, req.app.as_ref().map(|app| &app.name));
+
+    let ClientJson { app, body: req } = req;
+    match verify(&state, &req, app, client_ip, 1, false).await {
+        Ok(VerifyOutcome::Verified(card)) => HttpResponse::Ok().json(VerifyResult { activated_now: false, card, owner_secret: None }),
+        Ok(VerifyOutcome::Activated(card, owner_secret)) => with_code(
+            HttpResponse::Ok().json(VerifyResult { activated_now: true, card, owner_secret }),
+            "OK",
+            "卡密激活成功".to_string(),
+        ),
+        Ok(VerifyOutcome::Rejected(reason, card)) => {
+            let (code, message) = match reason {
+                VerifyRejection::Revoked => (CODE_CARD_REVOKED, "此卡密已被撤销"),
+                VerifyRejection::Expired => (CODE_CARD_EXPIRED, "此卡密已过期"),
+                VerifyRejection::UsedByOther => (CODE_CARD_USED_BY_OTHER, "此卡密已被其他用户使用"),
+                VerifyRejection::Paused => (CODE_CARD_PAUSED, "此卡密已暂停"),
+                VerifyRejection::Exhausted => (CODE_CARD_EXHAUSTED, "此卡密次数已用完"),
+                VerifyRejection::NotYetValid => (CODE_CARD_NOT_YET_VALID, "此卡密尚未到生效时间"),
+                VerifyRejection::ValidityEnded => (CODE_CARD_VALIDITY_ENDED, "此卡密已超过固定到期时间"),
+                VerifyRejection::ActivationClosed => (CODE_ACTIVATION_CLOSED, "此卡密已超过激活截止时间"),
+            };
+            with_code(HttpResponse::BadRequest().json(card), code, message.to_string())
+        }
+        Ok(VerifyOutcome::NotFound) => {
+            with_code(HttpResponse::NotFound().finish(), CODE_CARD_NOT_FOUND, "卡密不存在".to_string())
+        }
+        Ok(VerifyOutcome::InvalidExpiry) => {
+            with_code(HttpResponse::BadRequest().finish(), CODE_CARD_INVALID, "无效的过期日期".to_string())
+        }
+        Ok(VerifyOutcome::ActivationConflict) => with_code(
+            HttpResponse::Conflict().finish(),
+            CODE_CARD_CONFLICT,
+            "卡密无法激活，可能已被其他请求激活".to_string(),
+        ),
+        Ok(VerifyOutcome::AppInvalid) => with_code(
+            HttpResponse::Unauthorized().finish(),
+            CODE_APP_INVALID,
+            "应用不存在或已停用".to_string(),
+        ),
+        Ok(VerifyOutcome::IdentifierRequired) => with_code(
+            HttpResponse::BadRequest().finish(),
+            CODE_IDENTIFIER_REQUIRED,
+            "缺少设备标识 user_identifier".to_string(),
+        ),
+        Ok(VerifyOutcome::NotMetered) => with_code(
+            HttpResponse::BadRequest().finish(),
+            CODE_CARD_NOT_METERED,
+            "此卡密不按次数计费".to_string(),
+        ),
+        Err(e) => {
+            error!("Error verifying card '{}': {}", req.card_number, e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
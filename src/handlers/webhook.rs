@@ -0,0 +1,427 @@
+use actix_web::{web, HttpResponse, Scope};
+use chrono::Utc;
+use futures::stream::TryStreamExt;
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+
+use crate::{
+    auth::{require_admin, AuthenticatedUser},
+    models::webhook::{
+        CreateWebhookRequest, DeliveryQuery, DeliveryStatus, UpdateWebhookRequest, Webhook,
+        WebhookDelivery, WebhookInfo, EVENT_PING, SUPPORTED_EVENTS,
+    },
+    openapi::{SecretRotated, WebhookCreated},
+    webhook::{enqueue, generate_secret},
+    AppState,
+};
+
+// 配置 Webhook 相关的路由（仅管理员）
+pub fn config() -> Scope {
+    web::scope("/webhooks")
+        .route("", web::post().to(create_webhook))                                  // 创建 Webhook
+        .route("", web::get().to(get_all_webhooks))                                 // 获取所有 Webhook
+        .route("/deliveries/{id}/redeliver", web::post().to(redeliver))             // 手动重新投递
+        .route("/{id}", web::get().to(get_webhook))                                 // 获取单个 Webhook
+        .route("/{id}", web::put().to(update_webhook))                              // 更新 Webhook
+        .route("/{id}", web::delete().to(delete_webhook))                           // 删除 Webhook
+        .route("/{id}/deliveries", web::get().to(get_deliveries))                   // 获取投递记录
+        .route("/{id}/test", web::post().to(test_webhook))                          // 发送测试事件
+}
+
+// 校验 URL 和事件类型
+fn validate_webhook(url: Option<&String>, events: Option<&Vec<String>>) -> Result<(), String> {
+    if let Some(url) = url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err("URL must start with http:// or https://".to_string());
+        }
+    }
+    if let Some(events) = events {
+        if events.is_empty() {
+            return Err("At least one event is required".to_string());
+        }
+        if let Some(unknown) = events.iter().find(|e| *e != "*" && !SUPPORTED_EVENTS.contains(&e.as_str())) {
+            return Err(format!("Unsupported event: {}", unknown));
+        }
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    summary = "创建 Webhook",
+    description = "未提供 secret 时自动生成，签名密钥只在创建时返回一次",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "创建的 Webhook 和签名密钥", body = WebhookCreated),
+        (status = 400, description = "URL 或事件类型无效"),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn create_webhook(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    req: web::Json<CreateWebhookRequest>,
+) -> HttpResponse {
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' is creating webhook. Request params: url={}, events={:?}", user.username, req.url, req.events);
+
+    if let Err(msg) = validate_webhook(Some(&req.url), Some(&req.events)) {
+        warn!("Invalid webhook request: {}", msg);
+        return HttpResponse::BadRequest().body(msg);
+    }
+
+    let req = req.into_inner();
+    let secret = req.secret.filter(|s| !s.is_empty()).unwrap_or_else(generate_secret);
+    let mut webhook = Webhook::new(req.url, secret.clone(), req.events, req.description, Some(user.user_id.clone()));
+
+    match state.db.collection::<Webhook>("webhooks").insert_one(&webhook, None).await {
+        Ok(result) => {
+            webhook.id = result.inserted_id.as_object_id();
+            info!("Webhook '{}' created by user '{}'", webhook.url, user.username);
+            state.audit.record(&user, "webhook.create", webhook.id.map(|id| id.to_hex()), Some(serde_json::json!({
+                "url": webhook.url,
+                "events": webhook.events,
+            })));
+            // 仅在创建时返回一次签名密钥
+            HttpResponse::Created().json(serde_json::json!({
+                "webhook": WebhookInfo::from(webhook),
+                "secret": secret,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to create webhook: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    summary = "获取所有 Webhook",
+    responses(
+        (status = 200, description = "Webhook 列表", body = Vec<WebhookInfo>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_all_webhooks(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    match state.db.collection::<Webhook>("webhooks").find(None, None).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<Webhook>>().await {
+            Ok(webhooks) => {
+                let infos: Vec<WebhookInfo> = webhooks.into_iter().map(WebhookInfo::from).collect();
+                HttpResponse::Ok().json(infos)
+            }
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    summary = "获取单个 Webhook",
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "Webhook 信息", body = WebhookInfo),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "Webhook 不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_webhook(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    let object_id = match ObjectId::parse_str(path.into_inner()) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    match state.db.collection::<Webhook>("webhooks").find_one(doc! { "_id": object_id }, None).await {
+        Ok(Some(webhook)) => HttpResponse::Ok().json(WebhookInfo::from(webhook)),
+        Ok(None) => HttpResponse::NotFound().body("Webhook not found"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    summary = "更新 Webhook",
+    description = "rotate_secret 为 true 时重新生成签名密钥并在响应中返回",
+    params(("id" = String, Path, description = "Webhook ID")),
+    request_body = UpdateWebhookRequest,
+    responses(
+        (status = 200, description = "更新成功；重新生成密钥时返回新密钥", body = SecretRotated),
+        (status = 400, description = "ID 格式、URL 或事件类型无效"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "Webhook 不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn update_webhook(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    req: web::Json<UpdateWebhookRequest>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' attempting to update webhook {}. Request params: url={:?}, events={:?}, enabled={:?}, rotate_secret={}",
+        user.username, id, req.url, req.events, req.enabled, req.rotate_secret);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    if let Err(msg) = validate_webhook(req.url.as_ref(), req.events.as_ref()) {
+        warn!("Invalid webhook update: {}", msg);
+        return HttpResponse::BadRequest().body(msg);
+    }
+
+    let mut update_doc = doc! { "updated_at_str": Utc::now().to_rfc3339() };
+    if let Some(url) = &req.url {
+        update_doc.insert("url", url);
+    }
+    if let Some(events) = &req.events {
+        update_doc.insert("events", events);
+    }
+    if let Some(enabled) = req.enabled {
+        update_doc.insert("enabled", enabled);
+    }
+    if let Some(description) = &req.description {
+        update_doc.insert("description", description);
+    }
+    let new_secret = if req.rotate_secret {
+        let secret = generate_secret();
+        update_doc.insert("secret", &secret);
+        Some(secret)
+    } else {
+        None
+    };
+
+    let collection = state.db.collection::<Webhook>("webhooks");
+    match collection.update_one(doc! { "_id": object_id }, doc! { "$set": update_doc }, None).await {
+        Ok(result) if result.matched_count == 0 => HttpResponse::NotFound().body("Webhook not found"),
+        Ok(_) => {
+            state.audit.record(&user, "webhook.update", Some(id), Some(serde_json::json!({
+                "url": req.url,
+                "events": req.events,
+                "enabled": req.enabled,
+                "rotate_secret": req.rotate_secret,
+            })));
+            match new_secret {
+                Some(secret) => HttpResponse::Ok().json(serde_json::json!({ "secret": secret })),
+                None => HttpResponse::Ok().body("Webhook updated successfully"),
+            }
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    summary = "删除 Webhook",
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 200, description = "删除成功"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "Webhook 不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn delete_webhook(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' attempting to delete webhook {}", user.username, id);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    match state.db.collection::<Webhook>("webhooks").delete_one(doc! { "_id": object_id }, None).await {
+        Ok(result) if result.deleted_count == 0 => HttpResponse::NotFound().body("Webhook not found"),
+        Ok(_) => {
+            state.audit.record(&user, "webhook.delete", Some(id), None);
+            HttpResponse::Ok().body("Webhook deleted successfully")
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// 获取某个 Webhook 的投递记录，按创建时间倒序
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    summary = "获取投递记录",
+    params(("id" = String, Path, description = "Webhook ID"), DeliveryQuery),
+    responses(
+        (status = 200, description = "投递记录，按创建时间倒序", body = Vec<WebhookDelivery>),
+        (status = 401, description = "未认证或不是管理员"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn get_deliveries(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    query: web::Query<DeliveryQuery>,
+) -> HttpResponse {
+    if let Err(e) = require_admin(user) {
+        return HttpResponse::from_error(e);
+    }
+
+    let mut filter = doc! { "webhook_id": path.into_inner() };
+    if let Some(status) = &query.status {
+        filter.insert("status", bson::to_bson(status).unwrap());
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at_str": -1 })
+        .limit(query.limit.unwrap_or(100).clamp(1, 500))
+        .build();
+
+    match state.db.collection::<WebhookDelivery>("webhook_deliveries").find(filter, options).await {
+        Ok(cursor) => match cursor.try_collect::<Vec<WebhookDelivery>>().await {
+            Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// 将投递记录重置为待投递状态，由后台任务立即重新发送
+#[utoipa::path(
+    post,
+    path = "/webhooks/deliveries/{id}/redeliver",
+    tag = "webhooks",
+    summary = "重新投递",
+    params(("id" = String, Path, description = "投递记录ID")),
+    responses(
+        (status = 202, description = "已加入投递队列"),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "投递记录不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn redeliver(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+    info!("User '{}' requested redelivery of {}", user.username, id);
+
+    let object_id = match ObjectId::parse_str(&id) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    let now = Utc::now().to_rfc3339();
+    let update = doc! {
+        "$set": {
+            "status": bson::to_bson(&DeliveryStatus::Pending).unwrap(),
+            "attempts": 0,
+            "next_attempt_at_str": &now,
+            "updated_at_str": &now,
+        }
+    };
+
+    match state.db.collection::<WebhookDelivery>("webhook_deliveries").update_one(doc! { "_id": object_id }, update, None).await {
+        Ok(result) if result.matched_count == 0 => HttpResponse::NotFound().body("Delivery not found"),
+        Ok(_) => HttpResponse::Accepted().body("Delivery queued for redelivery"),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+// 向 Webhook 发送一条测试事件
+#[utoipa::path(
+    post,
+    path = "/webhooks/{id}/test",
+    tag = "webhooks",
+    summary = "发送测试事件",
+    description = "向 Webhook 投递一条 webhook.ping 事件",
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 202, description = "已加入投递队列的测试事件", body = WebhookDelivery),
+        (status = 400, description = "ID 格式错误"),
+        (status = 401, description = "未认证或不是管理员"),
+        (status = 404, description = "Webhook 不存在"),
+    ),
+    security(("bearer_auth" = []), ("api_key" = [])),
+)]
+async fn test_webhook(
+    state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let user = match require_admin(user) {
+        Ok(user) => user,
+        Err(e) => return HttpResponse::from_error(e),
+    };
+
+    let object_id = match ObjectId::parse_str(path.into_inner()) {
+        Ok(oid) => oid,
+        Err(_) => return HttpResponse::BadRequest().body("Invalid ID format"),
+    };
+
+    let webhook = match state.db.collection::<Webhook>("webhooks").find_one(doc! { "_id": object_id }, None).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => return HttpResponse::NotFound().body("Webhook not found"),
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let data = serde_json::json!({ "triggered_by": user.username });
+    match enqueue(&state.db, &webhook, EVENT_PING, data).await {
+        Ok(delivery) => HttpResponse::Accepted().json(delivery),
+        Err(e) => {
+            error!("Failed to enqueue test delivery: {}", e);
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
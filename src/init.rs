@@ -1,20 +1,60 @@
 // use chrono::Utc;
-use log::info;
+use log::{info, warn};
 use mongodb::bson::doc;
-use mongodb::Database;
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Database};
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::auth::hash_password;
+use crate::config::{AdminConfig, DatabaseConfig};
+use crate::metrics::MongoMetrics;
 use crate::models::user::{User, UserRole};
 
-pub async fn init_admin_user(db: &Database) -> Result<(), mongodb::error::Error> {
+// 连接重试的初始间隔和最大间隔
+const CONNECT_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const CONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+// 未在连接字符串中指定时使用的服务器选择超时
+const SERVER_SELECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 连接 MongoDB 并确认可用，失败时按指数退避重试
+pub async fn connect_database(config: &DatabaseConfig) -> Result<Database, mongodb::error::Error> {
+    let mut client_options = ClientOptions::parse(&config.uri).await?;
+    // 记录每条命令的耗时
+    client_options.command_event_handler = Some(Arc::new(MongoMetrics));
+    if client_options.server_selection_timeout.is_none() {
+        client_options.server_selection_timeout = Some(SERVER_SELECTION_TIMEOUT);
+    }
+    let db = Client::with_options(client_options)?.database(&config.name);
+
+    let mut backoff = CONNECT_BACKOFF_INITIAL;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match db.run_command(doc! { "ping": 1 }, None).await {
+            Ok(_) => {
+                info!("Connected to MongoDB database '{}'", config.name);
+                return Ok(db);
+            }
+            Err(e) if attempt <= config.connect_retries => {
+                warn!("MongoDB connection attempt {} failed: {}; retrying in {}s", attempt, e, backoff.as_secs());
+                actix_web::rt::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(CONNECT_BACKOFF_MAX);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub async fn init_admin_user(db: &Database, config: &AdminConfig) -> Result<(), mongodb::error::Error> {
     let collection = db.collection::<User>("users");
     
-    // 从环境变量获取管理员用户名和密码
-    let admin_username = std::env::var("ADMIN_USERNAME").unwrap_or_else(|_| "admin".to_string());
-    let admin_password = std::env::var("ADMIN_PASSWORD").unwrap_or_else(|_| "admin123".to_string());
+    // 从配置获取管理员用户名和密码
+    let admin_username = &config.username;
+    let admin_password = &config.password;
     
     // 检查管理员用户是否存在
-    let filter = doc! { "username": &admin_username };
+    let filter = doc! { "username": admin_username };
     match collection.find_one(filter, None).await? {
         Some(_) => {
             info!("Admin user '{}' already exists", admin_username);
@@ -22,7 +62,7 @@ pub async fn init_admin_user(db: &Database) -> Result<(), mongodb::error::Error>
         }
         None => {
             // 创建新的管理员用户，使用 UserRole::Admin 而不是 "ADMIN"
-            let password_hash = hash_password(&admin_password).expect("Failed to hash password");
+            let password_hash = hash_password(admin_password).expect("Failed to hash password");
             
             let admin = User::new(
                 admin_username.clone(),
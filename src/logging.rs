@@ -0,0 +1,165 @@
+use log::LevelFilter;
+use log4rs::{
+    append::console::ConsoleAppender,
+    append::rolling_file::{
+        policy::compound::{
+            roll::{delete::DeleteRoller, fixed_window::FixedWindowRoller, Roll},
+            trigger::size::SizeTrigger,
+            CompoundPolicy,
+        },
+        RollingFileAppender,
+    },
+    config::{Appender, Config, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
+    Handle,
+};
+use std::cell::RefCell;
+use std::fs::{self, create_dir_all};
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::config::{LogConfig, LogFormat};
+
+// 文本格式的日志模板，包含请求ID（非请求内的日志显示为 -）
+const TEXT_PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S)} {l} [{X(request_id)(-)}] - {m}\n";
+
+// 日志系统句柄，保留配置以便定时任务轮转日志文件后重新打开
+pub struct Logger {
+    handle: Handle,
+    config: LogConfig,
+}
+
+// 根据日志配置初始化日志系统：
+// 日志文件超过 file_size 时轮转为 card-platform.log.1 ... card-platform.log.{file_count-1}，超出数量的文件被删除
+pub fn init(config: &LogConfig) -> io::Result<Logger> {
+    // 确保日志目录存在
+    if let Some(log_dir) = Path::new(&config.file).parent() {
+        create_dir_all(log_dir)?;
+    }
+
+    let handle = log4rs::init_config(build_config(config)?).map_err(io::Error::other)?;
+    Ok(Logger { handle, config: config.clone() })
+}
+
+// 按文件数量选择轮转方式：保留归档文件时依次重命名，否则直接删除
+fn roller(config: &LogConfig) -> io::Result<Box<dyn Roll>> {
+    if config.file_count > 1 {
+        let pattern = format!("{}.{{}}", config.file);
+        let roller = FixedWindowRoller::builder().base(1).build(&pattern, config.file_count - 1).map_err(io::Error::other)?;
+        Ok(Box::new(roller))
+    } else {
+        Ok(Box::new(DeleteRoller::new()))
+    }
+}
+
+// 创建日志配置：写入时超过大小上限立即轮转
+fn build_config(config: &LogConfig) -> io::Result<Config> {
+    let level = config.level.parse::<LevelFilter>().unwrap_or(LevelFilter::Info);
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(config.file_size)), roller(config)?);
+    let file_appender = RollingFileAppender::builder()
+        .encoder(encoder(config.format))
+        .build(&config.file, Box::new(policy))?;
+
+    let mut builder = Config::builder().appender(Appender::builder().build("file", Box::new(file_appender)));
+    let mut root = Root::builder().appender("file");
+
+    // 容器环境下同时输出到标准输出
+    if config.stdout {
+        let console = ConsoleAppender::builder().encoder(encoder(config.format)).build();
+        builder = builder.appender(Appender::builder().build("stdout", Box::new(console)));
+        root = root.appender("stdout");
+    }
+
+    builder.build(root.build(level)).map_err(io::Error::other)
+}
+
+impl Logger {
+    // 检查日志文件大小并在超过上限时轮转，作为写入时轮转的补充（如写入时轮转失败后文件持续增大）；
+    // 轮转后重新加载配置，使追加器打开新的日志文件
+    pub fn rotate_if_needed(&self) -> io::Result<bool> {
+        let path = Path::new(&self.config.file);
+        let size = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if size < self.config.file_size {
+            return Ok(false);
+        }
+
+        roller(&self.config)?.roll(path).map_err(io::Error::other)?;
+        self.handle.set_config(build_config(&self.config)?);
+        Ok(true)
+    }
+}
+
+// JSON 格式每行一个对象，请求上下文字段位于 mdc 中
+fn encoder(format: LogFormat) -> Box<dyn Encode> {
+    match format {
+        LogFormat::Text => Box::new(PatternEncoder::new(TEXT_PATTERN)),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    }
+}
+
+// 请求级日志上下文（请求ID、用户、客户端IP等），在请求处理期间写入 MDC
+#[derive(Clone, Default)]
+pub struct LogContext(Rc<RefCell<LogContextInner>>);
+
+#[derive(Default)]
+struct LogContextInner {
+    fields: Vec<(&'static str, String)>,
+    active: bool,  // 是否正在 poll 中，是则新字段立即写入 MDC
+}
+
+impl LogContext {
+    // 添加或覆盖字段
+    pub fn insert(&self, key: &'static str, value: impl Into<String>) {
+        let value = value.into();
+        let mut inner = self.0.borrow_mut();
+        if inner.active {
+            log_mdc::insert(key, value.as_str());
+        }
+        match inner.fields.iter_mut().find(|(k, _)| *k == key) {
+            Some(field) => field.1 = value,
+            None => inner.fields.push((key, value)),
+        }
+    }
+
+    fn enter(&self) {
+        let mut inner = self.0.borrow_mut();
+        inner.active = true;
+        for (key, value) in inner.fields.iter() {
+            log_mdc::insert(*key, value.as_str());
+        }
+    }
+
+    fn exit(&self) {
+        self.0.borrow_mut().active = false;
+        log_mdc::clear();
+    }
+
+    // 包装请求处理 future：同一线程上会交替处理多个请求，因此每次 poll 前写入上下文、poll 后清除
+    pub fn scope<F: Future>(self, future: F) -> LogContextFuture<F> {
+        LogContextFuture { context: self, future: Box::pin(future) }
+    }
+}
+
+pub struct LogContextFuture<F> {
+    context: LogContext,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for LogContextFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.context.enter();
+        let result = self.future.as_mut().poll(cx);
+        self.context.exit();
+        result
+    }
+}
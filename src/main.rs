@@ -1,113 +1,247 @@
 // 导入模块
+mod api_key;    // API 密钥认证模块
+mod audit;      // 审计日志模块
 mod auth;       // 认证相关模块
+mod cli;        // 命令行子命令模块
+mod client_auth; // 客户端请求签名模块
+mod client_ip;  // 客户端IP解析模块
+mod config;     // 配置加载模块
+mod envelope;   // v2 统一响应格式模块
 mod errors;     // 错误处理模块
 mod handlers;   // 请求处理器模块
 mod init;       // 初始化模块
+mod logging;    // 日志系统模块
+mod metrics;    // Prometheus 指标模块
+mod migrations; // 数据库迁移模块
 mod models;     // 数据模型模块
+mod openapi;    // OpenAPI 文档模块
 mod middleware; // 自定义中间件模块
+mod scheduler;  // 定时任务模块
+mod shutdown;   // 停机协调模块
+mod webhook;    // Webhook 投递模块
 
 use actix_cors::Cors;
 use actix_files as fs;
-use actix_web::{middleware as actix_middleware, web, App, HttpServer};
+use actix_web::{web, App, HttpServer};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::Config;
 use dotenv::dotenv;
-use mongodb::{Client, Database};
-use std::env;
-use std::fs::create_dir_all;
-use log::{info, LevelFilter};
-use log4rs::{
-    append::file::FileAppender,
-    config::{Appender, Config, Root},
-    encode::pattern::PatternEncoder,
-};
-use middleware::ClientIpMiddleware;
-
-// 应用状态结构体，包含数据库连接
+use mongodb::Database;
+use std::sync::Arc;
+use log::info;
+use audit::AuditLogger;
+use client_auth::ResponseSigning;
+use envelope::EnvelopeMiddleware;
+use logging::Logger;
+use middleware::RequestContextMiddleware;
+use scheduler::Scheduler;
+use shutdown::Shutdown;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// 应用状态结构体，包含数据库连接、配置、任务调度器和审计日志记录器
 pub struct AppState {
     pub db: Database,
+    pub config: Arc<Config>,
+    pub scheduler: Scheduler,
+    pub shutdown: Shutdown,
+    pub audit: AuditLogger,
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    // 解析命令行参数，未指定子命令时启动 HTTP 服务
+    let cli = Cli::parse();
+
     // 加载环境变量
     dotenv().ok();
+
+    // 加载并校验配置，配置有误时拒绝启动
+    let config = Arc::new(Config::load().map_err(std::io::Error::other)?);
     
     // 初始化日志系统
-    let log_level = env::var("LOG_LEVEL")
-        .unwrap_or_else(|_| "info".to_string())
-        .parse::<LevelFilter>()
-        .unwrap_or(LevelFilter::Info);
-    
-    let log_file = env::var("LOG_FILE").unwrap_or_else(|_| "./logs/card-platform.log".to_string());
+    let logger = Arc::new(logging::init(&config.log)?);
 
-    // 确保日志目录存在
-    if let Some(log_dir) = std::path::Path::new(&log_file).parent() {
-        create_dir_all(log_dir)?;
+    let command = cli.command.unwrap_or(Command::Serve);
+    if matches!(command, Command::Serve) {
+        info!("Starting card platform service in {:?} mode...", config.environment);
     }
 
-    // 创建文件追加器
-    let file_appender = FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} {l} - {m}\n")))
-        .build(log_file)
-        .unwrap();
-
-    // 创建日志配置
-    let config = Config::builder()
-        .appender(Appender::builder().build("file", Box::new(file_appender)))
-        .build(Root::builder().appender("file").build(log_level))
-        .unwrap();
-
-    // 初始化日志系统
-    log4rs::init_config(config).unwrap();
-
-    info!("Starting card platform service...");
+    // 连接到 MongoDB 数据库，连接失败时按指数退避重试
+    let db = init::connect_database(&config.database).await.map_err(|e| {
+        log::error!("Failed to connect to MongoDB: {}", e);
+        std::io::Error::other(e)
+    })?;
+
+    match command {
+        Command::Serve => serve(db, config, logger).await,
+        // 管理命令与 HTTP 服务共用配置和数据库连接，失败时以非零状态退出
+        command => cli::run(command, &db, &config).await.map_err(|e| {
+            log::error!("Command failed: {}", e);
+            std::io::Error::other(e.to_string())
+        }),
+    }
+}
 
-    // 从环境变量获取数据库配置
-    let mongodb_uri = env::var("MONGODB_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
-    let database_name = env::var("DATABASE_NAME").unwrap_or_else(|_| "card_platform".to_string());
+// 启动 HTTP 服务及后台任务，直到收到停止信号
+async fn serve(db: Database, config: Arc<Config>, logger: Arc<Logger>) -> std::io::Result<()> {
+    // 执行未完成的数据库迁移
+    let applied = migrations::run_pending(&db).await.map_err(|e| {
+        log::error!("Failed to apply migrations: {}", e);
+        std::io::Error::other(e)
+    })?;
+    if !applied.is_empty() {
+        info!("Applied {} migrations: {}", applied.len(), applied.join(", "));
+    }
 
-    // 连接到 MongoDB 数据库
-    let client = Client::with_uri_str(&mongodb_uri)
-        .await
-        .expect("Failed to connect to MongoDB");
-    let db = client.database(&database_name);
+    // 上次运行中未结束的批量任务已无法继续，标记为失败
+    match handlers::bulk::fail_interrupted_jobs(&db).await {
+        Ok(0) => {}
+        Ok(count) => log::warn!("Marked {} bulk jobs interrupted by the previous run as failed", count),
+        Err(e) => log::error!("Failed to recover interrupted bulk jobs: {}", e),
+    }
 
     // 初始化管理员用户
-    init::init_admin_user(&db)
+    init::init_admin_user(&db, &config.admin)
         .await
         .expect("Failed to initialize admin user");
 
-    // 创建应用状态
-    let app_state = web::Data::new(AppState { db });
+    // 后台任务共享的停机协调器
+    let shutdown = Shutdown::new();
+
+    // 启动 Webhook 后台投递任务
+    webhook::spawn_worker(db.clone(), shutdown.clone());
 
-    // 从环境变量获取服务器配置
-    let host = env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port = env::var("SERVER_PORT").unwrap_or_else(|_| "5005".to_string())
-        .parse::<u16>()
-        .expect("SERVER_PORT must be a number");
-    let static_files_path = env::var("STATIC_FILES_PATH").unwrap_or_else(|_| "./static".to_string());
+    // 启动审计日志后台写入任务
+    let audit = AuditLogger::new(db.clone());
+    audit.spawn_writer(shutdown.clone());
+
+    // 启动定时任务
+    let scheduler = Scheduler::new(db.clone(), config.clone(), logger, shutdown.clone());
+    scheduler.start();
+
+    // 服务器配置
+    let host = config.server.host.clone();
+    let port = config.server.port;
+    let static_files_path = config.server.static_files_path.clone();
+    let shutdown_timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+
+    // 创建应用状态
+    let app_state = web::Data::new(AppState {
+        db: db.clone(),
+        config,
+        scheduler,
+        shutdown: shutdown.clone(),
+        audit: audit.clone(),
+    });
 
     println!("Server running at http://{}:{}", host, port);
 
-    // 启动 HTTP 服务器
-    HttpServer::new(move || {
+    // 启动 HTTP 服务器，停止信号由下面的任务处理
+    let server = HttpServer::new(move || {
         // 配置 CORS
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
             .allow_any_header()
+            .expose_any_header()  // 允许前端读取 X-Request-Id 等响应头
             .max_age(3600);
 
         // 配置应用
         App::new()
-            .wrap(actix_middleware::Logger::default())  // 启用日志中间件
             .wrap(cors)                           // 启用 CORS 中间件
-            .wrap(ClientIpMiddleware)             // 添加客户端IP中间件
+            .wrap(RequestContextMiddleware)       // 分配请求ID、记录客户端IP并统计请求耗时
             .app_data(app_state.clone())          // 注入应用状态
-            .service(handlers::card::config())    // 注册卡密相关路由
-            .service(handlers::user::config())    // 注册用户相关路由
+            .service(handlers::docs::config())    // 注册 OpenAPI 文档和 Swagger UI 路由，需在 /api 作用域之前注册
+            .service(
+                web::scope("/api/v2")
+                    .wrap(EnvelopeMiddleware)          // v2 使用统一响应格式
+                    .wrap(ResponseSigning)             // 对签名请求的响应签名，需在统一响应格式之外
+                    .configure(handlers::v2::config)   // v2 中响应格式变化的路由，覆盖同路径的 v1 路由
+                    .configure(handlers::routes),
+            )
+            .service(
+                web::scope("/api/v1")
+                    .wrap(handlers::v1_deprecation_headers())
+                    .wrap(ResponseSigning)
+                    .configure(handlers::routes),
+            )
+            .service(
+                web::scope("/api")                   // 未带版本的旧路径，与 v1 相同
+                    .wrap(handlers::v1_deprecation_headers())
+                    .wrap(ResponseSigning)
+                    .configure(handlers::routes),
+            )
+            .service(handlers::metrics::config()) // 注册 Prometheus 指标路由
+            .configure(handlers::health::config)  // 注册健康检查路由
             .service(fs::Files::new("/", &static_files_path).index_file("index.html"))  // 静态文件服务
     })
     .bind(format!("{}:{}", host, port))?
-    .run()
-    .await
+    .shutdown_timeout(shutdown_timeout.as_secs())
+    .disable_signals()
+    .run();
+
+    // 收到 SIGTERM/SIGINT 后先通知后台任务停止开始新的工作（进行中的批量操作据此标记为中断），
+    // 再停止接受新连接，并在超时时间内等待进行中的请求完成
+    // 整个停机过程共用一个截止时间，从收到停止信号时开始计算，各阶段只使用剩余的时间
+    let server_handle = server.handle();
+    let signal_shutdown = shutdown.clone();
+    let signal_deadline = Rc::new(Cell::new(None));
+    let deadline_slot = signal_deadline.clone();
+    actix_web::rt::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown signal received, stopping HTTP server (timeout {}s)...", shutdown_timeout.as_secs());
+        deadline_slot.set(Some(Instant::now() + shutdown_timeout));
+        signal_shutdown.request();
+        server_handle.stop(true).await;
+    });
+    let result = server.await;
+
+    // HTTP 服务已停止，后台任务在主线程的运行时中继续执行，直到完成或超时
+    let deadline = signal_deadline.get().unwrap_or_else(|| Instant::now() + shutdown_timeout);
+    let remaining = || deadline.saturating_duration_since(Instant::now());
+    info!("HTTP server stopped, draining background work (timeout {:.1}s)...", remaining().as_secs_f64());
+    shutdown.request();
+
+    // 投递已到期的 Webhook，未完成的记录保留在队列中，重启后继续投递
+    match actix_web::rt::time::timeout(remaining(), webhook::flush(&db)).await {
+        Ok(Ok(flushed)) => info!("Flushed {} webhook deliveries", flushed),
+        Ok(Err(e)) => log::error!("Failed to flush webhook deliveries: {}", e),
+        Err(_) => log::warn!("Timed out flushing webhook deliveries"),
+    }
+
+    // 等待进行中的定时任务、投递批次和批量操作完成
+    if shutdown.wait_idle(remaining()).await {
+        info!("Shutdown complete");
+    }
+
+    // 最后写入缓冲中的审计日志，包括停机期间完成的请求和后台任务产生的记录
+    match actix_web::rt::time::timeout(remaining(), audit.flush()).await {
+        Ok(Ok(written)) => info!("Flushed {} audit logs", written),
+        Ok(Err(e)) => log::error!("Failed to flush audit logs: {}", e),
+        Err(_) => log::warn!("Timed out flushing audit logs"),
+    }
+    log::logger().flush();
+    result
+}
+
+// 等待停止信号：SIGTERM（docker stop）或 SIGINT（Ctrl+C）
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                futures::future::select(Box::pin(sigterm.recv()), Box::pin(actix_web::rt::signal::ctrl_c())).await;
+                return;
+            }
+            Err(e) => log::error!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = actix_web::rt::signal::ctrl_c().await {
+        log::error!("Failed to listen for SIGINT: {}", e);
+        std::future::pending::<()>().await;
+    }
 }
@@ -0,0 +1,141 @@
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandSucceededEvent};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+
+// 卡密被拒绝的原因，作为 card_rejections_total 的 reason 标签
+pub const REJECT_NOT_FOUND: &str = "not_found";
+pub const REJECT_EXPIRED: &str = "expired";
+pub const REJECT_USED_BY_OTHER: &str = "used_by_other";
+pub const REJECT_REVOKED: &str = "revoked";
+pub const REJECT_ALREADY_ACTIVATED: &str = "already_activated";
+pub const REJECT_PAUSED: &str = "paused";
+pub const REJECT_EXHAUSTED: &str = "exhausted";
+pub const REJECT_NOT_YET_VALID: &str = "not_yet_valid";
+pub const REJECT_VALIDITY_ENDED: &str = "validity_ended";
+pub const REJECT_ACTIVATION_CLOSED: &str = "activation_closed";
+pub const REJECT_NOT_METERED: &str = "not_metered";
+
+// 未匹配到路由（如静态文件）的请求使用的 route 标签
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+// 进程内所有 Prometheus 指标
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub cards_generated: IntCounter,
+    pub cards_verified: IntCounter,
+    pub cards_activated: IntCounter,
+    pub card_rejections: IntCounterVec,
+    pub logins: IntCounterVec,
+    pub mongo_command_duration: HistogramVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+// 获取全局指标
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("card_platform".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by method, route pattern and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by method, route pattern and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let cards_generated = IntCounter::new("cards_generated_total", "Cards generated").unwrap();
+        let cards_verified = IntCounter::new("cards_verified_total", "Successful card verifications").unwrap();
+        let cards_activated = IntCounter::new("cards_activated_total", "Cards activated").unwrap();
+        let card_rejections = IntCounterVec::new(
+            Opts::new("card_rejections_total", "Card verifications and activations rejected, by reason"),
+            &["reason"],
+        )
+        .unwrap();
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by result"),
+            &["result"],
+        )
+        .unwrap();
+        let mongo_command_duration = HistogramVec::new(
+            HistogramOpts::new("mongodb_command_duration_seconds", "MongoDB command latency by command and outcome")
+                .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["command", "outcome"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(cards_generated.clone())).unwrap();
+        registry.register(Box::new(cards_verified.clone())).unwrap();
+        registry.register(Box::new(cards_activated.clone())).unwrap();
+        registry.register(Box::new(card_rejections.clone())).unwrap();
+        registry.register(Box::new(logins.clone())).unwrap();
+        registry.register(Box::new(mongo_command_duration.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            cards_generated,
+            cards_verified,
+            cards_activated,
+            card_rejections,
+            logins,
+            mongo_command_duration,
+        }
+    }
+
+    // 记录一次 HTTP 请求，route 使用路由模板（如 /api/cards/{id}）以避免标签基数爆炸
+    pub fn observe_http(&self, method: &str, route: Option<&str>, status: u16, seconds: f64) {
+        let status = status.to_string();
+        let labels = [method, route.unwrap_or(UNMATCHED_ROUTE), status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration.with_label_values(&labels).observe(seconds);
+    }
+
+    pub fn reject_card(&self, reason: &str) {
+        self.card_rejections.with_label_values(&[reason]).inc();
+    }
+
+    pub fn login(&self, success: bool) {
+        self.logins.with_label_values(&[if success { "success" } else { "failure" }]).inc();
+    }
+
+    // 以 Prometheus 文本格式导出所有指标
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+// MongoDB 命令监听器，记录每条命令的耗时
+pub struct MongoMetrics;
+
+impl CommandEventHandler for MongoMetrics {
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        metrics()
+            .mongo_command_duration
+            .with_label_values(&[event.command_name.as_str(), "success"])
+            .observe(event.duration.as_secs_f64());
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        metrics()
+            .mongo_command_duration
+            .with_label_values(&[event.command_name.as_str(), "failure"])
+            .observe(event.duration.as_secs_f64());
+    }
+}
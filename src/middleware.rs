@@ -1,14 +1,88 @@
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error, HttpMessage,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{ErrorInternalServerError, InternalError},
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
 };
 use futures::future::{ready, LocalBoxFuture, Ready};
+use log::{info, warn};
 use std::rc::Rc;
+use std::time::Instant;
 
-// 客户端IP提取中间件
-pub struct ClientIpMiddleware;
+use crate::client_ip::{self, ClientIp};
+use crate::logging::LogContext;
+use crate::metrics::metrics;
+use crate::AppState;
 
-impl<S, B> Transform<S, ServiceRequest> for ClientIpMiddleware
+// 请求ID响应头，客户端传入合法的值时沿用，否则生成新的ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// 请求处理耗时响应头
+const RESPONSE_TIME_HEADER: &str = "x-response-time";
+// 沿用客户端请求ID时允许的最大长度
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// 请求上下文，由 RequestContextMiddleware 创建并存入请求扩展，可直接作为提取器使用
+#[derive(Clone)]
+pub struct RequestContext(Rc<RequestContextInner>);
+
+struct RequestContextInner {
+    request_id: String,
+    started_at: Instant,
+    log: LogContext,
+}
+
+impl RequestContext {
+    fn new(request_id: String, client_ip: ClientIp) -> Self {
+        let log = LogContext::default();
+        log.insert("request_id", request_id.as_str());
+        log.insert("client_ip", client_ip.to_string());
+        Self(Rc::new(RequestContextInner {
+            request_id,
+            started_at: Instant::now(),
+            log,
+        }))
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.0.request_id
+    }
+
+    // 记录已认证用户，后续日志都会带上用户名
+    pub fn set_user(&self, username: &str) {
+        self.0.log.insert("user", username);
+    }
+
+    // 请求已处理的时长（毫秒）
+    pub fn elapsed_ms(&self) -> f64 {
+        self.0.started_at.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+impl FromRequest for RequestContext {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<RequestContext>()
+                .cloned()
+                .ok_or_else(|| ErrorInternalServerError("Request context missing")),
+        )
+    }
+}
+
+// 只接受由字母、数字和 -_.: 组成的请求ID，避免日志注入
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+}
+
+// 请求上下文中间件：分配或沿用请求ID，解析客户端IP，记录请求指标，并在响应中返回请求ID和处理耗时
+pub struct RequestContextMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestContextMiddleware
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
@@ -17,21 +91,21 @@ where
     type Response = ServiceResponse<B>;
     type Error = Error;
     type InitError = ();
-    type Transform = ClientIpMiddlewareService<S>;
+    type Transform = RequestContextMiddlewareService<S>;
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(ClientIpMiddlewareService {
+        ready(Ok(RequestContextMiddlewareService {
             service: Rc::new(service),
         }))
     }
 }
 
-pub struct ClientIpMiddlewareService<S> {
+pub struct RequestContextMiddlewareService<S> {
     service: Rc<S>,
 }
 
-impl<S, B> Service<ServiceRequest> for ClientIpMiddlewareService<S>
+impl<S, B> Service<ServiceRequest> for RequestContextMiddlewareService<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
@@ -45,20 +119,96 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = Rc::clone(&self.service);
-        
-        // 获取客户端IP
-        let client_ip = req
-            .connection_info()
-            .realip_remote_addr()
-            .unwrap_or("unknown")
-            .to_string();
-        
-        // 将IP添加到请求扩展中
-        req.extensions_mut().insert(client_ip.clone());
-        
-        Box::pin(async move {
-            let res = service.call(req).await?;
+
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // 根据受信任代理配置解析客户端IP，不受信任的连接忽略转发头
+        let client_ip = match req.app_data::<web::Data<AppState>>() {
+            Some(state) => client_ip::resolve(req.peer_addr(), req.headers(), &state.config.server.trusted_proxies.0),
+            None => client_ip::resolve(req.peer_addr(), req.headers(), &[]),
+        };
+
+        let context = RequestContext::new(request_id, client_ip);
+        req.extensions_mut().insert(client_ip);
+        req.extensions_mut().insert(context.clone());
+
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let log = context.0.log.clone();
+
+        Box::pin(log.scope(async move {
+            let result = service.call(req).await;
+            let elapsed_ms = context.elapsed_ms();
+
+            // 内层返回的错误在此转换为响应并附加请求ID和处理耗时，再交给外层输出
+            let mut res = match result {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!("{} {} failed after {:.1}ms: {}", method, path, elapsed_ms, e);
+                    let mut response = e.error_response();
+                    metrics().observe_http(&method, None, response.status().as_u16(), elapsed_ms / 1000.0);
+                    insert_context_headers(response.headers_mut(), &context, elapsed_ms);
+                    return Err(InternalError::from_response(e, response).into());
+                }
+            };
+            info!("{} {} {} {:.1}ms", method, path, res.status().as_u16(), elapsed_ms);
+            metrics().observe_http(&method, res.request().match_pattern().as_deref(), res.status().as_u16(), elapsed_ms / 1000.0);
+
+            insert_context_headers(res.headers_mut(), &context, elapsed_ms);
             Ok(res)
-        })
+        }))
+    }
+}
+
+// 在响应头中写入请求ID和处理耗时
+fn insert_context_headers(headers: &mut HeaderMap, context: &RequestContext, elapsed_ms: f64) {
+    if let Ok(value) = HeaderValue::from_str(context.request_id()) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
     }
-} 
\ No newline at end of file
+    if let Ok(value) = HeaderValue::from_str(&format!("{:.1}ms", elapsed_ms)) {
+        headers.insert(HeaderName::from_static(RESPONSE_TIME_HEADER), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{error::ErrorUnauthorized, http::StatusCode, test, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn error_responses_carry_context_headers() {
+        let app = test::init_service(
+            App::new()
+                .wrap_fn(|_, _| async { Err::<ServiceResponse, _>(ErrorUnauthorized("denied")) })
+                .wrap(RequestContextMiddleware)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").insert_header((REQUEST_ID_HEADER, "req-1")).to_request();
+        // 错误交由外层转换为响应，与服务器实际输出的响应一致
+        let res = test::try_call_service(&app, req).await.unwrap_err().error_response();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "req-1");
+        assert!(res.headers().contains_key(RESPONSE_TIME_HEADER));
+    }
+
+    #[actix_web::test]
+    async fn success_responses_carry_context_headers() {
+        let app = test::init_service(
+            App::new().wrap(RequestContextMiddleware).route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().contains_key(REQUEST_ID_HEADER));
+        assert!(res.headers().contains_key(RESPONSE_TIME_HEADER));
+    }
+}
@@ -0,0 +1,336 @@
+use chrono::Utc;
+use futures::future::BoxFuture;
+use futures::stream::TryStreamExt;
+use log::info;
+use mongodb::bson::{doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Database, IndexModel};
+use serde::{Deserialize, Serialize};
+
+type MigrationResult = Result<(), mongodb::error::Error>;
+
+// 数据库迁移，按 id 顺序执行，已执行的记录在 migrations 集合中
+pub struct Migration {
+    pub id: &'static str,
+    pub description: &'static str,
+    run: for<'a> fn(&'a Database) -> BoxFuture<'a, MigrationResult>,
+}
+
+// 所有迁移，新增迁移追加到末尾，已发布的迁移不要修改
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        id: "0001_create_indexes",
+        description: "Create indexes for cards, users, webhook deliveries and audit logs",
+        run: |db| Box::pin(create_indexes(db)),
+    },
+    Migration {
+        id: "0002_api_key_indexes",
+        description: "Create indexes for API keys",
+        run: |db| Box::pin(create_api_key_indexes(db)),
+    },
+    Migration {
+        id: "0003_client_signature_indexes",
+        description: "Create indexes for applications and expiring request nonces",
+        run: |db| Box::pin(create_client_signature_indexes(db)),
+    },
+    Migration {
+        id: "0004_card_app_index",
+        description: "Create index for listing cards by application",
+        run: |db| Box::pin(create_card_app_index(db)),
+    },
+    Migration {
+        id: "0005_card_device_bindings",
+        description: "Move card used_by_identifier into device bindings",
+        run: |db| Box::pin(migrate_device_bindings(db)),
+    },
+    Migration {
+        id: "0006_card_rebind_indexes",
+        description: "Create indexes for card owners, rebind records and expiring rebind codes",
+        run: |db| Box::pin(create_card_rebind_indexes(db)),
+    },
+    Migration {
+        id: "0007_customer_indexes",
+        description: "Create indexes for customers, customer cards and expiring login codes",
+        run: |db| Box::pin(create_customer_indexes(db)),
+    },
+    Migration {
+        id: "0008_duration_seconds",
+        description: "Convert card and application durations from days to seconds and index card shelf life",
+        run: |db| Box::pin(migrate_duration_seconds(db)),
+    },
+    Migration {
+        id: "0009_card_window_indexes",
+        description: "Create indexes for expiring unused cards by activation deadline and fixed expiry",
+        run: |db| Box::pin(create_card_window_indexes(db)),
+    },
+    Migration {
+        id: "0010_session_indexes",
+        description: "Create index for expiring user sessions",
+        run: |db| Box::pin(create_session_indexes(db)),
+    },
+];
+
+// 已执行的迁移记录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub description: String,
+    pub applied_at_str: String,
+}
+
+// 获取已执行的迁移 ID
+async fn applied_ids(db: &Database) -> Result<Vec<String>, mongodb::error::Error> {
+    let applied: Vec<AppliedMigration> = db
+        .collection::<AppliedMigration>("migrations")
+        .find(None, None)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(applied.into_iter().map(|m| m.id).collect())
+}
+
+// 获取尚未执行的迁移
+pub async fn pending(db: &Database) -> Result<Vec<&'static Migration>, mongodb::error::Error> {
+    let applied = applied_ids(db).await?;
+    Ok(MIGRATIONS.iter().filter(|m| !applied.iter().any(|id| id == m.id)).collect())
+}
+
+// 依次执行所有未执行的迁移，返回本次执行的迁移 ID
+pub async fn run_pending(db: &Database) -> Result<Vec<&'static str>, mongodb::error::Error> {
+    let collection = db.collection::<AppliedMigration>("migrations");
+    let mut applied = Vec::new();
+
+    for migration in pending(db).await? {
+        info!("Applying migration '{}': {}", migration.id, migration.description);
+        (migration.run)(db).await?;
+        collection
+            .insert_one(
+                AppliedMigration {
+                    id: migration.id.to_string(),
+                    description: migration.description.to_string(),
+                    applied_at_str: Utc::now().to_rfc3339(),
+                },
+                None,
+            )
+            .await?;
+        applied.push(migration.id);
+    }
+
+    Ok(applied)
+}
+
+fn index(keys: Document, unique: bool) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(unique).build())
+        .build()
+}
+
+// 创建唯一索引前检查已有的重复值（包括回收站中的记录），有重复时列出并终止迁移，需手动清理后重新执行
+async fn ensure_unique(db: &Database, collection: &str, field: &str) -> MigrationResult {
+    let pipeline = vec![
+        doc! { "$group": { "_id": format!("${}", field), "count": { "$sum": 1 } } },
+        doc! { "$match": { "count": { "$gt": 1 } } },
+        doc! { "$sort": { "_id": 1 } },
+        doc! { "$limit": 20 },
+    ];
+    let duplicates: Vec<Document> = db.collection::<Document>(collection)
+        .aggregate(pipeline, None)
+        .await?
+        .try_collect()
+        .await?;
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let listed = duplicates
+        .iter()
+        .map(|d| {
+            let value = d.get("_id").map_or("null".to_string(), |v| v.to_string());
+            let count = d.get("count").map_or("?".to_string(), |v| v.to_string());
+            format!("{} x{}", value, count)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let message = format!(
+        "Cannot create unique index on {}.{}: duplicate values found (showing up to 20): {}. Remove or rename the duplicates and run the migration again",
+        collection, field, listed
+    );
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message).into())
+}
+
+// 0001：为常用查询创建索引，卡号和用户名唯一
+async fn create_indexes(db: &Database) -> MigrationResult {
+    ensure_unique(db, "cards", "card_number").await?;
+    ensure_unique(db, "users", "username").await?;
+    db.collection::<Document>("cards")
+        .create_indexes(
+            [
+                index(doc! { "card_number": 1 }, true),
+                index(doc! { "created_by": 1, "deleted_at_str": 1 }, false),
+                index(doc! { "batch_id": 1 }, false),
+                index(doc! { "expires_at_str": 1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    db.collection::<Document>("users")
+        .create_indexes([index(doc! { "username": 1 }, true)], None)
+        .await?;
+    db.collection::<Document>("webhook_deliveries")
+        .create_indexes(
+            [
+                index(doc! { "status": 1, "next_attempt_at_str": 1 }, false),
+                index(doc! { "webhook_id": 1, "created_at_str": -1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    db.collection::<Document>("audit_logs")
+        .create_indexes(
+            [
+                index(doc! { "created_at_str": -1 }, false),
+                index(doc! { "actor": 1, "created_at_str": -1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// 0002：API 密钥按哈希查找（唯一），按用户列出
+async fn create_api_key_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("api_keys")
+        .create_indexes(
+            [
+                index(doc! { "key_hash": 1 }, true),
+                index(doc! { "user_id": 1, "created_at_str": -1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// 0003：应用按 app_key 查找（唯一）；已使用的 nonce 到期后由 TTL 索引自动删除
+async fn create_client_signature_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("applications")
+        .create_indexes([index(doc! { "app_key": 1 }, true)], None)
+        .await?;
+    let ttl = IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(IndexOptions::builder().expire_after(std::time::Duration::ZERO).build())
+        .build();
+    db.collection::<Document>("nonces").create_index(ttl, None).await?;
+    Ok(())
+}
+
+// 0004：按应用筛选卡密列表、导出和统计
+async fn create_card_app_index(db: &Database) -> MigrationResult {
+    db.collection::<Document>("cards")
+        .create_indexes([index(doc! { "app_id": 1, "deleted_at_str": 1 }, false)], None)
+        .await?;
+    Ok(())
+}
+
+// 0005：卡密的单个使用者标识改为设备绑定列表，原标识作为第一个绑定，绑定时间取激活时间
+async fn migrate_device_bindings(db: &Database) -> MigrationResult {
+    let pipeline = vec![
+        doc! { "$set": { "bindings": [{
+            "identifier": "$used_by_identifier",
+            "device": null,
+            "bound_at_str": { "$ifNull": ["$activated_at_str", "$created_at_str"] },
+            "last_seen_at_str": null,
+        }] } },
+        doc! { "$unset": "used_by_identifier" },
+    ];
+    let result = db.collection::<Document>("cards")
+        .update_many(doc! { "used_by_identifier": { "$type": "string" } }, pipeline, None)
+        .await?;
+    info!("Migrated device bindings for {} cards", result.modified_count);
+    db.collection::<Document>("cards")
+        .update_many(doc! { "used_by_identifier": { "$exists": true } }, doc! { "$unset": { "used_by_identifier": "" } }, None)
+        .await?;
+    Ok(())
+}
+
+// 0006：所有者凭证按卡密查找（唯一）；换绑记录按卡密和时间统计；换绑验证码到期后由 TTL 索引自动删除
+async fn create_card_rebind_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("card_owners")
+        .create_indexes([index(doc! { "card_id": 1 }, true)], None)
+        .await?;
+    db.collection::<Document>("card_rebinds")
+        .create_indexes([index(doc! { "card_id": 1, "created_at_str": -1 }, false)], None)
+        .await?;
+    let ttl = IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(IndexOptions::builder().expire_after(std::time::Duration::ZERO).build())
+        .build();
+    db.collection::<Document>("rebind_codes").create_index(ttl, None).await?;
+    Ok(())
+}
+
+// 0007：客户按邮箱查找（唯一）；按客户列出卡密；登录验证码到期后由 TTL 索引自动删除
+async fn create_customer_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("customers")
+        .create_indexes([index(doc! { "email": 1 }, true)], None)
+        .await?;
+    db.collection::<Document>("cards")
+        .create_indexes([index(doc! { "customer_id": 1, "redeemed_at_str": -1 }, false)], None)
+        .await?;
+    let ttl = IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(IndexOptions::builder().expire_after(std::time::Duration::ZERO).build())
+        .build();
+    db.collection::<Document>("customer_login_codes").create_index(ttl, None).await?;
+    Ok(())
+}
+
+// 0008：卡密有效期和应用默认套餐由天数改为秒数，并为保质期筛选未激活卡密创建索引
+async fn migrate_duration_seconds(db: &Database) -> MigrationResult {
+    let pipeline = vec![
+        doc! { "$set": { "duration_seconds": { "$multiply": ["$duration_days", 86_400_i64] } } },
+        doc! { "$unset": "duration_days" },
+    ];
+    let result = db.collection::<Document>("cards")
+        .update_many(doc! { "duration_days": { "$type": "number" } }, pipeline, None)
+        .await?;
+    info!("Converted durations of {} cards to seconds", result.modified_count);
+
+    let pipeline = vec![
+        doc! { "$set": { "default_duration_seconds": { "$multiply": ["$default_duration_days", 86_400_i64] } } },
+        doc! { "$unset": "default_duration_days" },
+    ];
+    let result = db.collection::<Document>("applications")
+        .update_many(doc! { "default_duration_days": { "$type": "number" } }, pipeline, None)
+        .await?;
+    info!("Converted default durations of {} applications to seconds", result.modified_count);
+
+    db.collection::<Document>("cards")
+        .create_indexes([index(doc! { "is_activated": 1, "shelf_expires_at_str": 1 }, false)], None)
+        .await?;
+    Ok(())
+}
+
+// 0009：定时任务按激活截止时间和固定到期时间查找需要标记过期的未激活卡密
+async fn create_card_window_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("cards")
+        .create_indexes(
+            [
+                index(doc! { "is_activated": 1, "activate_before_str": 1 }, false),
+                index(doc! { "is_activated": 1, "valid_until_str": 1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// 0010：定时任务按令牌过期时间清理过期的登录会话
+async fn create_session_indexes(db: &Database) -> MigrationResult {
+    db.collection::<Document>("users")
+        .create_indexes([index(doc! { "token_expires_at_str": 1 }, false)], None)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// API 密钥的前缀，Authorization 中以该前缀开头的令牌按 API 密钥处理
+pub const API_KEY_PREFIX: &str = "cpk_";
+
+// API 密钥权限范围，按资源和读写划分；GET 请求需要 read，其他请求需要 write
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+pub enum ApiKeyScope {
+    #[serde(rename = "cards:read")]
+    CardsRead,
+    #[serde(rename = "cards:write")]
+    CardsWrite,
+    #[serde(rename = "stats:read")]
+    StatsRead,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+    #[serde(rename = "webhooks:read")]
+    WebhooksRead,
+    #[serde(rename = "webhooks:write")]
+    WebhooksWrite,
+    #[serde(rename = "jobs:read")]
+    JobsRead,
+    #[serde(rename = "jobs:write")]
+    JobsWrite,
+    #[serde(rename = "applications:read")]
+    ApplicationsRead,
+    #[serde(rename = "applications:write")]
+    ApplicationsWrite,
+}
+
+impl ApiKeyScope {
+    // 访问指定资源所需的权限范围，resource 为 /api 之后的第一级路径（如 cards）
+    pub fn required(resource: &str, write: bool) -> Option<Self> {
+        let scope = match (resource, write) {
+            ("cards", false) => ApiKeyScope::CardsRead,
+            ("cards", true) => ApiKeyScope::CardsWrite,
+            ("stats", _) => ApiKeyScope::StatsRead,
+            ("users", false) => ApiKeyScope::UsersRead,
+            ("users", true) => ApiKeyScope::UsersWrite,
+            ("webhooks", false) => ApiKeyScope::WebhooksRead,
+            ("webhooks", true) => ApiKeyScope::WebhooksWrite,
+            ("jobs", false) => ApiKeyScope::JobsRead,
+            ("jobs", true) => ApiKeyScope::JobsWrite,
+            ("applications", false) => ApiKeyScope::ApplicationsRead,
+            ("applications", true) => ApiKeyScope::ApplicationsWrite,
+            _ => return None,
+        };
+        Some(scope)
+    }
+}
+
+// API 密钥，只保存密钥的 SHA-256 哈希，明文只在创建时返回一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub key_hash: String,                   // 完整密钥的 SHA-256 十六进制哈希
+    pub key_prefix: String,                 // 密钥开头的若干字符，用于在列表中辨认
+    pub user_id: String,                    // 所属用户，使用密钥时以该用户的身份和角色访问
+    pub username: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub allowed_ips: Vec<String>,           // IP 或 CIDR 白名单，为空表示不限制
+    pub expires_at_str: Option<String>,     // 过期时间，为空表示永不过期
+    pub last_used_at_str: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at_str: Option<String>,     // 撤销时间，撤销后立即失效
+    pub created_at_str: String,
+}
+
+impl ApiKey {
+    pub fn new(name: String, key_hash: String, key_prefix: String, user_id: String, username: String) -> Self {
+        Self {
+            id: None,
+            name,
+            key_hash,
+            key_prefix,
+            user_id,
+            username,
+            scopes: Vec::new(),
+            allowed_ips: Vec::new(),
+            expires_at_str: None,
+            last_used_at_str: None,
+            last_used_ip: None,
+            revoked_at_str: None,
+            created_at_str: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// 返回给客户端的 API 密钥信息（不包含哈希）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub user_id: String,
+    pub username: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub allowed_ips: Vec<String>,
+    pub expires_at_str: Option<String>,
+    pub last_used_at_str: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at_str: Option<String>,
+    pub created_at_str: String,
+}
+
+impl From<ApiKey> for ApiKeyInfo {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: key.name,
+            key_prefix: key.key_prefix,
+            user_id: key.user_id,
+            username: key.username,
+            scopes: key.scopes,
+            allowed_ips: key.allowed_ips,
+            expires_at_str: key.expires_at_str,
+            last_used_at_str: key.last_used_at_str,
+            last_used_ip: key.last_used_ip,
+            revoked_at_str: key.revoked_at_str,
+            created_at_str: key.created_at_str,
+        }
+    }
+}
+
+// 创建 API 密钥请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    pub expires_in_days: Option<i64>,  // 有效天数，不提供则永不过期
+}
+
+// 创建 API 密钥响应，key 只在此时返回
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyCreated {
+    pub api_key: ApiKeyInfo,
+    pub key: String,
+}
@@ -0,0 +1,192 @@
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::device::{self, DeviceIdentity};
+use super::duration::{self as duration_input, DAY_SECONDS};
+
+// 设备策略，决定验证卡密时如何识别使用者以及如何匹配设备指纹
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct DevicePolicy {
+    pub require_identifier: bool,  // 是否要求提供 user_identifier 或设备指纹，为 false 时未提供则使用客户端IP
+    #[schema(minimum = 1, default = 1)]
+    pub max_devices: u32,          // 每张卡密最多绑定的设备数
+    #[schema(minimum = 0.0, maximum = 1.0, default = 0.6)]
+    pub match_threshold: f64,      // 设备指纹相似度达到该值即视为同一设备，1.0 表示上报的字段必须全部一致
+    pub required_components: Vec<String>,  // 必须保持一致的指纹字段，如 ["machine_id"]
+    #[schema(default = 3)]
+    pub rebind_limit: u32,         // 每个周期内允许自助换绑的次数，为0时禁止自助换绑
+    #[schema(minimum = 1, default = 30)]
+    pub rebind_period_days: u32,   // 换绑次数的统计周期（天）
+    #[schema(default = 0)]
+    pub rebind_penalty_hours: u32, // 每次换绑扣除的有效时间（小时），为0时不扣除
+}
+
+impl Default for DevicePolicy {
+    fn default() -> Self {
+        Self {
+            require_identifier: false,
+            max_devices: 1,
+            match_threshold: 0.6,
+            required_components: Vec::new(),
+            rebind_limit: 3,
+            rebind_period_days: 30,
+            rebind_penalty_hours: 0,
+        }
+    }
+}
+
+impl DevicePolicy {
+    // 校验策略参数，返回错误信息
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_devices < 1 {
+            return Err("device_policy.max_devices must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.match_threshold) {
+            return Err("device_policy.match_threshold must be between 0 and 1".to_string());
+        }
+        if self.rebind_period_days < 1 {
+            return Err("device_policy.rebind_period_days must be at least 1".to_string());
+        }
+        if let Some(name) = self.required_components.iter().find(|name| !device::is_component(name)) {
+            return Err(format!("Unknown device component '{}'", name));
+        }
+        Ok(())
+    }
+}
+
+fn default_duration_seconds() -> i64 {
+    30 * DAY_SECONDS
+}
+
+// 接入的客户端应用（产品），卡密归属于应用；客户端使用 app_key 和 secret 对验证卡密、心跳请求签名
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Application {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub app_key: String,           // 公开的应用标识，随请求通过 X-App-Key 传递
+    pub secret: String,            // HMAC 签名密钥，只在创建和重新生成时返回
+    pub enabled: bool,             // 停用后该应用的验证请求全部被拒绝
+    #[serde(default = "default_duration_seconds")]
+    pub default_duration_seconds: i64,  // 默认套餐：生成卡密时未指定有效期则使用该时长（秒）
+    #[serde(default)]
+    pub device_policy: DevicePolicy,
+    #[serde(default)]
+    pub allow_owner_pause: bool,     // 是否允许卡密所有者自行暂停/恢复（可暂停的套餐），管理员不受此限制
+    #[serde(default)]
+    pub require_signature: bool,     // 是否只接受签名请求，开启后不能在请求体中通过 app_key 指定该应用
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at_str: String,
+    pub updated_at_str: String,
+}
+
+impl Application {
+    pub fn new(name: String, app_key: String, secret: String, description: Option<String>, created_by: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: None,
+            name,
+            app_key,
+            secret,
+            enabled: true,
+            default_duration_seconds: default_duration_seconds(),
+            device_policy: DevicePolicy::default(),
+            allow_owner_pause: false,
+            require_signature: false,
+            description,
+            created_by,
+            created_at_str: now.clone(),
+            updated_at_str: now,
+        }
+    }
+}
+
+// 返回给客户端的应用信息（不包含密钥）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApplicationInfo {
+    pub id: String,
+    pub name: String,
+    pub app_key: String,
+    pub enabled: bool,
+    pub default_duration_seconds: i64,
+    pub device_policy: DevicePolicy,
+    pub allow_owner_pause: bool,
+    pub require_signature: bool,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at_str: String,
+    pub updated_at_str: String,
+}
+
+impl From<Application> for ApplicationInfo {
+    fn from(app: Application) -> Self {
+        Self {
+            id: app.id.map(|id| id.to_hex()).unwrap_or_default(),
+            name: app.name,
+            app_key: app.app_key,
+            enabled: app.enabled,
+            default_duration_seconds: app.default_duration_seconds,
+            device_policy: app.device_policy,
+            allow_owner_pause: app.allow_owner_pause,
+            require_signature: app.require_signature,
+            description: app.description,
+            created_by: app.created_by,
+            created_at_str: app.created_at_str,
+            updated_at_str: app.updated_at_str,
+        }
+    }
+}
+
+// 创建应用请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApplicationRequest {
+    pub name: String,
+    // 默认套餐时长，如 "2h"、"30d"，数字按天计算（兼容 default_duration_days），不提供则为30天
+    #[serde(default, alias = "default_duration_days", deserialize_with = "duration_input::deserialize_optional")]
+    #[schema(value_type = Option<String>, example = "30d")]
+    pub default_duration: Option<i64>,
+    pub device_policy: Option<DevicePolicy>,
+    #[serde(default)]
+    pub allow_owner_pause: bool,  // 是否允许所有者自行暂停/恢复卡密，默认不允许
+    #[serde(default)]
+    pub require_signature: bool,  // 是否只接受签名请求，默认不要求
+    pub description: Option<String>,
+}
+
+// 更新应用请求，字段均可选
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateApplicationRequest {
+    pub name: Option<String>,
+    pub enabled: Option<bool>,
+    #[serde(default, alias = "default_duration_days", deserialize_with = "duration_input::deserialize_optional")]
+    #[schema(value_type = Option<String>, example = "30d")]
+    pub default_duration: Option<i64>,
+    pub device_policy: Option<DevicePolicy>,
+    pub allow_owner_pause: Option<bool>,
+    pub require_signature: Option<bool>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rotate_secret: bool,  // 是否重新生成签名密钥
+}
+
+// 心跳请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HeartbeatRequest {
+    pub card_number: String,
+    pub user_identifier: Option<String>,  // 与验证卡密时相同，未提供时使用客户端IP
+    pub device: Option<DeviceIdentity>,   // 与验证卡密时相同
+}
+
+// 心跳响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HeartbeatResponse {
+    pub card_number: String,
+    pub expires_at_str: Option<String>,
+    pub remaining_seconds: Option<i64>,  // 距离过期的剩余秒数，不限时间的卡密为空
+    pub remaining_uses: Option<i64>,     // 剩余次数，不按次数计费的卡密为空
+    pub server_time: String,     // 服务器当前时间，客户端可据此校准时钟
+}
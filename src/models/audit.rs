@@ -0,0 +1,46 @@
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth::AuthenticatedUser;
+
+// 审计日志：记录员工和 API 密钥执行的管理操作
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditLog {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    pub actor_id: String,              // 操作者用户ID
+    pub actor: String,                 // 操作者用户名
+    pub api_key_id: Option<String>,    // 通过 API 密钥操作时的密钥ID
+    pub action: String,                // 操作类型，如 card.delete
+    pub target: Option<String>,        // 操作对象的ID
+    #[schema(value_type = Option<Object>)]
+    pub detail: Option<serde_json::Value>,  // 操作参数
+    pub created_at_str: String,
+}
+
+impl AuditLog {
+    pub fn new(user: &AuthenticatedUser, action: &str, target: Option<String>, detail: Option<serde_json::Value>) -> Self {
+        Self {
+            id: None,
+            actor_id: user.user_id.clone(),
+            actor: user.username.clone(),
+            api_key_id: user.api_key_id.clone(),
+            action: action.to_string(),
+            target,
+            detail,
+            created_at_str: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// 审计日志查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    pub actor: Option<String>,   // 按操作者用户名筛选
+    pub action: Option<String>,  // 按操作类型筛选
+    pub target: Option<String>,  // 按操作对象ID筛选
+    pub limit: Option<i64>,      // 默认100，最多500
+}
@@ -0,0 +1,78 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::duration;
+use super::CardQuery;
+
+// 批量操作类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Delete,    // 移入回收站
+    Revoke,    // 撤销
+    Extend,    // 延长有效期
+    Reassign,  // 转移创建者（仅管理员）
+    Unbind,    // 解除设备绑定
+}
+
+// 批量操作请求，ids 与 filter 二选一
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkCardRequest {
+    pub action: BulkAction,
+    pub ids: Option<Vec<String>>,
+    pub filter: Option<CardQuery>,
+    // extend: 延长的时长，如 "2h"、"-30m"、"7d"，数字按天计算（兼容 days），可为负数
+    #[serde(default, alias = "days", deserialize_with = "duration::deserialize_optional")]
+    #[schema(value_type = Option<String>, example = "7d")]
+    pub duration: Option<i64>,
+    pub creator_id: Option<String>,  // reassign: 新的创建者用户ID
+}
+
+// 单张卡密的处理结果
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkItemResult {
+    pub id: String,
+    pub card_number: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+// 批量任务状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkJobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+// 批量任务，数量较多的操作在后台执行并记录进度
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    pub action: BulkAction,
+    pub status: BulkJobStatus,
+    pub total: i64,
+    pub processed: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub results: Vec<BulkItemResult>,
+    pub error: Option<String>,
+    pub created_by: String,
+    pub created_by_username: String,
+    pub created_at_str: String,
+    pub finished_at_str: Option<String>,
+}
+
+// 同步执行的批量操作响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse {
+    pub action: BulkAction,
+    pub total: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub results: Vec<BulkItemResult>,
+}
@@ -1,5 +1,3 @@
-use serde::{Serialize, Deserialize};
-
 // 注释掉这个结构体，因为我们已经在 mod.rs 中定义了它
 // #[derive(Debug, Serialize, Deserialize)]
 // pub struct CreateCardRequest {
@@ -0,0 +1,117 @@
+use chrono::Utc;
+use mongodb::bson::{self, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::device::DeviceBinding;
+use super::CardStatus;
+
+// 终端客户账号，与后台员工账号（users 集合）分开存储，使用独立的令牌
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Customer {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub email: String,                  // 登录邮箱，保存为小写
+    pub password_hash: Option<String>,  // 只使用邮箱验证码登录的账号没有密码
+    pub created_at_str: String,
+    pub updated_at_str: String,
+    pub last_login_at_str: Option<String>,
+}
+
+impl Customer {
+    pub fn new(email: String, password_hash: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: None,
+            email,
+            password_hash,
+            created_at_str: now.clone(),
+            updated_at_str: now,
+            last_login_at_str: None,
+        }
+    }
+}
+
+// 返回给客户的账号信息（不包含密码）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CustomerInfo {
+    pub id: String,
+    pub email: String,
+    pub has_password: bool,
+    pub created_at_str: String,
+}
+
+impl From<Customer> for CustomerInfo {
+    fn from(customer: Customer) -> Self {
+        Self {
+            id: customer.id.map(|id| id.to_hex()).unwrap_or_default(),
+            email: customer.email,
+            has_password: customer.password_hash.is_some(),
+            created_at_str: customer.created_at_str,
+        }
+    }
+}
+
+// 邮箱登录验证码，每个邮箱同时只有一个有效验证码，过期后由 TTL 索引自动删除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomerLoginCode {
+    #[serde(rename = "_id")]
+    pub email: String,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub created_at_str: String,
+    pub expires_at: bson::DateTime,
+}
+
+// 客户注册/密码登录请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CustomerPasswordRequest {
+    pub email: String,
+    #[schema(min_length = 8)]
+    pub password: String,
+}
+
+// 申请邮箱登录验证码请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CustomerCodeRequest {
+    pub email: String,
+}
+
+// 邮箱验证码登录请求，邮箱未注册时自动创建账号
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CustomerCodeLoginRequest {
+    pub email: String,
+    pub code: String,
+}
+
+// 客户登录响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CustomerLoginResponse {
+    pub token: String,
+    pub customer: CustomerInfo,
+}
+
+// 兑换卡密到账号的请求；已激活的卡密需要提供激活时返回的所有者密钥
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RedeemCardRequest {
+    pub card_number: String,
+    pub owner_secret: Option<String>,
+}
+
+// 客户账号中的授权信息
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CustomerLicense {
+    pub card_number: String,
+    pub app_id: Option<String>,
+    pub app_name: Option<String>,
+    pub status: CardStatus,
+    pub duration_seconds: i64,
+    pub activated_at_str: Option<String>,
+    pub expires_at_str: Option<String>,
+    pub remaining_seconds: Option<i64>,  // 剩余有效时间，未激活或不限时间时为空，已过期时为0
+    pub usage_limit: Option<i64>,        // 可使用的总次数，不按次数计费时为空
+    pub remaining_uses: Option<i64>,
+    pub max_devices: u32,
+    pub bindings: Vec<DeviceBinding>,
+    pub redeemed_at_str: Option<String>,
+}
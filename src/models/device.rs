@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::application::DevicePolicy;
+
+// 设备指纹字段及其在相似度中的权重，机器ID最稳定，主机名和系统最容易变化
+const COMPONENT_WEIGHTS: [(&str, u32); 6] = [
+    ("machine_id", 4),
+    ("disk_hash", 2),
+    ("mac_hash", 2),
+    ("cpu_hash", 2),
+    ("hostname_hash", 1),
+    ("os", 1),
+];
+
+// 参与模糊匹配的指纹至少需要的字段权重（相当于机器ID，或两个硬件哈希），更弱的指纹按使用者标识匹配
+pub const MIN_DEVICE_WEIGHT: u32 = 4;
+
+// 是否为有效的设备指纹字段名
+pub fn is_component(name: &str) -> bool {
+    COMPONENT_WEIGHTS.iter().any(|(component, _)| *component == name)
+}
+
+// 客户端上报的设备指纹，各字段均可选；主机名、MAC 等敏感信息由客户端哈希后上报
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeviceIdentity {
+    pub machine_id: Option<String>,     // 操作系统的机器ID（如 /etc/machine-id、MachineGuid）
+    pub os: Option<String>,             // 操作系统，如 windows-10.0.19045
+    pub hostname_hash: Option<String>,  // 主机名哈希
+    pub mac_hash: Option<String>,       // 主网卡 MAC 地址哈希
+    pub cpu_hash: Option<String>,       // CPU 型号/序列号哈希
+    pub disk_hash: Option<String>,      // 系统盘序列号哈希
+}
+
+impl DeviceIdentity {
+    fn component(&self, name: &str) -> Option<&str> {
+        match name {
+            "machine_id" => self.machine_id.as_deref(),
+            "os" => self.os.as_deref(),
+            "hostname_hash" => self.hostname_hash.as_deref(),
+            "mac_hash" => self.mac_hash.as_deref(),
+            "cpu_hash" => self.cpu_hash.as_deref(),
+            "disk_hash" => self.disk_hash.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn set_component(&mut self, name: &str, value: Option<String>) {
+        match name {
+            "machine_id" => self.machine_id = value,
+            "os" => self.os = value,
+            "hostname_hash" => self.hostname_hash = value,
+            "mac_hash" => self.mac_hash = value,
+            "cpu_hash" => self.cpu_hash = value,
+            "disk_hash" => self.disk_hash = value,
+            _ => {}
+        }
+    }
+
+    // 已上报字段的权重之和
+    pub fn weight(&self) -> u32 {
+        COMPONENT_WEIGHTS.iter()
+            .filter(|(name, _)| self.component(name).is_some())
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    // 与已绑定指纹一致的字段权重：只统计绑定中存在且本次上报值相同的字段
+    fn matched_weight(&self, bound: &DeviceIdentity) -> u32 {
+        COMPONENT_WEIGHTS.iter()
+            .filter(|(name, _)| bound.component(name).is_some() && self.component(name) == bound.component(name))
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    // 与已绑定指纹的相似度（0-1）：分母为绑定中存在的字段权重，本次未上报的字段按不一致计算；绑定没有字段时为0
+    pub fn similarity(&self, bound: &DeviceIdentity) -> f64 {
+        let total = bound.weight();
+        if total == 0 { 0.0 } else { self.matched_weight(bound) as f64 / total as f64 }
+    }
+
+    // 按设备策略判断是否为已绑定的设备：必须一致的字段双方都要上报且相同，一致字段的权重不低于 MIN_DEVICE_WEIGHT，
+    // 相似度达到阈值即可容忍少量硬件变化
+    pub fn matches(&self, bound: &DeviceIdentity, policy: &DevicePolicy) -> bool {
+        let required_match = policy.required_components.iter()
+            .all(|name| self.component(name).is_some() && self.component(name) == bound.component(name));
+        required_match
+            && self.matched_weight(bound) >= MIN_DEVICE_WEIGHT
+            && self.similarity(bound) >= policy.match_threshold
+    }
+
+    // 将本次上报的字段合并到已绑定的指纹：上报的字段更新为新值，未上报的字段保留原值，避免部分指纹削弱绑定
+    pub fn merged(&self, update: &DeviceIdentity) -> DeviceIdentity {
+        let mut merged = self.clone();
+        for (name, _) in COMPONENT_WEIGHTS {
+            if let Some(value) = update.component(name) {
+                merged.set_component(name, Some(value.to_string()));
+            }
+        }
+        merged
+    }
+}
+
+// 卡密与设备的绑定，一张卡密最多绑定设备策略允许的设备数
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeviceBinding {
+    pub identifier: Option<String>,        // 使用者标识（user_identifier，未提供设备信息时为客户端IP）
+    pub device: Option<DeviceIdentity>,    // 绑定时上报的设备指纹，匹配成功后更新为最新的指纹
+    pub bound_at_str: String,
+    pub last_seen_at_str: Option<String>,  // 最近一次验证或心跳时间
+}
+
+// 请求中的使用者身份
+#[derive(Debug, Clone)]
+pub struct PresentedDevice {
+    pub identifier: Option<String>,
+    pub device: Option<DeviceIdentity>,
+}
+
+impl PresentedDevice {
+    // 未提供 user_identifier 和设备指纹时使用客户端IP作为标识；字段权重不足的指纹不可靠，视为未提供
+    pub fn new(identifier: Option<String>, device: Option<DeviceIdentity>, client_ip: impl ToString) -> Self {
+        let device = device.filter(|device| device.weight() >= MIN_DEVICE_WEIGHT);
+        let identifier = match (identifier, &device) {
+            (Some(identifier), _) => Some(identifier),
+            (None, Some(_)) => None,
+            (None, None) => Some(client_ip.to_string()),
+        };
+        Self { identifier, device }
+    }
+
+    // 是否与绑定为同一使用者：双方都有设备指纹时按策略模糊匹配，否则比较标识
+    pub fn matches(&self, binding: &DeviceBinding, policy: &DevicePolicy) -> bool {
+        match (&self.device, &binding.device) {
+            (Some(device), Some(bound)) => device.matches(bound, policy),
+            _ => self.identifier.is_some() && self.identifier == binding.identifier,
+        }
+    }
+
+    pub fn to_binding(&self, now: &str) -> DeviceBinding {
+        DeviceBinding {
+            identifier: self.identifier.clone(),
+            device: self.device.clone(),
+            bound_at_str: now.to_string(),
+            last_seen_at_str: Some(now.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_device() -> DeviceIdentity {
+        DeviceIdentity {
+            machine_id: Some("machine-1".to_string()),
+            os: Some("windows-10.0.19045".to_string()),
+            hostname_hash: Some("host".to_string()),
+            mac_hash: Some("mac".to_string()),
+            cpu_hash: Some("cpu".to_string()),
+            disk_hash: Some("disk".to_string()),
+        }
+    }
+
+    fn os_only() -> DeviceIdentity {
+        DeviceIdentity { os: Some("windows-10.0.19045".to_string()), ..Default::default() }
+    }
+
+    fn binding(identifier: Option<&str>, device: Option<DeviceIdentity>) -> DeviceBinding {
+        DeviceBinding {
+            identifier: identifier.map(str::to_string),
+            device,
+            bound_at_str: "2024-01-01T00:00:00+00:00".to_string(),
+            last_seen_at_str: None,
+        }
+    }
+
+    #[test]
+    fn similarity_of_identical_devices_is_one() {
+        assert_eq!(full_device().similarity(&full_device()), 1.0);
+    }
+
+    #[test]
+    fn similarity_counts_missing_fields_as_mismatch() {
+        let bound = full_device();
+        assert_eq!(os_only().similarity(&bound), 1.0 / 12.0);
+
+        let mut without_mac = full_device();
+        without_mac.mac_hash = None;
+        assert_eq!(without_mac.similarity(&bound), 10.0 / 12.0);
+    }
+
+    #[test]
+    fn similarity_with_changed_field() {
+        let mut changed = full_device();
+        changed.mac_hash = Some("new-mac".to_string());
+        assert_eq!(changed.similarity(&full_device()), 10.0 / 12.0);
+    }
+
+    #[test]
+    fn similarity_against_empty_binding_is_zero() {
+        assert_eq!(full_device().similarity(&DeviceIdentity::default()), 0.0);
+        assert_eq!(DeviceIdentity::default().similarity(&full_device()), 0.0);
+    }
+
+    #[test]
+    fn partial_fingerprint_does_not_match_full_binding() {
+        let policy = DevicePolicy::default();
+        assert!(!os_only().matches(&full_device(), &policy));
+    }
+
+    #[test]
+    fn weak_overlap_does_not_match_even_when_similar() {
+        let policy = DevicePolicy::default();
+        assert!(!os_only().matches(&os_only(), &policy));
+    }
+
+    #[test]
+    fn small_hardware_change_still_matches() {
+        let policy = DevicePolicy::default();
+        let mut changed = full_device();
+        changed.mac_hash = Some("new-mac".to_string());
+        assert!(changed.matches(&full_device(), &policy));
+    }
+
+    #[test]
+    fn required_component_must_be_present_and_equal() {
+        let policy = DevicePolicy { required_components: vec!["machine_id".to_string()], ..Default::default() };
+        let mut other_machine = full_device();
+        other_machine.machine_id = Some("machine-2".to_string());
+        assert!(!other_machine.matches(&full_device(), &policy));
+
+        let mut without_machine = full_device();
+        without_machine.machine_id = None;
+        assert!(!without_machine.matches(&full_device(), &policy));
+
+        assert!(full_device().matches(&full_device(), &policy));
+    }
+
+    #[test]
+    fn merge_keeps_unreported_fields() {
+        let update = DeviceIdentity { mac_hash: Some("new-mac".to_string()), ..Default::default() };
+        let merged = full_device().merged(&update);
+        assert_eq!(merged.mac_hash.as_deref(), Some("new-mac"));
+        assert_eq!(merged.machine_id.as_deref(), Some("machine-1"));
+        assert_eq!(merged.disk_hash.as_deref(), Some("disk"));
+    }
+
+    #[test]
+    fn weak_fingerprint_falls_back_to_identifier() {
+        let presented = PresentedDevice::new(None, Some(os_only()), "10.0.0.1");
+        assert!(presented.device.is_none());
+        assert_eq!(presented.identifier.as_deref(), Some("10.0.0.1"));
+        assert!(!presented.matches(&binding(None, Some(full_device())), &DevicePolicy::default()));
+        assert!(presented.matches(&binding(Some("10.0.0.1"), None), &DevicePolicy::default()));
+    }
+
+    #[test]
+    fn presented_full_device_matches_binding() {
+        let presented = PresentedDevice::new(None, Some(full_device()), "10.0.0.1");
+        assert!(presented.matches(&binding(None, Some(full_device())), &DevicePolicy::default()));
+    }
+}
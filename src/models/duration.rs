@@ -0,0 +1,86 @@
+use serde::{Deserialize, Deserializer};
+
+pub const MINUTE_SECONDS: i64 = 60;
+pub const HOUR_SECONDS: i64 = 60 * MINUTE_SECONDS;
+pub const DAY_SECONDS: i64 = 24 * HOUR_SECONDS;
+pub const WEEK_SECONDS: i64 = 7 * DAY_SECONDS;
+
+// 解析时长，返回秒数：支持 "30m"、"2h"、"7d"、"1w" 及组合 "1d12h"，单位 s/m/h/d/w；
+// 不带单位的数字按天计算，兼容原来以天为单位的输入；可带负号（如批量延期缩短有效期）
+pub fn parse_duration(input: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid duration '{}', expected e.g. 30m, 2h, 7d or 1d12h", input);
+    let trimmed = input.trim();
+    let (sign, body) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, trimmed),
+    };
+    if body.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(days) = body.parse::<i64>() {
+        return days.checked_mul(DAY_SECONDS).map(|seconds| sign * seconds).ok_or_else(invalid);
+    }
+
+    let mut total: i64 = 0;
+    let mut number = String::new();
+    for c in body.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => MINUTE_SECONDS,
+            'h' => HOUR_SECONDS,
+            'd' => DAY_SECONDS,
+            'w' => WEEK_SECONDS,
+            _ => return Err(invalid()),
+        };
+        let value: i64 = number.parse().map_err(|_| invalid())?;
+        total = value.checked_mul(unit).and_then(|seconds| total.checked_add(seconds)).ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(sign * total)
+}
+
+// 将秒数格式化为 parse_duration 可解析的形式，如 "7d"、"1h30m"，0 为 "0s"
+pub fn format_duration(seconds: i64) -> String {
+    if seconds == 0 {
+        return "0s".to_string();
+    }
+    let mut rest = seconds.unsigned_abs();
+    let mut formatted = String::from(if seconds < 0 { "-" } else { "" });
+    for (unit, suffix) in [(DAY_SECONDS, 'd'), (HOUR_SECONDS, 'h'), (MINUTE_SECONDS, 'm'), (1, 's')] {
+        let unit = unit as u64;
+        if rest >= unit {
+            formatted.push_str(&format!("{}{}", rest / unit, suffix));
+            rest %= unit;
+        }
+    }
+    formatted
+}
+
+// 反序列化可选的时长：字符串按 parse_duration 解析，数字按天计算（兼容原来的 duration_days），结果为秒数
+pub fn deserialize_optional<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+
+    match Option::<StringOrNumber>::deserialize(deserializer)? {
+        Some(StringOrNumber::String(s)) => parse_duration(&s).map(Some).map_err(D::Error::custom),
+        Some(StringOrNumber::Number(days)) => days.checked_mul(DAY_SECONDS).map(Some)
+            .ok_or_else(|| D::Error::custom("duration out of range")),
+        None => Ok(None),
+    }
+}
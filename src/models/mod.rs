@@ -1,66 +1,287 @@
 pub mod user;
+pub mod bulk;  // 批量操作相关模型
 pub mod card;  // 添加 card 模块声明
+pub mod stats; // 统计相关模型
+pub mod webhook; // Webhook 相关模型
+pub mod audit; // 审计日志相关模型
+pub mod api_key; // API 密钥相关模型
+pub mod application; // 客户端应用相关模型
+pub mod device; // 设备指纹相关模型
+pub mod rebind; // 自助换绑相关模型
+pub mod customer; // 终端客户相关模型
+pub mod duration; // 时长解析与格式化
 pub use self::user::*;
 // pub use self::card::*;  // 注释掉这行，避免导入冲突
 
 use chrono::{DateTime, Utc};
-use mongodb::bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::{IntoParams, ToSchema};
+
+use self::device::{DeviceBinding, DeviceIdentity};
 
 // 卡密创建请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateCardRequest {
-    // 使用更简单的字符串处理方式
-    #[serde(deserialize_with = "deserialize_string_or_number")]
-    pub duration_days: i32,
+    // 有效期，如 "30m"、"2h"、"7d"，数字按天计算（兼容 duration_days）；指定了应用时可省略，使用应用的默认套餐
+    #[serde(default, alias = "duration_days", deserialize_with = "duration::deserialize_optional")]
+    #[schema(value_type = Option<String>, example = "7d")]
+    pub duration: Option<i64>,
     #[serde(default = "default_count")]
+    #[schema(default = 1, minimum = 1, maximum = 100)]
     pub count: i32,
+    pub app_id: Option<String>,  // 卡密所属的应用
+    #[schema(minimum = 1)]
+    pub usage_limit: Option<i64>,  // 可使用次数，指定后按次数计费；同时将 duration 设为0表示不限时间
+    // 保质期，格式与 duration 相同；超过保质期仍未激活的卡密自动过期
+    #[serde(default, deserialize_with = "duration::deserialize_optional")]
+    #[schema(value_type = Option<String>, example = "90d")]
+    pub shelf_life: Option<i64>,
+    pub valid_from: Option<DateTime<Utc>>,       // 生效时间，之前不能验证或激活
+    pub activate_before: Option<DateTime<Utc>>,  // 激活截止时间，之后不能再激活
+    pub valid_until: Option<DateTime<Utc>>,      // 固定到期时间，无论何时激活都在此时到期
 }
 
 fn default_count() -> i32 {
     1
 }
 
-// 简化的反序列化函数，可以处理字符串或数字
-fn deserialize_string_or_number<'de, D>(deserializer: D) -> Result<i32, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    use serde::de::Error;
-    
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum StringOrNumber {
-        String(String),
-        Number(i32),
-    }
-    
-    match StringOrNumber::deserialize(deserializer)? {
-        StringOrNumber::String(s) => s.parse::<i32>().map_err(D::Error::custom),
-        StringOrNumber::Number(n) => Ok(n),
-    }
-}
-
 // 卡密激活请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct ActivateCardRequest {
     pub card_number: String,
+    pub app_key: Option<String>,  // 应用标识，签名请求使用 X-App-Key，无需填写；归属应用的卡密只能通过该应用激活
+    pub email: Option<String>,    // 所有者邮箱（可选），用于接收自助换绑验证码
+}
+
+// 激活卡密响应：卡密信息附带激活时签发的所有者密钥
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivateCardResponse {
+    #[serde(flatten)]
+    pub card: Card,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_secret: Option<String>,  // 所有者密钥，只返回这一次，用于自助换绑
+}
+
+// 所有者暂停/恢复卡密请求，凭激活时返回的所有者密钥操作
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OwnerPauseRequest {
+    pub card_number: String,
+    pub owner_secret: String,
+    pub app_key: Option<String>,  // 应用标识，签名请求使用 X-App-Key，无需填写
 }
 
 // 卡密验证请求
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct VerifyCardRequest {
     pub card_number: String,
     pub user_identifier: Option<String>,  // 添加用户标识符（可选）
+    pub device: Option<DeviceIdentity>,   // 设备指纹（可选），按应用的设备策略模糊匹配已绑定的设备
+    pub email: Option<String>,            // 所有者邮箱（可选），激活时记录，用于接收自助换绑验证码
+    pub app_key: Option<String>,          // 应用标识，签名请求使用 X-App-Key，无需填写
+}
+
+fn default_consume_amount() -> i64 {
+    1
+}
+
+// 消耗次数请求，设备和应用标识与验证卡密相同
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ConsumeCardRequest {
+    #[serde(flatten)]
+    pub verify: VerifyCardRequest,
+    #[serde(default = "default_consume_amount")]
+    #[schema(default = 1, minimum = 1)]
+    pub amount: i64,  // 本次消耗的次数
+}
+
+// 消耗次数响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConsumeCardResponse {
+    pub card: Card,
+    pub consumed: i64,        // 本次消耗的次数
+    pub remaining_uses: i64,  // 消耗后剩余的次数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_secret: Option<String>,  // 本次请求激活了卡密时签发的所有者密钥
+}
+
+// 卡密状态（用于列表筛选）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CardStatus {
+    Unused,   // 未激活
+    Active,   // 已激活且未过期
+    Expired,  // 已过期
+    Revoked,  // 已撤销
+    Paused,   // 已暂停
+    Exhausted,  // 次数已用完
+}
+
+impl CardStatus {
+    // 导出文件中显示的状态名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            CardStatus::Unused => "未激活",
+            CardStatus::Active => "已激活",
+            CardStatus::Expired => "已过期",
+            CardStatus::Revoked => "已撤销",
+            CardStatus::Paused => "已暂停",
+            CardStatus::Exhausted => "已用完",
+        }
+    }
+}
+
+// 卡密列表筛选条件，列表查询与批量操作共用
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CardQuery {
+    pub status: Option<CardStatus>,
+    pub created_by: Option<String>,
+    pub batch_id: Option<String>,
+    // 按有效期筛选，格式与生成卡密时相同（兼容 duration_days）
+    #[serde(default, alias = "duration_days", deserialize_with = "duration::deserialize_optional")]
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
+    pub duration: Option<i64>,
+    pub search: Option<String>,   // 按卡号模糊搜索
+    pub app_id: Option<String>,   // 按所属应用筛选
+}
+
+impl CardQuery {
+    // 转换为 MongoDB 查询条件（不含角色范围限制）
+    pub fn to_filter(&self) -> Document {
+        let mut filter = doc! { "deleted_at_str": null };
+        let now = Utc::now().to_rfc3339();
+
+        match self.status {
+            Some(CardStatus::Unused) => {
+                filter.insert("is_activated", false);
+                filter.insert("is_revoked", doc! { "$ne": true });
+                filter.insert("shelf_expires_at_str", doc! { "$not": { "$lte": &now } });
+                filter.insert("activate_before_str", doc! { "$not": { "$lte": &now } });
+                filter.insert("valid_until_str", doc! { "$not": { "$lte": &now } });
+            }
+            Some(CardStatus::Active) => {
+                filter.insert("is_activated", true);
+                filter.insert("is_revoked", doc! { "$ne": true });
+                // 未过期或不限时间（暂停的卡密过期时间为空，不会匹配），且次数未用完
+                filter.insert("$or", vec![
+                    doc! { "expires_at_str": { "$gt": now } },
+                    doc! { "duration_seconds": 0, "expires_at_str": null, "is_paused": { "$ne": true } },
+                ]);
+                filter.insert("remaining_uses", doc! { "$not": { "$lte": 0 } });
+            }
+            Some(CardStatus::Expired) => {
+                // 激活后到期，或超过保质期、激活截止时间、固定到期时间仍未激活
+                filter.insert("$or", vec![
+                    doc! { "is_activated": true, "expires_at_str": { "$lte": &now } },
+                    doc! { "is_activated": false, "shelf_expires_at_str": { "$lte": &now } },
+                    doc! { "is_activated": false, "activate_before_str": { "$lte": &now } },
+                    doc! { "is_activated": false, "valid_until_str": { "$lte": &now } },
+                ]);
+            }
+            Some(CardStatus::Revoked) => {
+                filter.insert("is_revoked", true);
+            }
+            Some(CardStatus::Paused) => {
+                filter.insert("is_paused", true);
+                filter.insert("is_revoked", doc! { "$ne": true });
+            }
+            Some(CardStatus::Exhausted) => {
+                filter.insert("remaining_uses", doc! { "$lte": 0 });
+                filter.insert("is_revoked", doc! { "$ne": true });
+            }
+            None => {}
+        }
+        if let Some(created_by) = &self.created_by {
+            filter.insert("created_by", created_by);
+        }
+        if let Some(batch_id) = &self.batch_id {
+            filter.insert("batch_id", batch_id);
+        }
+        if let Some(duration) = self.duration {
+            filter.insert("duration_seconds", duration);
+        }
+        if let Some(app_id) = &self.app_id {
+            filter.insert("app_id", app_id);
+        }
+        if let Some(search) = self.search.as_ref().filter(|s| !s.is_empty()) {
+            filter.insert("card_number", doc! { "$regex": escape_regex(search), "$options": "i" });
+        }
+        filter
+    }
+}
+
+// 转义正则表达式中的特殊字符
+fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// 生成卡密的有效期、次数、保质期和使用时间窗口设置，同一批次的卡密共用
+#[derive(Debug, Clone, Default)]
+pub struct CardSpec {
+    pub duration_seconds: i64,
+    pub usage_limit: Option<i64>,
+    pub shelf_life_seconds: Option<i64>,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub activate_before: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl CardSpec {
+    // 校验设置：有效期为正数，按次数计费或指定了固定到期时间时可以为0；次数至少为1；保质期为正数；
+    // 激活截止时间和固定到期时间必须晚于当前时间和生效时间，激活截止时间不能晚于固定到期时间
+    pub fn validate(&self) -> Result<(), String> {
+        if self.usage_limit.is_some_and(|uses| uses < 1) {
+            return Err("usage_limit must be at least 1".to_string());
+        }
+        let untimed = self.usage_limit.is_none() && self.valid_until.is_none();
+        if self.duration_seconds < 0 || (self.duration_seconds == 0 && untimed) {
+            return Err("duration must be positive, e.g. 30m, 2h or 7d".to_string());
+        }
+        if self.shelf_life_seconds.is_some_and(|seconds| seconds <= 0) {
+            return Err("shelf_life must be positive, e.g. 30d".to_string());
+        }
+        let now = Utc::now();
+        for (name, deadline) in [("activate_before", self.activate_before), ("valid_until", self.valid_until)] {
+            let Some(deadline) = deadline else { continue };
+            if deadline <= now {
+                return Err(format!("{} must be in the future", name));
+            }
+            if self.valid_from.is_some_and(|valid_from| deadline <= valid_from) {
+                return Err(format!("{} must be later than valid_from", name));
+            }
+        }
+        if let (Some(activate_before), Some(valid_until)) = (self.activate_before, self.valid_until) {
+            if activate_before > valid_until {
+                return Err("activate_before must not be later than valid_until".to_string());
+            }
+        }
+        Ok(())
+    }
 }
 
-// 卡密模型
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn parse_time(value: &Option<String>) -> Option<DateTime<Utc>> {
+    value.as_ref().and_then(|s| s.parse::<DateTime<Utc>>().ok())
+}
+
+// 卡密模型；derive 生成的是存储字段的 Card::serialize / Card::deserialize，
+// Serialize 实现在此基础上附带 v1 兼容字段，见下方 impl Serialize for Card
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(remote = "Self")]
 pub struct Card {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
     pub id: Option<ObjectId>,  // 改回使用 id 字段名，但在序列化时重命名为 _id
     pub card_number: String,
-    pub duration_days: i32,
+    pub duration_seconds: i64,                // 有效期（秒），按次数计费的卡密为0时不限时间
     pub is_activated: bool,
     pub activated_at_str: Option<String>,
     pub expires_at_str: Option<String>,
@@ -68,7 +289,36 @@ pub struct Card {
     pub created_by: Option<String>,           // 添加创建者ID
     pub created_by_username: Option<String>,  // 添加创建者用户名
     pub used_by: Option<String>,              // 添加使用者标识
-    pub used_by_identifier: Option<String>,   // 添加使用者唯一标识符（如设备ID、IP等）
+    #[serde(default)]
+    pub bindings: Vec<DeviceBinding>,         // 已绑定的设备，数量受应用设备策略的 max_devices 限制
+    pub batch_id: Option<String>,             // 生成批次ID，同一次生成的卡密共享
+    pub app_id: Option<String>,               // 所属应用ID，为空表示未归属任何应用（验证时无需应用标识）
+    #[serde(default)]
+    pub is_expired: bool,                     // 由定时任务标记的过期状态
+    pub expired_at_str: Option<String>,       // 标记过期的时间
+    #[serde(default)]
+    pub expiry_notified: bool,                // 是否已发送到期提醒
+    pub deleted_at_str: Option<String>,       // 删除时间（软删除），为空表示未删除
+    pub deleted_by: Option<String>,           // 删除操作者用户名
+    #[serde(default)]
+    pub is_revoked: bool,                     // 是否已撤销，撤销后无法再通过验证
+    pub revoked_at_str: Option<String>,       // 撤销时间
+    pub revoked_by: Option<String>,           // 撤销操作者用户名
+    pub last_heartbeat_at_str: Option<String>,  // 客户端最近一次心跳时间
+    pub customer_id: Option<String>,          // 兑换到的客户账号ID
+    pub redeemed_at_str: Option<String>,      // 兑换到客户账号的时间
+    #[serde(default)]
+    pub is_paused: bool,                      // 是否已暂停，暂停期间不计时，expires_at_str 为空
+    pub paused_at_str: Option<String>,        // 暂停时间
+    pub paused_remaining_seconds: Option<i64>,  // 暂停时剩余的有效时间（秒），恢复时据此重新计算过期时间
+    pub paused_by: Option<String>,            // 暂停操作者（后台用户名，或所有者、客户）
+    pub usage_limit: Option<i64>,             // 可使用的总次数，为空表示不按次数计费
+    pub remaining_uses: Option<i64>,          // 剩余次数，每次消耗时原子扣减
+    pub exhausted_at_str: Option<String>,     // 次数用完的时间
+    pub shelf_expires_at_str: Option<String>, // 保质期截止时间，之后仍未激活的卡密自动过期，为空表示不限
+    pub valid_from_str: Option<String>,       // 生效时间，之前不能验证或激活
+    pub activate_before_str: Option<String>,  // 激活截止时间，之后不能再激活
+    pub valid_until_str: Option<String>,      // 固定到期时间，激活后的过期时间不晚于此时间
     
     #[serde(skip_serializing, skip_deserializing)]
     pub activated_at: Option<DateTime<Utc>>,
@@ -78,13 +328,49 @@ pub struct Card {
     pub created_at: DateTime<Utc>,
 }
 
+// 只包含存储字段的序列化形式，用于写入数据库
+struct StoredCard<'a>(&'a Card);
+
+impl Serialize for StoredCard<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Card::serialize(self.0, serializer)
+    }
+}
+
+// 序列化为 API 响应、Webhook 和导出数据时，在存储字段之外附带 v1 兼容字段：
+// used_by_identifier 为第一个绑定设备的标识（原来的单设备绑定字段），duration_days 为有效期的整天数（向下取整）
+impl Serialize for Card {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct WithLegacy<'a> {
+            #[serde(flatten)]
+            card: StoredCard<'a>,
+            used_by_identifier: Option<&'a str>,
+            duration_days: i64,
+        }
+
+        WithLegacy {
+            card: StoredCard(self),
+            used_by_identifier: self.bindings.first().and_then(|binding| binding.identifier.as_deref()),
+            duration_days: self.duration_days(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Card {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Card::deserialize(deserializer)
+    }
+}
+
 impl Card {
-    pub fn new(card_number: String, duration_days: i32) -> Self {
+    pub fn new(card_number: String, duration_seconds: i64) -> Self {
         let now = Utc::now();
         Self {
             id: None,  // 使用 id 字段
             card_number,
-            duration_days,
+            duration_seconds,
             is_activated: false,
             activated_at: None,
             expires_at: None,
@@ -95,30 +381,191 @@ impl Card {
             created_by: None,           // 初始化为None
             created_by_username: None,  // 初始化为None
             used_by: None,              // 初始化为None
-            used_by_identifier: None,   // 初始化为None
+            bindings: Vec::new(),
+            batch_id: None,             // 初始化为None
+            app_id: None,
+            is_expired: false,
+            expired_at_str: None,
+            expiry_notified: false,
+            deleted_at_str: None,
+            deleted_by: None,
+            is_revoked: false,
+            revoked_at_str: None,
+            revoked_by: None,
+            last_heartbeat_at_str: None,
+            customer_id: None,
+            redeemed_at_str: None,
+            is_paused: false,
+            paused_at_str: None,
+            paused_remaining_seconds: None,
+            paused_by: None,
+            usage_limit: None,
+            remaining_uses: None,
+            exhausted_at_str: None,
+            shelf_expires_at_str: None,
+            valid_from_str: None,
+            activate_before_str: None,
+            valid_until_str: None,
         }
     }
     
-    pub fn with_activation(&self, now: DateTime<Utc>, user_id: Option<String>, identifier: Option<String>) -> Self {
-        let expires_at = now + chrono::Duration::days(self.duration_days as i64);
+    // 有效期的整天数（向下取整），兼容 v1 的 duration_days
+    pub fn duration_days(&self) -> i64 {
+        self.duration_seconds / duration::DAY_SECONDS
+    }
+
+    // 写入数据库的文档，只包含存储字段，不包含 v1 兼容的派生字段
+    pub fn to_document(&self) -> Result<Document, mongodb::bson::ser::Error> {
+        mongodb::bson::to_document(&StoredCard(self))
+    }
+
+    // 是否有时间限制，按次数计费、有效期为0且没有固定到期时间的卡密不限时间
+    pub fn is_time_limited(&self) -> bool {
+        self.duration_seconds > 0 || self.valid_until_str.is_some() || self.usage_limit.is_none()
+    }
+
+    // 未激活的卡密是否已超过保质期
+    pub fn is_shelf_expired(&self, now: DateTime<Utc>) -> bool {
+        !self.is_activated && parse_time(&self.shelf_expires_at_str).is_some_and(|shelf_expires_at| shelf_expires_at <= now)
+    }
+
+    // 是否尚未到生效时间
+    pub fn is_not_yet_valid(&self, now: DateTime<Utc>) -> bool {
+        parse_time(&self.valid_from_str).is_some_and(|valid_from| valid_from > now)
+    }
+
+    // 是否已超过固定到期时间
+    pub fn is_validity_ended(&self, now: DateTime<Utc>) -> bool {
+        parse_time(&self.valid_until_str).is_some_and(|valid_until| valid_until <= now)
+    }
+
+    // 未激活的卡密是否已超过激活截止时间
+    pub fn is_activation_closed(&self, now: DateTime<Utc>) -> bool {
+        !self.is_activated && parse_time(&self.activate_before_str).is_some_and(|activate_before| activate_before <= now)
+    }
+
+    // 次数是否已用完
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining_uses.is_some_and(|uses| uses <= 0)
+    }
+
+    // 当前状态：撤销优先，其次是暂停，再按激活、过期时间和剩余次数判断
+    pub fn status(&self, now: DateTime<Utc>) -> CardStatus {
+        let expired = self.is_time_limited() && self.expires_at_str.as_ref()
+            .and_then(|s| s.parse::<DateTime<Utc>>().ok())
+            .is_none_or(|expires_at| expires_at <= now);
+        if self.is_revoked {
+            CardStatus::Revoked
+        } else if self.is_paused {
+            CardStatus::Paused
+        } else if !self.is_activated && (self.is_shelf_expired(now) || self.is_activation_closed(now) || self.is_validity_ended(now)) {
+            CardStatus::Expired
+        } else if !self.is_activated {
+            CardStatus::Unused
+        } else if expired {
+            CardStatus::Expired
+        } else if self.is_exhausted() {
+            CardStatus::Exhausted
+        } else {
+            CardStatus::Active
+        }
+    }
+
+    // 失效时间：激活后的过期时间，未激活时已超过的保质期、激活截止时间、固定到期时间中最早的一个，或次数用完的时间；
+    // 与 status 一致，未过期或未用完时为空
+    pub fn expired_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.status(now) {
+            CardStatus::Expired if self.is_activated => parse_time(&self.expires_at_str),
+            CardStatus::Expired => [&self.shelf_expires_at_str, &self.activate_before_str, &self.valid_until_str]
+                .into_iter()
+                .filter_map(parse_time)
+                .filter(|deadline| *deadline <= now)
+                .min(),
+            CardStatus::Exhausted => parse_time(&self.exhausted_at_str),
+            _ => None,
+        }
+    }
+
+    pub fn with_activation(&self, now: DateTime<Utc>, user_id: Option<String>, binding: Option<DeviceBinding>) -> Self {
+        // 过期时间为激活时间加有效期，有固定到期时间时不晚于固定到期时间；不限时间的卡密激活后没有过期时间
+        let relative = Some(now + chrono::Duration::seconds(self.duration_seconds)).filter(|_| self.duration_seconds > 0);
+        let expires_at = match (relative, parse_time(&self.valid_until_str)) {
+            (Some(relative), Some(valid_until)) => Some(relative.min(valid_until)),
+            (relative, valid_until) => relative.or(valid_until),
+        };
         Self {
-            id: self.id,  // 使用 id 字段
-            card_number: self.card_number.clone(),
-            duration_days: self.duration_days,
             is_activated: true,
             activated_at: Some(now),
-            expires_at: Some(expires_at),
-            created_at: self.created_at,
+            expires_at,
             activated_at_str: Some(now.to_rfc3339()),
-            expires_at_str: Some(expires_at.to_rfc3339()),
-            created_at_str: self.created_at_str.clone(),
-            created_by: self.created_by.clone(),           // 保留创建者ID
-            created_by_username: self.created_by_username.clone(),  // 保留创建者用户名
+            expires_at_str: expires_at.map(|expires_at| expires_at.to_rfc3339()),
             used_by: user_id,                              // 设置使用者ID
-            used_by_identifier: identifier,                // 设置使用者标识符
+            bindings: binding.into_iter().collect(),       // 绑定首次验证的设备
+            ..self.clone()                                 // 其余字段（创建者、批次等）保持不变
         }
     }
 }
 
 // 删除这行，避免重复导入
-// pub use self::card::CreateCardRequest; 
\ No newline at end of file
+// pub use self::card::CreateCardRequest; 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound_card() -> Card {
+        let mut card = Card::new("1pZKyLAywgUOEUqd".to_string(), 30 * 86_400);
+        card.bindings.push(DeviceBinding {
+            identifier: Some("device_id_123".to_string()),
+            device: None,
+            bound_at_str: "2024-01-01T00:00:00+00:00".to_string(),
+            last_seen_at_str: None,
+        });
+        card
+    }
+
+    #[test]
+    fn json_includes_v1_fields() {
+        let json = serde_json::to_value(bound_card()).unwrap();
+        assert_eq!(json["used_by_identifier"], "device_id_123");
+        assert_eq!(json["duration_days"], 30);
+        assert_eq!(json["card_number"], "1pZKyLAywgUOEUqd");
+
+        let unbound = serde_json::to_value(Card::new("x".to_string(), 0)).unwrap();
+        assert!(unbound["used_by_identifier"].is_null());
+    }
+
+    #[test]
+    fn stored_document_omits_v1_fields() {
+        let document = bound_card().to_document().unwrap();
+        assert!(!document.contains_key("used_by_identifier"));
+        assert!(!document.contains_key("duration_days"));
+        assert_eq!(document.get_str("card_number").unwrap(), "1pZKyLAywgUOEUqd");
+    }
+
+    #[test]
+    fn duration_days_rounds_down() {
+        assert_eq!(Card::new("x".to_string(), 2 * 3_600).duration_days(), 0);
+        assert_eq!(Card::new("x".to_string(), 36 * 3_600).duration_days(), 1);
+        assert_eq!(Card::new("x".to_string(), 0).duration_days(), 0);
+    }
+
+    #[test]
+    fn activate_response_flattens_card() {
+        let json = serde_json::to_value(ActivateCardResponse {
+            card: bound_card(),
+            owner_secret: Some("own_secret".to_string()),
+        })
+        .unwrap();
+        assert_eq!(json["card_number"], "1pZKyLAywgUOEUqd");
+        assert_eq!(json["used_by_identifier"], "device_id_123");
+        assert_eq!(json["owner_secret"], "own_secret");
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let json = serde_json::to_string(&bound_card()).unwrap();
+        let card: Card = serde_json::from_str(&json).unwrap();
+        assert_eq!(card.bindings.len(), 1);
+        assert_eq!(card.duration_seconds, 30 * 86_400);
+    }
+}
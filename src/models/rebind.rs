@@ -0,0 +1,91 @@
+use mongodb::bson::{self, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::device::{DeviceBinding, DeviceIdentity};
+use super::Card;
+
+// 所有者密钥前缀
+pub const OWNER_SECRET_PREFIX: &str = "own_";
+
+// 卡密所有者凭证：首次验证激活卡密时签发所有者密钥，只保存哈希；用于自助换绑
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardOwner {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub card_id: String,
+    pub card_number: String,
+    pub secret_hash: String,     // 所有者密钥的 SHA-256 哈希
+    pub email: Option<String>,   // 接收换绑验证码的邮箱，激活时提供
+    pub created_at_str: String,
+}
+
+// 换绑邮箱验证码，每张卡密同时只有一个有效验证码，过期后由 TTL 索引自动删除
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebindCode {
+    #[serde(rename = "_id")]
+    pub card_id: String,
+    pub code_hash: String,
+    pub attempts: i32,              // 已尝试次数，超过上限后验证码失效
+    pub created_at_str: String,
+    pub expires_at: bson::DateTime,
+}
+
+// 换绑的认证方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RebindMethod {
+    OwnerSecret,  // 所有者密钥
+    EmailCode,    // 邮箱验证码
+    Customer,     // 客户账号中管理设备
+}
+
+// 换绑记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RebindRecord {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    pub card_id: String,
+    pub card_number: String,
+    pub method: RebindMethod,
+    pub previous_bindings: Vec<DeviceBinding>,  // 换绑前的设备绑定
+    pub binding: Option<DeviceBinding>,         // 换绑后的设备，客户账号中解绑设备时为空
+    pub penalty_hours: u32,                     // 本次扣除的有效时间（小时）
+    pub expires_at_str: Option<String>,         // 换绑后的过期时间
+    pub client_ip: String,
+    pub created_at_str: String,
+}
+
+// 申请换绑邮箱验证码请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RebindCodeRequest {
+    pub card_number: String,
+    pub app_key: Option<String>,  // 应用标识，签名请求使用 X-App-Key，无需填写
+}
+
+// 自助换绑请求，所有者密钥和邮箱验证码提供其一
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RebindRequest {
+    pub card_number: String,
+    pub owner_secret: Option<String>,     // 激活时返回的所有者密钥
+    pub email_code: Option<String>,       // 邮箱验证码
+    pub user_identifier: Option<String>,  // 新设备的使用者标识，与验证卡密时相同
+    pub device: Option<DeviceIdentity>,   // 新设备的指纹，与验证卡密时相同
+    pub app_key: Option<String>,          // 应用标识，签名请求使用 X-App-Key，无需填写
+}
+
+// 自助换绑响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RebindResponse {
+    pub card: Card,
+    pub penalty_hours: u32,      // 本次扣除的有效时间（小时）
+    pub remaining_rebinds: u32,  // 当前周期内剩余的换绑次数
+}
+
+// 重新签发所有者密钥的响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OwnerSecretResponse {
+    pub card_number: String,
+    pub owner_secret: String,  // 新的所有者密钥，只返回这一次，原密钥立即失效
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::Card;
+
+// 统计查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    pub days: Option<i64>,           // 按天统计的回溯天数，默认30天
+    pub expiring_days: Option<i64>,  // 即将过期的统计窗口（天），默认7天
+    pub top: Option<i64>,            // 排行榜数量，默认10
+    pub app_id: Option<String>,      // 只统计指定应用的卡密
+}
+
+// 每日统计
+#[derive(Debug, Serialize, Default, Clone, ToSchema)]
+pub struct DailyStats {
+    pub date: String,
+    pub generated: i64,
+    pub activated: i64,
+    pub expired: i64,
+}
+
+// 激活率统计（按批次/套餐/创建者分组）
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ActivationRate {
+    pub key: String,
+    pub total: i64,
+    pub activated: i64,
+    #[serde(default)]
+    pub rate: f64,
+}
+
+// 代理商排行
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ResellerStats {
+    pub created_by: Option<String>,
+    pub username: String,
+    pub generated: i64,
+    pub activated: i64,
+}
+
+// 统计汇总响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsResponse {
+    pub total_cards: i64,
+    pub activated_cards: i64,
+    pub active_licenses: i64,
+    pub expiring_soon: i64,
+    pub daily: Vec<DailyStats>,
+    pub activation_by_batch: Vec<ActivationRate>,
+    pub activation_by_plan: Vec<ActivationRate>,
+    pub activation_by_creator: Vec<ActivationRate>,
+    pub upcoming_expirations: Vec<Card>,
+    pub top_resellers: Vec<ResellerStats>,
+}
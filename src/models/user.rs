@@ -1,8 +1,9 @@
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum UserRole {
     #[serde(alias = "ADMIN", alias = "Admin")]
@@ -22,11 +23,16 @@ pub struct User {
     pub created_at_str: String,
     pub updated_at_str: String,
     
+    #[allow(dead_code)]
     #[serde(skip_serializing, skip_deserializing)]
     pub created_at: DateTime<Utc>,
+    #[allow(dead_code)]
     #[serde(skip_serializing, skip_deserializing)]
     pub updated_at: DateTime<Utc>,
     pub last_token: Option<String>,
+    pub token_expires_at_str: Option<String>,  // 最新令牌的过期时间，过期后由定时任务清除
+    pub deleted_at_str: Option<String>,  // 删除时间（软删除），为空表示未删除
+    pub deleted_by: Option<String>,      // 删除操作者用户名
 }
 
 impl User {
@@ -43,11 +49,14 @@ impl User {
             created_at_str: now.to_rfc3339(),
             updated_at_str: now.to_rfc3339(),
             last_token: None,
+            token_expires_at_str: None,
+            deleted_at_str: None,
+            deleted_by: None,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserInfo {
     pub id: String,
     pub username: String,
@@ -66,7 +75,7 @@ impl From<User> for UserInfo {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateUserRequest {
     pub username: String,
     pub password: String,
@@ -74,19 +83,19 @@ pub struct CreateUserRequest {
     pub role: UserRole,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
     pub user: UserInfo,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateUserRequest {
     pub username: Option<String>,
     pub password: Option<String>,
@@ -0,0 +1,165 @@
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+// 卡密生命周期事件类型
+pub const EVENT_CARD_ACTIVATED: &str = "card.activated";
+pub const EVENT_CARD_EXPIRED: &str = "card.expired";
+pub const EVENT_CARD_EXPIRING: &str = "card.expiring";
+pub const EVENT_CARD_REVOKED: &str = "card.revoked";
+pub const EVENT_CARD_REBIND_CODE: &str = "card.rebind_code";
+pub const EVENT_CARD_REBOUND: &str = "card.rebound";
+pub const EVENT_CARD_PAUSED: &str = "card.paused";
+pub const EVENT_CARD_RESUMED: &str = "card.resumed";
+pub const EVENT_CARD_EXHAUSTED: &str = "card.exhausted";
+pub const EVENT_CUSTOMER_LOGIN_CODE: &str = "customer.login_code";
+pub const EVENT_PING: &str = "webhook.ping";
+
+// 可订阅的事件列表，"*" 表示订阅全部事件
+pub const SUPPORTED_EVENTS: &[&str] = &[
+    EVENT_CARD_ACTIVATED,
+    EVENT_CARD_EXPIRED,
+    EVENT_CARD_EXPIRING,
+    EVENT_CARD_REVOKED,
+    EVENT_CARD_REBIND_CODE,
+    EVENT_CARD_REBOUND,
+    EVENT_CARD_PAUSED,
+    EVENT_CARD_RESUMED,
+    EVENT_CARD_EXHAUSTED,
+    EVENT_CUSTOMER_LOGIN_CODE,
+];
+
+// 包含验证码的事件，订阅 "*" 时不投递，需要明确订阅
+const SENSITIVE_EVENTS: &[&str] = &[EVENT_CARD_REBIND_CODE, EVENT_CUSTOMER_LOGIN_CODE];
+
+// 投递状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,    // 等待投递（包括等待重试）
+    Succeeded,  // 投递成功
+    Failed,     // 重试次数用尽，投递失败
+}
+
+// Webhook 端点配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub url: String,
+    pub secret: String,            // HMAC 签名密钥
+    pub events: Vec<String>,       // 订阅的事件类型
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at_str: String,
+    pub updated_at_str: String,
+}
+
+impl Webhook {
+    pub fn new(url: String, secret: String, events: Vec<String>, description: Option<String>, created_by: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: None,
+            url,
+            secret,
+            events,
+            enabled: true,
+            description,
+            created_by,
+            created_at_str: now.clone(),
+            updated_at_str: now,
+        }
+    }
+
+    // 是否订阅了指定事件；包含验证码的事件只投递给明确订阅的 Webhook
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        event == EVENT_PING
+            || self.events.iter().any(|e| e == event || (e == "*" && !SENSITIVE_EVENTS.contains(&event)))
+    }
+}
+
+// 返回给客户端的 Webhook 信息（不包含密钥）
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookInfo {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at_str: String,
+    pub updated_at_str: String,
+}
+
+impl From<Webhook> for WebhookInfo {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id.map(|id| id.to_hex()).unwrap_or_default(),
+            url: webhook.url,
+            events: webhook.events,
+            enabled: webhook.enabled,
+            description: webhook.description,
+            created_by: webhook.created_by,
+            created_at_str: webhook.created_at_str,
+            updated_at_str: webhook.updated_at_str,
+        }
+    }
+}
+
+// 单次投递尝试记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryAttempt {
+    pub attempted_at_str: String,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+}
+
+// 投递队列中的一条记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub id: Option<ObjectId>,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: String,               // 投递的 JSON 原文，签名基于此内容
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at_str: String,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub logs: Vec<DeliveryAttempt>,
+    pub created_at_str: String,
+    pub updated_at_str: String,
+}
+
+// 创建 Webhook 请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: Option<String>,   // 不提供则自动生成
+    pub description: Option<String>,
+}
+
+// 更新 Webhook 请求
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rotate_secret: bool,       // 是否重新生成签名密钥
+}
+
+// 投递记录查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQuery {
+    pub status: Option<DeliveryStatus>,
+    pub limit: Option<i64>,
+}
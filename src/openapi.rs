@@ -0,0 +1,182 @@
+use serde::Serialize;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::handlers;
+use crate::models::application::ApplicationInfo;
+use crate::models::webhook::WebhookInfo;
+use crate::models::{Card, UserInfo};
+
+// OpenAPI 文档，由各处理函数上的 #[utoipa::path] 和 models 中的类型生成；新增路由时在此登记
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Card Platform API",
+        description = "卡密管理系统接口。\n\n业务接口以 /api/v1 为前缀（未带版本的 /api 与 v1 相同），v1 已废弃，响应头中带有 Deprecation 和 Link。\n\n/api/v2 提供与 v1 相同的接口，响应统一包装为 Envelope：{ success, code, message, data, request_id }，其中 data 为对应 v1 接口的响应体；验证卡密等接口的失败响应带有具体的错误码。",
+    ),
+    paths(
+        handlers::v2::verify_card,
+        handlers::health::liveness,
+        handlers::health::readiness,
+        handlers::metrics::get_metrics,
+    ),
+    nest((path = "/api/v1", api = ApiV1)),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "cards", description = "卡密生成、激活、验证与管理"),
+        (name = "users", description = "登录与用户管理"),
+        (name = "stats", description = "统计数据"),
+        (name = "webhooks", description = "Webhook 配置与投递记录（仅管理员）"),
+        (name = "jobs", description = "定时任务（仅管理员）"),
+        (name = "api-keys", description = "API 密钥管理（仅限登录令牌）"),
+        (name = "applications", description = "客户端应用与签名密钥（仅管理员）"),
+        (name = "audit", description = "管理操作审计日志（仅管理员）"),
+        (name = "customers", description = "终端客户账号与授权管理，使用客户令牌"),
+        (name = "health", description = "健康检查与监控指标"),
+    ),
+)]
+pub struct ApiDoc;
+
+// v1 业务接口，路径相对于 /api/v1
+#[derive(OpenApi)]
+#[openapi(paths(
+    handlers::card::generate_card,
+    handlers::card::activate_card,
+    handlers::card::verify_card,
+    handlers::card::heartbeat,
+    handlers::card::consume_card,
+    handlers::rebind::request_rebind_code,
+    handlers::rebind::rebind_card,
+    handlers::rebind::reissue_owner_secret,
+    handlers::pause::owner_pause_card,
+    handlers::pause::owner_resume_card,
+    handlers::card::get_all_cards,
+    handlers::card::export_cards,
+    handlers::card::get_trash,
+    handlers::bulk::bulk_cards,
+    handlers::bulk::get_bulk_job,
+    handlers::card::delete_card,
+    handlers::card::restore_card,
+    handlers::pause::pause_card,
+    handlers::pause::resume_card,
+    handlers::user::register_user,
+    handlers::user::login,
+    handlers::user::get_current_user,
+    handlers::user::get_current_user_legacy,
+    handlers::user::get_all_users,
+    handlers::user::get_trash,
+    handlers::user::get_user,
+    handlers::user::update_user,
+    handlers::user::delete_user,
+    handlers::user::restore_user,
+    handlers::stats::get_stats,
+    handlers::webhook::create_webhook,
+    handlers::webhook::get_all_webhooks,
+    handlers::webhook::get_webhook,
+    handlers::webhook::update_webhook,
+    handlers::webhook::delete_webhook,
+    handlers::webhook::get_deliveries,
+    handlers::webhook::redeliver,
+    handlers::webhook::test_webhook,
+    handlers::job::get_jobs,
+    handlers::job::run_job,
+    handlers::api_key::create_api_key,
+    handlers::api_key::get_api_keys,
+    handlers::api_key::revoke_api_key,
+    handlers::application::create_application,
+    handlers::application::get_all_applications,
+    handlers::application::get_application,
+    handlers::application::update_application,
+    handlers::application::delete_application,
+    handlers::audit::get_audit_logs,
+    handlers::customer::register,
+    handlers::customer::login,
+    handlers::customer::request_login_code,
+    handlers::customer::login_with_code,
+    handlers::customer::get_me,
+    handlers::customer::get_licenses,
+    handlers::customer::redeem_card,
+    handlers::customer::remove_binding,
+    handlers::customer::pause_license,
+    handlers::customer::resume_license,
+))]
+struct ApiV1;
+
+// 登录接口返回的 JWT 或 API 密钥，通过 Authorization: Bearer <token> 传递；API 密钥也可通过 X-API-Key 头传递；
+// 客户接口使用客户登录返回的令牌
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+        components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        components.add_security_scheme(
+            "customer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+// 以下类型只用于描述处理函数中以 json! 构造的响应
+
+// 卡密被拒绝时的响应（已激活、已过期、已撤销或已被其他用户使用）
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct CardRejection {
+    pub error: String,
+    pub message: String,
+    pub card: Card,
+}
+
+// 验证卡密的响应：已激活的卡密直接返回卡密，首次验证时返回激活结果
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum VerifyCardResponse {
+    Verified(Card),
+    Activated { message: String, card: Card, owner_secret: Option<String> },
+}
+
+// 批量操作转为后台任务时的响应
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct BulkJobAccepted {
+    pub job_id: String,
+    pub total: i64,
+}
+
+// 回收站中的用户
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct TrashedUser {
+    pub user: UserInfo,
+    pub deleted_at_str: Option<String>,
+    pub deleted_by: Option<String>,
+}
+
+// 创建 Webhook 的响应，secret 只在此时返回
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct WebhookCreated {
+    pub webhook: WebhookInfo,
+    pub secret: String,
+}
+
+// 创建应用的响应，secret 只在此时返回
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct ApplicationCreated {
+    pub application: ApplicationInfo,
+    pub secret: String,
+}
+
+// 重新生成 Webhook 或应用签名密钥的响应
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct SecretRotated {
+    pub secret: String,
+}
@@ -0,0 +1,465 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use log::{info, error};
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use mongodb::{Collection, Database};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+use crate::auth::Claims;
+use crate::config::Config;
+use crate::logging::Logger;
+use crate::shutdown::Shutdown;
+use crate::models::webhook::{DeliveryStatus, EVENT_CARD_EXPIRED, EVENT_CARD_EXPIRING};
+use crate::models::{Card, CardQuery, CardStatus, User};
+use crate::webhook::{card_event_data, dispatch_event};
+
+type JobResult = Result<String, Box<dyn std::error::Error + Send + Sync>>;
+
+// 单次处理的卡密数量上限，剩余的留到下一轮
+const JOB_BATCH_SIZE: i64 = 500;
+// 到期提醒的提前天数
+const EXPIRY_NOTICE_DAYS: i64 = 3;
+// 任务实际执行时间允许滞后于计划时间的秒数，超过则认为调度器已停止
+const SCHEDULE_LAG_TOLERANCE_SECS: i64 = 60;
+// 单次任务执行时间上限，超过则认为任务卡住
+const JOB_STUCK_AFTER_SECS: i64 = 30 * 60;
+
+// 定时任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Job {
+    ExpireCards,      // 将已到期的卡密标记为过期并发送 card.expired 事件
+    NotifyExpiring,   // 对即将到期的卡密发送 card.expiring 事件
+    PruneDeliveries,  // 清理过期的 Webhook 投递记录
+    PurgeTrash,       // 彻底删除超过保留期的回收站数据
+    PruneAuditLogs,   // 清理过期的审计日志
+    PurgeSessions,    // 清除已过期的登录会话
+    RotateLogs,       // 日志文件轮转
+}
+
+impl Job {
+    pub const ALL: [Job; 7] = [
+        Job::ExpireCards,
+        Job::NotifyExpiring,
+        Job::PruneDeliveries,
+        Job::PurgeTrash,
+        Job::PruneAuditLogs,
+        Job::PurgeSessions,
+        Job::RotateLogs,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::ExpireCards => "expire_cards",
+            Job::NotifyExpiring => "notify_expiring",
+            Job::PruneDeliveries => "prune_deliveries",
+            Job::PurgeTrash => "purge_trash",
+            Job::PruneAuditLogs => "prune_audit_logs",
+            Job::PurgeSessions => "purge_sessions",
+            Job::RotateLogs => "rotate_logs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Job> {
+        Job::ALL.into_iter().find(|job| job.name() == name)
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Job::ExpireCards => "Mark expired cards and emit card.expired events",
+            Job::NotifyExpiring => "Emit card.expiring events for cards expiring within 3 days",
+            Job::PruneDeliveries => "Delete finished webhook delivery logs past retention",
+            Job::PurgeTrash => "Permanently delete trashed cards and users past TRASH_RETENTION_DAYS",
+            Job::PruneAuditLogs => "Delete audit logs past AUDIT_RETENTION_DAYS",
+            Job::PurgeSessions => "Clear stored login tokens that have expired",
+            Job::RotateLogs => "Rotate the log file when it exceeds LOG_FILE_SIZE",
+        }
+    }
+
+    // 执行间隔
+    fn interval(&self) -> std::time::Duration {
+        match self {
+            Job::ExpireCards => std::time::Duration::from_secs(60),
+            Job::NotifyExpiring => std::time::Duration::from_secs(15 * 60),
+            Job::PruneDeliveries => std::time::Duration::from_secs(6 * 3600),
+            Job::PurgeTrash => std::time::Duration::from_secs(6 * 3600),
+            Job::PruneAuditLogs => std::time::Duration::from_secs(6 * 3600),
+            Job::PurgeSessions => std::time::Duration::from_secs(3600),
+            Job::RotateLogs => std::time::Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+// 任务状态，供管理员查看
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub run_count: u64,
+    pub last_started_at_str: Option<String>,
+    pub last_finished_at_str: Option<String>,
+    pub last_duration_ms: Option<i64>,
+    pub last_result: Option<String>,
+    pub last_error: Option<String>,
+    pub next_run_at_str: Option<String>,
+}
+
+// 进程内定时任务调度器，克隆后共享同一份任务状态
+#[derive(Clone)]
+pub struct Scheduler {
+    db: Database,
+    config: Arc<Config>,
+    logger: Arc<Logger>,
+    statuses: Arc<Mutex<HashMap<Job, JobStatus>>>,
+    started: Arc<AtomicBool>,
+    shutdown: Shutdown,
+}
+
+impl Scheduler {
+    pub fn new(db: Database, config: Arc<Config>, logger: Arc<Logger>, shutdown: Shutdown) -> Self {
+        let statuses = Job::ALL
+            .into_iter()
+            .map(|job| {
+                (job, JobStatus {
+                    name: job.name(),
+                    description: job.description(),
+                    interval_secs: job.interval().as_secs(),
+                    running: false,
+                    run_count: 0,
+                    last_started_at_str: None,
+                    last_finished_at_str: None,
+                    last_duration_ms: None,
+                    last_result: None,
+                    last_error: None,
+                    next_run_at_str: None,
+                })
+            })
+            .collect();
+
+        Self {
+            db,
+            config,
+            logger,
+            statuses: Arc::new(Mutex::new(statuses)),
+            started: Arc::new(AtomicBool::new(false)),
+            shutdown,
+        }
+    }
+
+    // 为每个任务启动一个后台循环
+    pub fn start(&self) {
+        let now = Utc::now().to_rfc3339();
+        for job in Job::ALL {
+            // 首次执行在启动后立即进行
+            self.update(job, |status| status.next_run_at_str = Some(now.clone()));
+            let scheduler = self.clone();
+            actix_web::rt::spawn(async move {
+                let mut interval = actix_web::rt::time::interval(job.interval());
+                loop {
+                    interval.tick().await;
+                    // 停机后不再开始新的执行
+                    if scheduler.shutdown.is_requested() {
+                        break;
+                    }
+                    scheduler.run(job).await;
+                    let next = Utc::now() + Duration::from_std(job.interval()).unwrap_or_else(|_| Duration::zero());
+                    scheduler.update(job, |status| status.next_run_at_str = Some(next.to_rfc3339()));
+                }
+            });
+        }
+        self.started.store(true, Ordering::SeqCst);
+        info!("Scheduler started with {} jobs", Job::ALL.len());
+    }
+
+    // 调度器是否正常运行：已启动，且每个任务要么正在执行且未超时，要么下一次执行时间没有明显滞后
+    pub fn is_alive(&self) -> bool {
+        if !self.started.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = Utc::now();
+        let parse = |s: &Option<String>| s.as_deref().and_then(|s| s.parse::<DateTime<Utc>>().ok());
+
+        self.statuses.lock().unwrap().values().all(|status| {
+            if status.running {
+                parse(&status.last_started_at_str)
+                    .is_some_and(|started| now - started < Duration::seconds(JOB_STUCK_AFTER_SECS))
+            } else {
+                parse(&status.next_run_at_str)
+                    .is_some_and(|next| now - next < Duration::seconds(SCHEDULE_LAG_TOLERANCE_SECS))
+            }
+        })
+    }
+
+    // 获取所有任务状态
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        let statuses = self.statuses.lock().unwrap();
+        Job::ALL.iter().filter_map(|job| statuses.get(job).cloned()).collect()
+    }
+
+    fn update(&self, job: Job, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.statuses.lock().unwrap().get_mut(&job) {
+            f(status);
+        }
+    }
+
+    // 执行一次任务，同一任务正在运行或已开始停机时直接跳过；返回是否实际执行
+    pub async fn run(&self, job: Job) -> bool {
+        if self.shutdown.is_requested() {
+            return false;
+        }
+        let _active = self.shutdown.track();
+        let started = Utc::now();
+        {
+            let mut statuses = self.statuses.lock().unwrap();
+            let Some(status) = statuses.get_mut(&job) else { return false };
+            if status.running {
+                return false;
+            }
+            status.running = true;
+            status.last_started_at_str = Some(started.to_rfc3339());
+        }
+
+        let result = match job {
+            Job::ExpireCards => expire_cards(&self.db).await,
+            Job::NotifyExpiring => notify_expiring(&self.db).await,
+            Job::PruneDeliveries => prune_deliveries(&self.db, self.config.webhook.delivery_retention_days).await,
+            Job::PurgeTrash => purge_trash(&self.db, self.config.trash.retention_days).await,
+            Job::PruneAuditLogs => prune_audit_logs(&self.db, self.config.audit.retention_days).await,
+            Job::PurgeSessions => purge_sessions(&self.db, &self.config.auth.jwt_secret).await,
+            Job::RotateLogs => rotate_logs(&self.logger),
+        };
+
+        let finished = Utc::now();
+        match &result {
+            Ok(summary) => info!("Job '{}' finished: {}", job.name(), summary),
+            Err(e) => error!("Job '{}' failed: {}", job.name(), e),
+        }
+        self.update(job, |status| {
+            status.running = false;
+            status.run_count += 1;
+            status.last_finished_at_str = Some(finished.to_rfc3339());
+            status.last_duration_ms = Some((finished - started).num_milliseconds());
+            match result {
+                Ok(summary) => {
+                    status.last_result = Some(summary);
+                    status.last_error = None;
+                }
+                Err(e) => status.last_error = Some(e.to_string()),
+            }
+        });
+        true
+    }
+}
+
+// 将已到期（或超过保质期、激活截止时间、固定到期时间仍未激活）但尚未标记的卡密标记为过期
+async fn expire_cards(db: &Database) -> JobResult {
+    let collection = db.collection::<Card>("cards");
+    let now = Utc::now().to_rfc3339();
+
+    let mut filter = CardQuery { status: Some(CardStatus::Expired), ..Default::default() }.to_filter();
+    filter.insert("is_expired", doc! { "$ne": true });
+    let options = FindOptions::builder().limit(JOB_BATCH_SIZE).build();
+    let cards: Vec<Card> = collection.find(filter, options).await?.try_collect().await?;
+
+    let mut expired = 0;
+    for mut card in cards {
+        let Some(id) = card.id else { continue };
+        let result = collection
+            .update_one(
+                doc! { "_id": id, "is_expired": { "$ne": true } },
+                doc! { "$set": { "is_expired": true, "expired_at_str": &now } },
+                None,
+            )
+            .await?;
+        if result.modified_count == 0 {
+            continue;
+        }
+        card.is_expired = true;
+        card.expired_at_str = Some(now.clone());
+        dispatch_event(db, EVENT_CARD_EXPIRED, card_event_data(&card)).await;
+        expired += 1;
+    }
+
+    Ok(format!("{} cards expired", expired))
+}
+
+// 对即将到期的卡密发送提醒事件，每张卡只提醒一次
+async fn notify_expiring(db: &Database) -> JobResult {
+    let collection = db.collection::<Card>("cards");
+    let now = Utc::now();
+
+    let filter = doc! {
+        "is_activated": true,
+        "expiry_notified": { "$ne": true },
+        "expires_at_str": {
+            "$gt": now.to_rfc3339(),
+            "$lte": (now + Duration::days(EXPIRY_NOTICE_DAYS)).to_rfc3339(),
+        },
+        "deleted_at_str": null,
+    };
+    let options = FindOptions::builder().limit(JOB_BATCH_SIZE).build();
+    let cards: Vec<Card> = collection.find(filter, options).await?.try_collect().await?;
+
+    let mut notified = 0;
+    for card in cards {
+        let Some(id) = card.id else { continue };
+        let result = collection
+            .update_one(
+                doc! { "_id": id, "expiry_notified": { "$ne": true } },
+                doc! { "$set": { "expiry_notified": true } },
+                None,
+            )
+            .await?;
+        if result.modified_count == 0 {
+            continue;
+        }
+        dispatch_event(db, EVENT_CARD_EXPIRING, card_event_data(&card)).await;
+        notified += 1;
+    }
+
+    Ok(format!("{} expiry notices sent", notified))
+}
+
+// 删除超过保留期的已完成投递记录
+async fn prune_deliveries(db: &Database, retention_days: i64) -> JobResult {
+    let cutoff = (Utc::now() - Duration::days(retention_days)).to_rfc3339();
+
+    let filter = doc! {
+        "status": { "$in": [
+            bson::to_bson(&DeliveryStatus::Succeeded)?,
+            bson::to_bson(&DeliveryStatus::Failed)?,
+        ] },
+        "created_at_str": { "$lt": cutoff },
+    };
+    let result = db
+        .collection::<bson::Document>("webhook_deliveries")
+        .delete_many(filter, None)
+        .await?;
+
+    Ok(format!("{} delivery logs pruned", result.deleted_count))
+}
+
+// 彻底删除在回收站中超过保留期的卡密和用户，连同卡密的所有者凭证、换绑验证码和换绑记录，以及用户的 API 密钥；
+// 仍有卡密的用户保留在回收站中，待其卡密全部彻底删除后再清除
+async fn purge_trash(db: &Database, retention_days: i64) -> JobResult {
+    let cutoff = (Utc::now() - Duration::days(retention_days)).to_rfc3339();
+    let filter = doc! { "deleted_at_str": { "$ne": null, "$lt": cutoff } };
+
+    // 先删除依赖卡密的记录再删除卡密，中途失败时下次运行仍能找到这些卡密
+    let cards = db.collection::<bson::Document>("cards");
+    let mut purged_cards = 0;
+    loop {
+        let ids = find_ids(&cards, filter.clone(), Some(JOB_BATCH_SIZE)).await?;
+        if ids.is_empty() {
+            break;
+        }
+        let card_ids: Vec<String> = ids.iter().map(|id| id.to_hex()).collect();
+        for name in ["card_owners", "card_rebinds"] {
+            db.collection::<bson::Document>(name)
+                .delete_many(doc! { "card_id": { "$in": &card_ids } }, None)
+                .await?;
+        }
+        db.collection::<bson::Document>("rebind_codes")
+            .delete_many(doc! { "_id": { "$in": &card_ids } }, None)
+            .await?;
+        purged_cards += cards.delete_many(doc! { "_id": { "$in": &ids } }, None).await?.deleted_count;
+    }
+
+    // 用户创建的卡密可能仍是有效授权，仍有卡密的用户不清除
+    let users = db.collection::<bson::Document>("users");
+    let (mut purged_users, mut kept_users) = (0, 0);
+    for id in find_ids(&users, filter, None).await? {
+        let user_id = id.to_hex();
+        if cards.count_documents(doc! { "created_by": &user_id }, None).await? > 0 {
+            kept_users += 1;
+            continue;
+        }
+        db.collection::<bson::Document>("api_keys")
+            .delete_many(doc! { "user_id": &user_id }, None)
+            .await?;
+        purged_users += users.delete_one(doc! { "_id": id }, None).await?.deleted_count;
+    }
+
+    let mut summary = format!("{} cards and {} users purged", purged_cards, purged_users);
+    if kept_users > 0 {
+        summary.push_str(&format!(", {} users kept because they still have cards", kept_users));
+    }
+    Ok(summary)
+}
+
+// 查找符合条件的文档ID
+async fn find_ids(
+    collection: &Collection<bson::Document>,
+    filter: bson::Document,
+    limit: Option<i64>,
+) -> Result<Vec<ObjectId>, mongodb::error::Error> {
+    let options = FindOptions::builder().projection(doc! { "_id": 1 }).limit(limit).build();
+    let docs: Vec<bson::Document> = collection.find(filter, options).await?.try_collect().await?;
+    Ok(docs.iter().filter_map(|d| d.get_object_id("_id").ok()).collect())
+}
+
+// 删除超过保留期的审计日志
+async fn prune_audit_logs(db: &Database, retention_days: i64) -> JobResult {
+    let cutoff = (Utc::now() - Duration::days(retention_days)).to_rfc3339();
+    let result = db
+        .collection::<bson::Document>("audit_logs")
+        .delete_many(doc! { "created_at_str": { "$lt": cutoff } }, None)
+        .await?;
+
+    Ok(format!("{} audit logs pruned", result.deleted_count))
+}
+
+// 清除已过期的最新令牌；记录过期时间之前签发的令牌没有过期时间，从令牌中读取后补充
+async fn purge_sessions(db: &Database, jwt_secret: &str) -> JobResult {
+    let collection = db.collection::<User>("users");
+    let now = Utc::now();
+    let clear = doc! { "$unset": { "last_token": "", "token_expires_at_str": "" } };
+
+    let expired = collection
+        .update_many(doc! { "token_expires_at_str": { "$lt": now.to_rfc3339() } }, clear.clone(), None)
+        .await?;
+    let mut purged = expired.modified_count;
+
+    let filter = doc! { "last_token": { "$type": "string" }, "token_expires_at_str": null };
+    let options = FindOptions::builder().limit(JOB_BATCH_SIZE).build();
+    let users: Vec<User> = collection.find(filter, options).await?.try_collect().await?;
+    for user in users {
+        let (Some(id), Some(token)) = (user.id, user.last_token.as_deref()) else { continue };
+        let expires_at = token_expiration(token, jwt_secret);
+        let update = match expires_at {
+            Some(expires_at) if expires_at > now => doc! { "$set": { "token_expires_at_str": expires_at.to_rfc3339() } },
+            // 已过期或无法解析（如签名密钥已更换）的令牌都已失效
+            _ => {
+                purged += 1;
+                clear.clone()
+            }
+        };
+        collection.update_one(doc! { "_id": id, "last_token": token }, update, None).await?;
+    }
+
+    Ok(format!("{} expired sessions purged", purged))
+}
+
+// 读取令牌的过期时间，签名无效时返回 None
+fn token_expiration(token: &str, jwt_secret: &str) -> Option<DateTime<Utc>> {
+    let mut validation = Validation::default();
+    validation.validate_exp = false;
+    let claims = decode::<Claims>(token, &DecodingKey::from_secret(jwt_secret.as_bytes()), &validation).ok()?.claims;
+    DateTime::from_timestamp(claims.exp as i64, 0)
+}
+
+// 检查日志文件大小并在需要时轮转
+fn rotate_logs(logger: &Logger) -> JobResult {
+    if logger.rotate_if_needed()? {
+        Ok("log file rotated".to_string())
+    } else {
+        Ok("log file below size limit".to_string())
+    }
+}
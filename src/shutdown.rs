@@ -0,0 +1,83 @@
+use actix_rt::{Arbiter, ArbiterHandle};
+use log::warn;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// 等待后台任务结束时的检查间隔
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// 停机协调器：收到停止信号后通知后台任务不再开始新的工作，并等待进行中的工作完成
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    active: Arc<AtomicUsize>,
+    runtime: ArbiterHandle,  // 主线程的运行时，HTTP 工作线程停止后仍继续运行
+}
+
+// 进行中的后台工作，释放时自动计数减一
+pub struct ActiveGuard {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    // 需在主线程的运行时中创建
+    pub fn new() -> Self {
+        Self {
+            requested: Arc::new(AtomicBool::new(false)),
+            active: Arc::new(AtomicUsize::new(0)),
+            runtime: Arbiter::current(),
+        }
+    }
+
+    // 是否已开始停机，后台循环据此停止开始新的工作
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    // 登记一项进行中的后台工作
+    pub fn track(&self) -> ActiveGuard {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ActiveGuard { active: self.active.clone() }
+    }
+
+    // 在主线程的运行时中执行后台工作并登记为进行中；请求处理函数中直接 spawn 的任务属于 HTTP 工作线程，
+    // 会在 HTTP 服务停止时被丢弃
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let active = self.track();
+        self.runtime.spawn(async move {
+            let _active = active;
+            future.await;
+        });
+    }
+
+    // 等待所有进行中的工作完成，超时返回 false
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let active = self.active.load(Ordering::SeqCst);
+            if active == 0 {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                warn!("Shutdown timed out with {} background tasks still running", active);
+                return false;
+            }
+            actix_web::rt::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::stream::TryStreamExt;
+use hmac::{Hmac, Mac};
+use log::{info, warn, error};
+use mongodb::bson::{self, doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use mongodb::Database;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::Sha256;
+use std::time::Instant;
+
+use crate::models::webhook::{DeliveryAttempt, DeliveryStatus, Webhook, WebhookDelivery};
+use crate::models::Card;
+use crate::shutdown::Shutdown;
+
+// 最大投递次数，超过后标记为失败
+pub const MAX_ATTEMPTS: i32 = 8;
+// 第一次重试的等待时间（秒），之后每次翻倍
+const RETRY_BASE_SECS: i64 = 30;
+// 重试等待时间上限（秒）
+const RETRY_MAX_SECS: i64 = 3600;
+// 投递进行中的租约时间，防止同一条记录被并发投递；每条记录在投递前单独认领，租约需长于单次请求超时
+const LEASE_SECS: i64 = 60;
+// 投递队列轮询间隔
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+// 每轮最多处理的投递数量
+const BATCH_SIZE: i64 = 50;
+// 单次请求超时时间
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const _: () = assert!(LEASE_SECS as u64 > REQUEST_TIMEOUT.as_secs() * 2);
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 生成随机签名密钥
+pub fn generate_secret() -> String {
+    let secret: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    format!("whsec_{}", secret)
+}
+
+// 计算签名：HMAC-SHA256(secret, "{timestamp}.{payload}")，十六进制编码
+pub fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// 计算第 attempts 次失败后的下次重试时间（指数退避）
+fn next_retry_at(now: DateTime<Utc>, attempts: i32) -> DateTime<Utc> {
+    let exponent = (attempts - 1).clamp(0, 16) as u32;
+    let delay = RETRY_BASE_SECS.saturating_mul(2_i64.pow(exponent)).min(RETRY_MAX_SECS);
+    now + Duration::seconds(delay)
+}
+
+// 构建卡密事件的数据部分
+pub fn card_event_data(card: &Card) -> serde_json::Value {
+    serde_json::json!({
+        "card_id": card.id.map(|id| id.to_hex()),
+        "card_number": card.card_number,
+        "duration_seconds": card.duration_seconds,
+        "duration_days": card.duration_days(),  // 兼容 v1，有效期的整天数（向下取整）
+        "is_activated": card.is_activated,
+        "activated_at": card.activated_at_str,
+        "expires_at": card.expires_at_str,
+        "is_revoked": card.is_revoked,
+        "revoked_at": card.revoked_at_str,
+        "deleted_at": card.deleted_at_str,  // 删除（移入回收站）时间，删除卡密时同样发送 card.revoked
+        "is_paused": card.is_paused,
+        "paused_remaining_seconds": card.paused_remaining_seconds,
+        "usage_limit": card.usage_limit,
+        "remaining_uses": card.remaining_uses,
+        "valid_from": card.valid_from_str,
+        "activate_before": card.activate_before_str,
+        "valid_until": card.valid_until_str,
+        "created_by": card.created_by,
+        "created_by_username": card.created_by_username,
+        "bindings": card.bindings,
+        "used_by_identifier": card.bindings.first().and_then(|binding| binding.identifier.as_deref()),  // 兼容 v1，第一个绑定设备的标识
+        "batch_id": card.batch_id,
+        "app_id": card.app_id,
+    })
+}
+
+// 为订阅了该事件的所有 Webhook 创建投递记录，由后台任务负责发送
+pub async fn dispatch_event(db: &Database, event: &str, data: serde_json::Value) {
+    let webhooks = match db
+        .collection::<Webhook>("webhooks")
+        .find(doc! { "enabled": true }, None)
+        .await
+    {
+        Ok(cursor) => match cursor.try_collect::<Vec<Webhook>>().await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                error!("Error collecting webhooks for event '{}': {}", event, e);
+                return;
+            }
+        },
+        Err(e) => {
+            error!("Error finding webhooks for event '{}': {}", event, e);
+            return;
+        }
+    };
+
+    for webhook in webhooks.iter().filter(|w| w.subscribes_to(event)) {
+        if let Err(e) = enqueue(db, webhook, event, data.clone()).await {
+            error!("Failed to enqueue '{}' delivery for webhook '{}': {}", event, webhook.url, e);
+        }
+    }
+}
+
+// 为指定 Webhook 创建一条投递记录
+pub async fn enqueue(
+    db: &Database,
+    webhook: &Webhook,
+    event: &str,
+    data: serde_json::Value,
+) -> Result<WebhookDelivery, mongodb::error::Error> {
+    let now = Utc::now();
+    let delivery_id = ObjectId::new();
+    let payload = serde_json::json!({
+        "id": delivery_id.to_hex(),
+        "event": event,
+        "created_at": now.to_rfc3339(),
+        "data": data,
+    })
+    .to_string();
+
+    let delivery = WebhookDelivery {
+        id: Some(delivery_id),
+        webhook_id: webhook.id.map(|id| id.to_hex()).unwrap_or_default(),
+        event: event.to_string(),
+        payload,
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at_str: now.to_rfc3339(),
+        last_status_code: None,
+        last_error: None,
+        logs: Vec::new(),
+        created_at_str: now.to_rfc3339(),
+        updated_at_str: now.to_rfc3339(),
+    };
+
+    db.collection::<WebhookDelivery>("webhook_deliveries")
+        .insert_one(&delivery, None)
+        .await?;
+    info!("Queued '{}' delivery {} for webhook '{}'", event, delivery_id.to_hex(), webhook.url);
+    Ok(delivery)
+}
+
+// 启动后台投递任务
+pub fn spawn_worker(db: Database, shutdown: Shutdown) {
+    actix_web::rt::spawn(async move {
+        let client = match build_client() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to build webhook HTTP client: {}", e);
+                return;
+            }
+        };
+
+        info!("Webhook delivery worker started");
+        let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            // 停机后由 flush 接管剩余的投递
+            if shutdown.is_requested() {
+                break;
+            }
+            let _active = shutdown.track();
+            if let Err(e) = process_due_deliveries(&db, &client).await {
+                error!("Error processing webhook deliveries: {}", e);
+            }
+        }
+        info!("Webhook delivery worker stopped");
+    });
+}
+
+fn build_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()
+}
+
+// 停机时投递所有已到期的记录，直到队列中没有到期记录；未完成的记录保留在队列中，重启后继续投递
+pub async fn flush(db: &Database) -> Result<usize, Box<dyn std::error::Error>> {
+    let client = build_client()?;
+    let mut total = 0;
+    loop {
+        let processed = process_due_deliveries(db, &client).await?;
+        if processed == 0 {
+            return Ok(total);
+        }
+        total += processed;
+    }
+}
+
+// 处理所有到期的投递记录
+pub async fn process_due_deliveries(db: &Database, client: &reqwest::Client) -> Result<usize, mongodb::error::Error> {
+    let deliveries = db.collection::<WebhookDelivery>("webhook_deliveries");
+    let now = Utc::now();
+
+    let filter = doc! {
+        "status": bson::to_bson(&DeliveryStatus::Pending)?,
+        "next_attempt_at_str": { "$lte": now.to_rfc3339() },
+    };
+    let options = FindOptions::builder()
+        .sort(doc! { "next_attempt_at_str": 1 })
+        .limit(BATCH_SIZE)
+        .build();
+    let due: Vec<WebhookDelivery> = deliveries.find(filter, options).await?.try_collect().await?;
+
+    let mut processed = 0;
+    for delivery in due {
+        let Some(delivery_id) = delivery.id else { continue };
+
+        // 通过租约认领该记录，避免多个实例重复投递；租约从认领时开始计算，
+        // 同一批中排在后面的记录不会因前面的投递耗时而在认领时租约已过期
+        let lease_until = (Utc::now() + Duration::seconds(LEASE_SECS)).to_rfc3339();
+        let claim = deliveries
+            .update_one(
+                doc! { "_id": delivery_id, "next_attempt_at_str": &delivery.next_attempt_at_str },
+                doc! { "$set": { "next_attempt_at_str": lease_until } },
+                None,
+            )
+            .await?;
+        if claim.modified_count == 0 {
+            continue;
+        }
+
+        deliver(db, client, delivery).await?;
+        processed += 1;
+    }
+
+    Ok(processed)
+}
+
+// 投递一条记录并记录结果
+async fn deliver(db: &Database, client: &reqwest::Client, delivery: WebhookDelivery) -> Result<(), mongodb::error::Error> {
+    let deliveries = db.collection::<WebhookDelivery>("webhook_deliveries");
+    let delivery_id = delivery.id.expect("claimed delivery has an id");
+
+    let webhook = match ObjectId::parse_str(&delivery.webhook_id) {
+        Ok(oid) => db.collection::<Webhook>("webhooks").find_one(doc! { "_id": oid }, None).await?,
+        Err(_) => None,
+    };
+
+    // 已删除或已停用的 Webhook 不再投递，记录直接标记为失败
+    let webhook = webhook.filter(|w| w.enabled);
+    let started = Instant::now();
+    let attempted_at = Utc::now();
+    let (status_code, error) = match webhook.as_ref() {
+        Some(webhook) => {
+            let timestamp = attempted_at.timestamp();
+            let signature = sign_payload(&webhook.secret, timestamp, &delivery.payload);
+            let result = client
+                .post(&webhook.url)
+                .header("Content-Type", "application/json")
+                .header("X-Webhook-Event", &delivery.event)
+                .header("X-Webhook-Delivery", delivery_id.to_hex())
+                .header("X-Webhook-Timestamp", timestamp.to_string())
+                .header("X-Webhook-Signature", format!("sha256={}", signature))
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+            match result {
+                Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+                Ok(response) => (
+                    Some(response.status().as_u16() as i32),
+                    Some(format!("Unexpected response status {}", response.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            }
+        }
+        None => (None, Some("Webhook not found or disabled".to_string())),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let log = DeliveryAttempt {
+        attempted_at_str: attempted_at.to_rfc3339(),
+        status_code,
+        error: error.clone(),
+        duration_ms: started.elapsed().as_millis() as i64,
+    };
+
+    let (status, next_attempt_at) = match &error {
+        None => {
+            info!("Delivered '{}' {} (attempt {})", delivery.event, delivery_id.to_hex(), attempts);
+            (DeliveryStatus::Succeeded, attempted_at)
+        }
+        Some(e) if attempts >= MAX_ATTEMPTS || webhook.is_none() => {
+            error!("Delivery {} failed permanently after {} attempts: {}", delivery_id.to_hex(), attempts, e);
+            (DeliveryStatus::Failed, attempted_at)
+        }
+        Some(e) => {
+            let next = next_retry_at(attempted_at, attempts);
+            warn!("Delivery {} failed (attempt {}), retrying at {}: {}", delivery_id.to_hex(), attempts, next.to_rfc3339(), e);
+            (DeliveryStatus::Pending, next)
+        }
+    };
+
+    let update = doc! {
+        "$set": {
+            "status": bson::to_bson(&status)?,
+            "attempts": attempts,
+            "next_attempt_at_str": next_attempt_at.to_rfc3339(),
+            "last_status_code": status_code,
+            "last_error": error,
+            "updated_at_str": Utc::now().to_rfc3339(),
+        },
+        "$push": { "logs": bson::to_bson(&log)? },
+    };
+    deliveries.update_one(doc! { "_id": delivery_id }, update, None).await?;
+    Ok(())
+}
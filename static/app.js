@@ -38,11 +38,21 @@ const app = createApp({
             });
         });
         const showGenerateCardModal = ref(false);
+        const showTrash = ref(false);
+        const trashedCards = ref([]);
         const cardForm = ref({
-            duration_days: 30,
-            count: 1
+            duration: '30d',
+            count: 1,
+            usage_limit: null,
+            shelf_life: '',
+            valid_from: '',
+            activate_before: '',
+            valid_until: ''
         });
         
+        // Stats state
+        const stats = ref(null);
+        
         // Users state
         const users = ref([]);
         const showCreateUserModal = ref(false);
@@ -61,7 +71,7 @@ const app = createApp({
                 logger.info('Attempting to login with username:', loginForm.value.username);
                 
                 // 修改登录请求的 URL 路径
-                const response = await axios.post('/api/users/login', loginForm.value);
+                const response = await axios.post('/api/v1/users/login', loginForm.value);
                 
                 token.value = response.data.token;
                 currentUser.value = response.data.user;
@@ -103,6 +113,9 @@ const app = createApp({
         // 切换视图
         const switchView = (view) => {
             currentView.value = view;
+            if (view === 'stats') {
+                fetchStats();
+            }
         };
         
         // 格式化日期
@@ -116,10 +129,25 @@ const app = createApp({
             }
         };
         
+        // 格式化时长（秒），如 "7天"、"1小时30分钟"
+        const formatDuration = (seconds) => {
+            if (!seconds) return '不限时间';
+            const units = [[86400, '天'], [3600, '小时'], [60, '分钟'], [1, '秒']];
+            let rest = seconds;
+            let text = '';
+            for (const [unit, name] of units) {
+                if (rest >= unit) {
+                    text += Math.floor(rest / unit) + name;
+                    rest %= unit;
+                }
+            }
+            return text;
+        };
+        
         // 获取所有卡密
         const fetchCards = async () => {
             try {
-                const response = await axios.get('/api/cards');
+                const response = await axios.get('/api/v1/cards');
                 cards.value = response.data;
                 logger.info('Fetched cards:', cards.value);
             } catch (error) {
@@ -133,14 +161,27 @@ const app = createApp({
         // 生成卡密
         const generateCard = async () => {
             try {
-                const response = await axios.post('/api/cards/generate', cardForm.value);
+                // 本地时间转换为 RFC3339，留空表示不限
+                const toRfc3339 = (value) => value ? new Date(value).toISOString() : null;
+                const response = await axios.post('/api/v1/cards/generate', {
+                    ...cardForm.value,
+                    shelf_life: cardForm.value.shelf_life || null,
+                    valid_from: toRfc3339(cardForm.value.valid_from),
+                    activate_before: toRfc3339(cardForm.value.activate_before),
+                    valid_until: toRfc3339(cardForm.value.valid_until)
+                });
                 logger.info('Generated cards:', response.data);
                 await fetchCards();
                 showGenerateCardModal.value = false;
                 // 重置表单
                 cardForm.value = {
-                    duration_days: 30,
-                    count: 1
+                    duration: '30d',
+                    count: 1,
+                    usage_limit: null,
+                    shelf_life: '',
+                    valid_from: '',
+                    activate_before: '',
+                    valid_until: ''
                 };
             } catch (error) {
                 logger.error('Error generating card:', error);
@@ -170,7 +211,7 @@ const app = createApp({
         
         // 修改删除卡密函数
         const deleteCard = async (card) => {
-            if (!confirm('确定要删除这个卡密吗？')) return;
+            if (!confirm('确定要删除这个卡密吗？删除后可在回收站中恢复。')) return;
             
             try {
                 // 获取卡密ID
@@ -184,7 +225,7 @@ const app = createApp({
                 
                 logger.info('Attempting to delete card with ID:', cardId);
                 
-                await axios.delete(`/api/cards/${cardId}`);
+                await axios.delete(`/api/v1/cards/${cardId}`);
                 logger.info('Deleted card successfully');
                 await fetchCards();
             } catch (error) {
@@ -197,13 +238,64 @@ const app = createApp({
             }
         };
         
+        // 获取回收站中的卡密
+        const fetchTrash = async () => {
+            try {
+                const response = await axios.get('/api/v1/cards/trash');
+                trashedCards.value = response.data;
+            } catch (error) {
+                logger.error('Error fetching trash:', error);
+                if (error.response && error.response.status === 401) {
+                    logout();
+                }
+            }
+        };
+        
+        // 切换回收站视图
+        const toggleTrash = async () => {
+            showTrash.value = !showTrash.value;
+            if (showTrash.value) {
+                await fetchTrash();
+            }
+        };
+        
+        // 从回收站恢复卡密
+        const restoreCard = async (card) => {
+            try {
+                await axios.post(`/api/v1/cards/${getCardId(card)}/restore`);
+                logger.info('Restored card:', card.card_number);
+                await fetchTrash();
+                await fetchCards();
+            } catch (error) {
+                logger.error('Error restoring card:', error);
+                alert('恢复卡密失败: ' + (error.response?.data || error.message));
+            }
+        };
+        
+        // 暂停或恢复卡密，暂停期间不计时
+        const togglePause = async (card) => {
+            const action = card.is_paused ? 'resume' : 'pause';
+            try {
+                await axios.post(`/api/v1/cards/${getCardId(card)}/${action}`);
+                logger.info(`Card ${action}d:`, card.card_number);
+                await fetchCards();
+            } catch (error) {
+                logger.error(`Error on card ${action}:`, error);
+                if (error.response && error.response.status === 401) {
+                    logout();
+                } else {
+                    alert((card.is_paused ? '恢复' : '暂停') + '卡密失败: ' + (error.response?.data || error.message));
+                }
+            }
+        };
+        
         // 修复导出卡密功能 - 方法二：使用 Blob 和 URL.createObjectURL
         const exportCards = async () => {
             try {
                 logger.info('Exporting cards...');
                 
                 // 直接使用 axios 发送请求，它会自动带上认证头
-                const response = await axios.get('/api/cards/export', {
+                const response = await axios.get('/api/v1/cards/export', {
                     responseType: 'blob' // 指定响应类型为 blob
                 });
                 
@@ -229,10 +321,29 @@ const app = createApp({
             }
         };
         
+        // 获取统计数据
+        const fetchStats = async () => {
+            try {
+                const response = await axios.get('/api/v1/stats');
+                stats.value = response.data;
+                logger.info('Fetched stats:', stats.value);
+            } catch (error) {
+                logger.error('Error fetching stats:', error);
+                if (error.response && error.response.status === 401) {
+                    logout();
+                }
+            }
+        };
+        
+        // 格式化激活率
+        const formatRate = (rate) => {
+            return (rate * 100).toFixed(1) + '%';
+        };
+        
         // 获取所有用户
         const fetchUsers = async () => {
             try {
-                const response = await axios.get('/api/users');
+                const response = await axios.get('/api/v1/users');
                 users.value = response.data;
                 logger.info('Fetched users:', users.value);
             } catch (error) {
@@ -291,11 +402,11 @@ const app = createApp({
                 
                 if (editingUser.value) {
                     // 更新现有用户
-                    await axios.put(`/api/users/${editingUser.value.id}`, formData);
+                    await axios.put(`/api/v1/users/${editingUser.value.id}`, formData);
                     logger.info('Updated user:', editingUser.value.id);
                 } else {
                     // 创建新用户
-                    await axios.post('/api/users/register', formData)
+                    await axios.post('/api/v1/users/register', formData)
                       .catch(error => {
                         logger.error('详细错误信息:', error.response?.data || error);
                         throw error;  // 继续抛出错误以便后续处理
@@ -319,7 +430,7 @@ const app = createApp({
             if (!confirm('确定要删除这个用户吗？')) return;
             
             try {
-                await axios.delete(`/api/users/${userId}`);
+                await axios.delete(`/api/v1/users/${userId}`);
                 logger.info('Deleted user:', userId);
                 await fetchUsers();
             } catch (error) {
@@ -338,22 +449,40 @@ const app = createApp({
             return expiresAt < new Date();
         };
         
+        // 未激活的卡密是否已超过保质期、激活截止时间或固定到期时间
+        const isShelfExpired = (card) => {
+            const now = new Date();
+            return [card.shelf_expires_at_str, card.activate_before_str, card.valid_until_str]
+                .some(deadline => !!deadline && new Date(deadline) <= now);
+        };
+        
         // 获取卡密状态文本
         const getCardStatusText = (card) => {
-            if (!card.is_activated) return '未激活';
+            if (!card.is_activated) return isShelfExpired(card) ? '已过期' : '未激活';
+            if (card.is_paused) return '已暂停';
             if (isExpired(card)) return '已过期';
-            if (card.used_by_identifier) return '已使用';
+            if (card.usage_limit && card.remaining_uses <= 0) return '已用完';
+            if (card.bindings && card.bindings.length) return '已使用';
             return '已激活';
         };
         
         // 获取卡密状态样式类
         const getCardStatusClass = (card) => {
-            if (!card.is_activated) return 'bg-success';
+            if (!card.is_activated) return isShelfExpired(card) ? 'bg-danger' : 'bg-success';
+            if (card.is_paused) return 'bg-secondary';
             if (isExpired(card)) return 'bg-danger';
-            if (card.used_by_identifier) return 'bg-info';
+            if (card.usage_limit && card.remaining_uses <= 0) return 'bg-danger';
+            if (card.bindings && card.bindings.length) return 'bg-info';
             return 'bg-warning';
         };
         
+        // 已绑定设备的显示文本：优先显示使用者标识，只有设备指纹时显示机器ID
+        const getCardDevices = (card) => {
+            return (card.bindings || [])
+                .map(binding => binding.identifier || (binding.device && binding.device.machine_id) || '未知设备')
+                .join(', ');
+        };
+        
         // 初始化
         onMounted(() => {
             // 如果已登录，设置 axios 默认请求头并加载数据
@@ -392,6 +521,16 @@ const app = createApp({
             generateCard,
             deleteCard,
             exportCards,
+            showTrash,
+            trashedCards,
+            toggleTrash,
+            restoreCard,
+            togglePause,
+            
+            // Stats
+            stats,
+            fetchStats,
+            formatRate,
             
             // Users
             users,
@@ -406,9 +545,11 @@ const app = createApp({
             
             // Utils
             formatDate,
+            formatDuration,
             isExpired,
             getCardStatusText,
-            getCardStatusClass
+            getCardStatusClass,
+            getCardDevices
         };
     }
 });